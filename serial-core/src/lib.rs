@@ -19,10 +19,12 @@
 // OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION
 // WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
 
+use std::cmp;
 use std::error::Error as StdError;
 use std::fmt;
 use std::io;
-use std::time::Duration;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 
 pub use BaudRate::*;
 pub use CharSize::*;
@@ -41,6 +43,9 @@ pub mod prelude {
     pub use {SerialPort, SerialPortSettings};
 }
 
+#[cfg(feature = "testing")]
+pub mod testing;
+
 /// A type for results generated by interacting with serial ports.
 ///
 /// The `Err` type is hard-wired to [`serial_core::Error`](struct.Error.html).
@@ -61,6 +66,9 @@ pub enum ErrorKind {
     /// A parameter was incorrect.
     InvalidInput,
 
+    /// The operation is not supported by the device or platform.
+    Unsupported,
+
     /// An I/O error occured.
     ///
     /// The type of I/O error is determined by the inner `io::ErrorKind`.
@@ -68,10 +76,19 @@ pub enum ErrorKind {
 }
 
 /// An error type for serial port operations.
+///
+/// Errors that originate from the operating system keep the underlying `io::Error`, which is
+/// available through `source()` and `raw_os_error()`. Errors may also record the operation that
+/// failed and the path of the device it was performed on, both of which are included when the
+/// error is displayed, e.g., `TCSETS2 on /dev/ttyUSB0: Invalid argument`.
 #[derive(Debug)]
 pub struct Error {
     kind: ErrorKind,
     description: String,
+    operation: Option<String>,
+    path: Option<PathBuf>,
+    source: Option<io::Error>,
+    transferred: Option<usize>,
 }
 
 impl Error {
@@ -79,17 +96,80 @@ impl Error {
         Error {
             kind: kind,
             description: description.into(),
+            operation: None,
+            path: None,
+            source: None,
+            transferred: None,
+        }
+    }
+
+    /// Attaches the underlying I/O error that caused this error.
+    pub fn with_source(mut self, source: io::Error) -> Self {
+        self.source = Some(source);
+        self
+    }
+
+    /// Records the name of the operation that failed, e.g., `TCSETS2`.
+    pub fn with_operation<T: Into<String>>(mut self, operation: T) -> Self {
+        self.operation = Some(operation.into());
+        self
+    }
+
+    /// Records the path of the device on which the error occurred.
+    ///
+    /// A path that has already been recorded is not replaced.
+    pub fn with_path<P: AsRef<Path>>(mut self, path: P) -> Self {
+        if self.path.is_none() {
+            self.path = Some(path.as_ref().to_path_buf());
         }
+
+        self
+    }
+
+    /// Records the number of bytes that were transferred before the error occurred.
+    pub fn with_transferred(mut self, transferred: usize) -> Self {
+        self.transferred = Some(transferred);
+        self
     }
 
     /// Returns the corresponding `ErrorKind` for this error.
     pub fn kind(&self) -> ErrorKind {
         self.kind
     }
+
+    /// Returns the name of the operation that failed, if it is known.
+    pub fn operation(&self) -> Option<&str> {
+        self.operation.as_ref().map(|s| &s[..])
+    }
+
+    /// Returns the path of the device on which the error occurred, if it is known.
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_ref().map(|p| p.as_ref())
+    }
+
+    /// Returns the number of bytes that were transferred before the error occurred, if it is known.
+    ///
+    /// This is recorded by operations that transfer a sequence of bytes, such as
+    /// [`SerialPort::read_exact_deadline()`](trait.SerialPort.html#method.read_exact_deadline).
+    pub fn transferred(&self) -> Option<usize> {
+        self.transferred
+    }
+
+    /// Returns the OS error code that caused this error, if there is one.
+    pub fn raw_os_error(&self) -> Option<i32> {
+        self.source.as_ref().and_then(|e| e.raw_os_error())
+    }
 }
 
 impl fmt::Display for Error {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> std::result::Result<(), fmt::Error> {
+        match (self.operation.as_ref(), self.path.as_ref()) {
+            (Some(operation), Some(path)) => try!(write!(fmt, "{} on {}: ", operation, path.display())),
+            (Some(operation), None)       => try!(write!(fmt, "{}: ", operation)),
+            (None, Some(path))            => try!(write!(fmt, "{}: ", path.display())),
+            (None, None)                  => (),
+        }
+
         fmt.write_str(&self.description)
     }
 }
@@ -98,23 +178,44 @@ impl StdError for Error {
     fn description(&self) -> &str {
         &self.description
     }
+
+    fn source(&self) -> Option<&(StdError + 'static)> {
+        self.source.as_ref().map(|source| source as &(StdError + 'static))
+    }
 }
 
 impl From<io::Error> for Error {
+    /// Converts an `io::Error` into a serial port error.
+    ///
+    /// An `io::Error` that was converted from a serial port error is unwrapped.
     fn from(io_error: io::Error) -> Error {
-        Error::new(ErrorKind::Io(io_error.kind()), format!("{}", io_error))
+        if io_error.get_ref().map(|e| e.is::<Error>()) == Some(true) {
+            return *io_error.into_inner().unwrap().downcast::<Error>().unwrap();
+        }
+
+        Error::new(ErrorKind::Io(io_error.kind()), format!("{}", io_error)).with_source(io_error)
     }
 }
 
 impl From<Error> for io::Error {
+    /// Converts a serial port error into an `io::Error`.
+    ///
+    /// The original error is preserved as the inner error of the `io::Error`, so its context and
+    /// OS error code can be recovered with `io::Error::get_ref()` or `io::Error::into_inner()`.
+    /// Converting the `io::Error` back into an `Error` recovers the original error, including its
+    /// kind.
+    ///
+    /// `ErrorKind::Unsupported` has no counterpart in `io::ErrorKind` before Rust 1.53, so it's
+    /// reported as `io::ErrorKind::Other`.
     fn from(error: Error) -> io::Error {
         let kind = match error.kind {
             ErrorKind::NoDevice     => io::ErrorKind::NotFound,
             ErrorKind::InvalidInput => io::ErrorKind::InvalidInput,
+            ErrorKind::Unsupported  => io::ErrorKind::Other,
             ErrorKind::Io(kind)     => kind,
         };
 
-        io::Error::new(kind, error.description)
+        io::Error::new(kind, error)
     }
 }
 
@@ -233,6 +334,112 @@ impl BaudRate {
     }
 }
 
+/// A baud rate produced by a UART, compared to the requested baud rate.
+#[derive(Debug,Copy,Clone,PartialEq,Eq)]
+pub struct EffectiveBaudRate {
+    /// The requested baud rate.
+    pub requested: usize,
+
+    /// The baud rate that's produced.
+    pub actual: usize,
+}
+
+impl EffectiveBaudRate {
+    /// Returns the difference between the actual and requested baud rates as a percentage of the
+    /// requested baud rate. The error is positive if the actual baud rate is faster.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use serial_core::EffectiveBaudRate;
+    /// let rate = EffectiveBaudRate { requested: 115200, actual: 111111 };
+    /// assert_eq!(format!("{:.2}", rate.error_percent()), "-3.55");
+    /// ```
+    pub fn error_percent(&self) -> f64 {
+        if self.requested == 0 {
+            return 0.0;
+        }
+
+        (self.actual as f64 - self.requested as f64) * 100.0 / self.requested as f64
+    }
+}
+
+/// A UART clock that produces baud rates by dividing a base rate.
+///
+/// The base rate is the baud rate with a divisor of one, which is the UART's clock divided by its
+/// oversampling rate (16 for most UARTs). Linux reports it as the `baud_base` of a serial port.
+#[derive(Debug,Copy,Clone,PartialEq,Eq)]
+pub struct BaudClock {
+    /// The baud rate with a divisor of one.
+    pub base: u32,
+
+    /// The largest divisor supported by the UART.
+    pub max_divisor: u32,
+
+    /// The number of fractional bits of the divisor, e.g., `3` for a UART whose divisor can be set
+    /// in eighths.
+    pub fraction_bits: u32,
+}
+
+impl BaudClock {
+    /// Creates a clock with integer divisors of up to 65,535, like a 16550 UART.
+    pub fn new(base: u32) -> Self {
+        BaudClock {
+            base: base,
+            max_divisor: 65535,
+            fraction_bits: 0,
+        }
+    }
+
+    /// Returns the achievable baud rate that's nearest to `requested`.
+    ///
+    /// This function returns `None` if the requested baud rate or the base rate is zero, or if the
+    /// divisors can't be computed without overflowing, e.g., with 64 or more `fraction_bits`.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use serial_core::{BaudClock, BaudRate};
+    /// // an AVR microcontroller running at 16 MHz
+    /// let rate = BaudClock::new(16_000_000 / 16).nearest(BaudRate::Baud115200).unwrap();
+    /// assert_eq!(rate.actual, 111111);
+    /// ```
+    pub fn nearest(&self, requested: BaudRate) -> Option<EffectiveBaudRate> {
+        let speed = requested.speed() as u64;
+
+        if speed == 0 || self.base == 0 {
+            return None;
+        }
+
+        // divisors are computed in units of the divisor's fractional part
+        let scale = 1u64.checked_shl(self.fraction_bits)?;
+
+        let base = (self.base as u64).checked_mul(scale);
+        let max = (self.max_divisor as u64).checked_mul(scale);
+
+        let (base, min, max) = match (base, max) {
+            (Some(base), Some(max)) => (base, scale, cmp::max(max, scale)),
+            _ => return None,
+        };
+
+        let divisor = base / speed;
+        let rates = [divisor, divisor.saturating_add(1)].iter()
+            .map(|&divisor| cmp::min(cmp::max(divisor, min), max))
+            .map(|divisor| base.checked_add(divisor / 2).map(|base| base / divisor))
+            .collect::<Option<Vec<u64>>>();
+
+        // `u64::abs_diff()` requires Rust 1.60
+        let nearest = rates.and_then(|rates| {
+            rates.into_iter().min_by_key(|&rate| cmp::max(rate, speed) - cmp::min(rate, speed))
+        });
+
+        nearest.map(|actual| EffectiveBaudRate {
+            requested: speed as usize,
+            actual: actual as usize,
+        })
+    }
+}
+
 /// Number of bits per character.
 #[derive(Debug,Copy,Clone,PartialEq,Eq)]
 pub enum CharSize {
@@ -293,6 +500,94 @@ pub enum FlowControl {
 
     /// Flow control using RTS/CTS signals.
     FlowHardware,
+
+    /// Flow control using DTR/DSR signals.
+    ///
+    /// Transmission is suspended while DSR is not asserted. Drivers that don't support DTR/DSR
+    /// flow control may emulate it, with some limitations. See the documentation of each port
+    /// type for details.
+    FlowDtrDsr,
+}
+
+/// The states of the modem control signals that are outputs of a serial port.
+#[derive(Debug,Copy,Clone,PartialEq,Eq)]
+pub struct ModemControl {
+    /// Whether the DTR (Data Terminal Ready) signal is asserted.
+    pub dtr: bool,
+
+    /// Whether the RTS (Request To Send) signal is asserted.
+    pub rts: bool,
+}
+
+/// The standard character that resumes transmission with software flow control (DC1).
+pub const XON: u8 = 0x11;
+
+/// The standard character that suspends transmission with software flow control (DC3).
+pub const XOFF: u8 = 0x13;
+
+// Timeouts longer than this are treated as infinite by `Timeout::deadline()`, since adding them to
+// an `Instant` may overflow. `Instant::checked_add()` requires Rust 1.34.
+const MAX_DEADLINE_SECS: u64 = 100 * 365 * 24 * 60 * 60;
+
+/// Timeouts for serial port I/O operations.
+///
+/// All serial port implementations follow the same contract for reads and writes with a timeout:
+///
+/// * An operation completes as soon as any data can be transferred, returning `Ok(n)` with the
+///   number of bytes transferred. This may be less than the size of the buffer.
+/// * If no data could be transferred before the timeout expired, the operation returns an error of
+///   kind `io::ErrorKind::TimedOut`, or `io::ErrorKind::WouldBlock` with a `NonBlocking` timeout.
+/// * `Ok(0)` is returned only for an empty buffer, or by a read when the device has reached end of
+///   file, e.g., when the other side of a pseudoterminal was closed.
+#[derive(Debug,Copy,Clone,PartialEq,Eq)]
+pub enum Timeout {
+    /// Operations wait until data can be transferred.
+    Infinite,
+
+    /// Operations transfer any data that can be transferred immediately without waiting.
+    NonBlocking,
+
+    /// Operations wait up to the given duration for data to be transferred.
+    Duration(Duration),
+}
+
+impl Timeout {
+    /// Returns the longest time an operation waits, or `None` if it may wait indefinitely.
+    ///
+    /// A `NonBlocking` timeout waits for a duration of zero.
+    pub fn as_duration(&self) -> Option<Duration> {
+        match *self {
+            Timeout::Infinite => None,
+            Timeout::NonBlocking => Some(Duration::from_secs(0)),
+            Timeout::Duration(duration) => Some(duration),
+        }
+    }
+
+    /// Returns the instant at which an operation that starts now times out, or `None` if it may
+    /// wait indefinitely.
+    ///
+    /// Durations longer than a century, such as `Duration::from_secs(u64::MAX)` used as a
+    /// practically infinite timeout, are treated as infinite rather than overflowing `Instant`.
+    pub fn deadline(&self) -> Option<Instant> {
+        match self.as_duration() {
+            Some(duration) if duration.as_secs() < MAX_DEADLINE_SECS => Some(Instant::now() + duration),
+            _ => None,
+        }
+    }
+
+    /// Returns the kind of error that indicates that an operation timed out.
+    pub fn error_kind(&self) -> io::ErrorKind {
+        match *self {
+            Timeout::NonBlocking => io::ErrorKind::WouldBlock,
+            _ => io::ErrorKind::TimedOut,
+        }
+    }
+}
+
+impl From<Duration> for Timeout {
+    fn from(duration: Duration) -> Self {
+        Timeout::Duration(duration)
+    }
 }
 
 /// A trait for implementing serial devices.
@@ -309,9 +604,11 @@ pub enum FlowControl {
 /// manipulated in memory before being commited to the device with `write_settings()`.
 ///
 /// Types that implement `SerialDevice` must also implement `std::io::Read` and `std::io::Write`.
-/// The `read()` and `write()` operations of these traits should honor the timeout that has been
-/// set with the most recent successful call to `set_timeout()`. This timeout value should also be
-/// accessible by calling the `timeout()` method.
+/// The `read()` and `write()` operations of these traits should honor the read and write timeouts
+/// that have been set with the most recent successful calls to `set_read_timeout()` and
+/// `set_write_timeout()`, following the contract described for [`Timeout`](enum.Timeout.html).
+/// These timeout values should also be accessible by calling the `read_timeout()` and
+/// `write_timeout()` methods.
 ///
 /// A serial port device should also provide access to some basic control signals: RTS, DTR, CTS,
 /// DSR, RI, and CD. The values for the control signals are represented as boolean values, with
@@ -361,11 +658,59 @@ pub trait SerialDevice: io::Read + io::Write {
     /// * `Io` for any other type of I/O error.
     fn write_settings(&mut self, settings: &Self::Settings) -> ::Result<()>;
 
-    /// Returns the current timeout.
-    fn timeout(&self) -> Duration;
+    /// Returns the current read timeout.
+    fn read_timeout(&self) -> Timeout;
+
+    /// Returns the current write timeout.
+    fn write_timeout(&self) -> Timeout;
+
+    /// Sets the timeout for future read operations.
+    fn set_read_timeout(&mut self, timeout: Timeout) -> ::Result<()>;
+
+    /// Sets the timeout for future write operations.
+    fn set_write_timeout(&mut self, timeout: Timeout) -> ::Result<()>;
+
+    /// Returns the current read timeout.
+    ///
+    /// This is equivalent to `read_timeout()`.
+    fn timeout(&self) -> Timeout {
+        self.read_timeout()
+    }
+
+    /// Sets the timeout for future read and write operations.
+    fn set_timeout(&mut self, timeout: Timeout) -> ::Result<()> {
+        try!(self.set_read_timeout(timeout));
+        self.set_write_timeout(timeout)
+    }
+
+    /// Returns the current inter-byte timeout in character times, if one is set.
+    fn inter_byte_timeout(&self) -> Option<u32>;
 
-    /// Sets the timeout for future I/O operations.
-    fn set_timeout(&mut self, timeout: Duration) -> ::Result<()>;
+    /// Sets the inter-byte timeout for future read operations, in character times.
+    ///
+    /// With an inter-byte timeout, a read waits up to the read timeout for the first byte to
+    /// arrive. It then continues receiving bytes until the buffer is full or no byte has arrived
+    /// for the given number of character times, as determined by the port's current settings (see
+    /// [`SerialPortSettings::char_time()`](trait.SerialPortSettings.html#method.char_time)). This
+    /// is useful for receiving frames from devices that go quiet after sending a frame.
+    ///
+    /// `None` disables the inter-byte timeout, so that reads return any bytes that are available
+    /// as soon as they arrive. Inter-byte timeouts don't apply to reads with a `NonBlocking`
+    /// timeout.
+    ///
+    /// On Windows, the inter-byte timeout is implemented by the driver's `ReadIntervalTimeout`, and
+    /// a read timeout other than `Infinite` limits the whole read rather than the wait for the
+    /// first byte. A read may then return before the line is idle if bytes are still arriving when
+    /// the read timeout expires.
+    ///
+    /// ## Errors
+    ///
+    /// This function returns an error if the inter-byte timeout could not be set:
+    ///
+    /// * `InvalidInput` if `chars` is zero.
+    /// * `NoDevice` if the device was disconnected.
+    /// * `Io` for any other type of I/O error.
+    fn set_inter_byte_timeout(&mut self, chars: Option<u32>) -> ::Result<()>;
 
     /// Sets the state of the RTS (Request To Send) control signal.
     ///
@@ -444,24 +789,228 @@ pub trait SerialDevice: io::Read + io::Write {
     /// * `NoDevice` if the device was disconnected.
     /// * `Io` for any other type of I/O error.
     fn read_cd(&mut self) -> ::Result<bool>;
+
+    /// Sets the states of the DTR and RTS control signals together.
+    ///
+    /// Devices that are reset or put into a bootloader by a combination of DTR and RTS may
+    /// misbehave when the signals are changed one at a time. Implementations should change both
+    /// signals in a single operation where possible. The default implementation sets DTR and then
+    /// RTS with separate calls to `set_dtr()` and `set_rts()`.
+    ///
+    /// ## Errors
+    ///
+    /// This function returns an error if the control signals could not be set to the desired
+    /// states on the underlying hardware:
+    ///
+    /// * `NoDevice` if the device was disconnected.
+    /// * `Io` for any other type of I/O error.
+    fn set_modem_control(&mut self, control: ModemControl) -> ::Result<()> {
+        try!(self.set_dtr(control.dtr));
+        self.set_rts(control.rts)
+    }
+
+    /// Reads the state of the DTR (Data Terminal Ready) control signal.
+    ///
+    /// This function returns a boolean that indicates whether the DTR control signal is asserted.
+    ///
+    /// ## Errors
+    ///
+    /// This function returns an error if the state of the DTR control signal could not be read
+    /// from the underlying hardware:
+    ///
+    /// * `Unsupported` if the device doesn't report the state of its output signals.
+    /// * `NoDevice` if the device was disconnected.
+    /// * `Io` for any other type of I/O error.
+    fn read_dtr(&mut self) -> ::Result<bool> {
+        Err(unsupported("read_dtr"))
+    }
+
+    /// Reads the state of the RTS (Request To Send) control signal.
+    ///
+    /// This function returns a boolean that indicates whether the RTS control signal is asserted.
+    ///
+    /// ## Errors
+    ///
+    /// This function returns an error if the state of the RTS control signal could not be read
+    /// from the underlying hardware:
+    ///
+    /// * `Unsupported` if the device doesn't report the state of its output signals.
+    /// * `NoDevice` if the device was disconnected.
+    /// * `Io` for any other type of I/O error.
+    fn read_rts(&mut self) -> ::Result<bool> {
+        Err(unsupported("read_rts"))
+    }
+
+    /// Sets or clears the break condition.
+    ///
+    /// While the break condition is set, the transmit line is held in the spacing state and no
+    /// data is transmitted.
+    ///
+    /// ## Errors
+    ///
+    /// This function returns an error if the break condition could not be changed:
+    ///
+    /// * `Unsupported` if the device doesn't support sending breaks.
+    /// * `NoDevice` if the device was disconnected.
+    /// * `Io` for any other type of I/O error.
+    fn set_break(&mut self, _enabled: bool) -> ::Result<()> {
+        Err(unsupported("set_break"))
+    }
+
+    /// Suspends transmission, as if an XOFF character had been received.
+    ///
+    /// ## Errors
+    ///
+    /// This function returns an error if transmission could not be suspended:
+    ///
+    /// * `Unsupported` if the device doesn't support suspending transmission.
+    /// * `NoDevice` if the device was disconnected.
+    /// * `Io` for any other type of I/O error.
+    fn suspend_output(&mut self) -> ::Result<()> {
+        Err(unsupported("suspend_output"))
+    }
+
+    /// Resumes transmission that was suspended by `suspend_output()` or a received XOFF character.
+    ///
+    /// ## Errors
+    ///
+    /// This function returns an error if transmission could not be resumed:
+    ///
+    /// * `Unsupported` if the device doesn't support resuming transmission.
+    /// * `NoDevice` if the device was disconnected.
+    /// * `Io` for any other type of I/O error.
+    fn resume_output(&mut self) -> ::Result<()> {
+        Err(unsupported("resume_output"))
+    }
+
+    /// Transmits an XOFF character to ask the other device to suspend transmission.
+    ///
+    /// The character is sent even if transmission is suspended.
+    ///
+    /// ## Errors
+    ///
+    /// This function returns an error if the XOFF character could not be sent:
+    ///
+    /// * `Unsupported` if the device doesn't support sending flow control characters.
+    /// * `NoDevice` if the device was disconnected.
+    /// * `Io` for any other type of I/O error.
+    fn send_xoff(&mut self) -> ::Result<()> {
+        Err(unsupported("send_xoff"))
+    }
+
+    /// Transmits an XON character to ask the other device to resume transmission.
+    ///
+    /// The character is sent even if transmission is suspended.
+    ///
+    /// ## Errors
+    ///
+    /// This function returns an error if the XON character could not be sent:
+    ///
+    /// * `Unsupported` if the device doesn't support sending flow control characters.
+    /// * `NoDevice` if the device was disconnected.
+    /// * `Io` for any other type of I/O error.
+    fn send_xon(&mut self) -> ::Result<()> {
+        Err(unsupported("send_xon"))
+    }
+
+    /// Returns `true` if transmission is suspended by a received XOFF character.
+    ///
+    /// ## Errors
+    ///
+    /// This function returns an error if the state of transmission could not be read:
+    ///
+    /// * `Unsupported` if the device doesn't report whether transmission is suspended.
+    /// * `NoDevice` if the device was disconnected.
+    /// * `Io` for any other type of I/O error.
+    fn is_output_stopped(&mut self) -> ::Result<bool> {
+        Err(unsupported("is_output_stopped"))
+    }
+
+    /// Waits until all written data has been transmitted, including the last stop bit.
+    ///
+    /// Unlike `flush()`, which waits for as long as it takes, this returns an error if `timeout`
+    /// expires first, e.g., because transmission is suspended by flow control.
+    ///
+    /// ## Errors
+    ///
+    /// This function returns an error if the data wasn't transmitted in time or the state of the
+    /// transmitter could not be read:
+    ///
+    /// * `Io(TimedOut)` if the timeout expired, or `Io(WouldBlock)` with a `NonBlocking` timeout.
+    /// * `Unsupported` if the device doesn't report the state of the transmitter.
+    /// * `NoDevice` if the device was disconnected.
+    /// * `Io` for any other type of I/O error.
+    fn wait_transmit_complete(&mut self, _timeout: Timeout) -> ::Result<()> {
+        Err(unsupported("wait_transmit_complete"))
+    }
 }
 
 /// A trait for serial port devices.
 ///
 /// Serial port input and output is implemented through the `std::io::Read` and `std::io::Write`
-/// traits. A timeout can be set with the `set_timeout()` method and applies to all subsequent I/O
-/// operations.
+/// traits. Timeouts can be set separately for reads and writes with the `set_read_timeout()` and
+/// `set_write_timeout()` methods, or for both with `set_timeout()`, and apply to all subsequent I/O
+/// operations. See [`Timeout`](enum.Timeout.html) for how reads and writes behave when the timeout
+/// expires.
 ///
 /// The `SerialPort` trait exposes several common control signals. Each control signal is
 /// represented as a boolean, where `true` indicates that the signal is asserted.
 ///
 /// The serial port will be closed when the value is dropped.
 pub trait SerialPort: io::Read + io::Write {
-    /// Returns the current timeout.
-    fn timeout(&self) -> Duration;
+    /// Returns the current read timeout.
+    fn read_timeout(&self) -> Timeout;
+
+    /// Returns the current write timeout.
+    fn write_timeout(&self) -> Timeout;
+
+    /// Sets the timeout for future read operations.
+    fn set_read_timeout(&mut self, timeout: Timeout) -> ::Result<()>;
+
+    /// Sets the timeout for future write operations.
+    fn set_write_timeout(&mut self, timeout: Timeout) -> ::Result<()>;
+
+    /// Returns the current read timeout.
+    ///
+    /// This is equivalent to `read_timeout()`.
+    fn timeout(&self) -> Timeout {
+        self.read_timeout()
+    }
+
+    /// Sets the timeout for future read and write operations.
+    fn set_timeout(&mut self, timeout: Timeout) -> ::Result<()> {
+        try!(self.set_read_timeout(timeout));
+        self.set_write_timeout(timeout)
+    }
+
+    /// Returns the current inter-byte timeout in character times, if one is set.
+    fn inter_byte_timeout(&self) -> Option<u32>;
 
-    /// Sets the timeout for future I/O operations.
-    fn set_timeout(&mut self, timeout: Duration) -> ::Result<()>;
+    /// Sets the inter-byte timeout for future read operations, in character times.
+    ///
+    /// With an inter-byte timeout, a read waits up to the read timeout for the first byte to
+    /// arrive. It then continues receiving bytes until the buffer is full or no byte has arrived
+    /// for the given number of character times, as determined by the port's current settings (see
+    /// [`SerialPortSettings::char_time()`](trait.SerialPortSettings.html#method.char_time)). This
+    /// is useful for receiving frames from devices that go quiet after sending a frame.
+    ///
+    /// `None` disables the inter-byte timeout, so that reads return any bytes that are available
+    /// as soon as they arrive. Inter-byte timeouts don't apply to reads with a `NonBlocking`
+    /// timeout.
+    ///
+    /// On Windows, the inter-byte timeout is implemented by the driver's `ReadIntervalTimeout`, and
+    /// a read timeout other than `Infinite` limits the whole read rather than the wait for the
+    /// first byte. A read may then return before the line is idle if bytes are still arriving when
+    /// the read timeout expires.
+    ///
+    /// ## Errors
+    ///
+    /// This function returns an error if the inter-byte timeout could not be set:
+    ///
+    /// * `InvalidInput` if `chars` is zero.
+    /// * `NoDevice` if the device was disconnected.
+    /// * `Io` for any other type of I/O error.
+    fn set_inter_byte_timeout(&mut self, chars: Option<u32>) -> ::Result<()>;
 
     /// Configures a serial port device.
     ///
@@ -515,6 +1064,44 @@ pub trait SerialPort: io::Read + io::Write {
     /// ```
     fn reconfigure(&mut self, setup: &Fn(&mut SerialPortSettings) -> ::Result<()>) -> ::Result<()>;
 
+    /// Reads a serial port's current settings without changing them.
+    ///
+    /// The default implementation returns an error of kind `Unsupported`.
+    ///
+    /// ## Errors
+    ///
+    /// This function returns an error if the settings could not be read from the underlying
+    /// hardware:
+    ///
+    /// * `NoDevice` if the device was disconnected.
+    /// * `Unsupported` if the port can't report its settings.
+    /// * `Io` for any other type of I/O error.
+    fn settings<'a>(&'a self) -> ::Result<Box<SerialPortSettings + 'a>> {
+        Err(unsupported("settings"))
+    }
+
+    /// Configures a serial port device and checks that the settings were applied.
+    ///
+    /// Devices may accept settings that they don't support, e.g., by falling back to another baud
+    /// rate. After configuring the device, its settings are read back with `settings()` and
+    /// compared to `settings`.
+    ///
+    /// ## Errors
+    ///
+    /// This function returns an error if the settings could not be applied to the underlying
+    /// hardware:
+    ///
+    /// * `InvalidInput` if a setting is not compatible with the underlying hardware or wasn't
+    ///   applied. The error's description lists each setting that differs from `settings`, e.g.,
+    ///   `baud rate: requested BaudOther(3000000), got None`.
+    /// * `NoDevice` if the device was disconnected.
+    /// * `Unsupported` if the port can't report its settings.
+    /// * `Io` for any other type of I/O error.
+    fn configure_verified(&mut self, settings: &PortSettings) -> ::Result<()> {
+        try!(self.configure(settings));
+        verify_settings(settings, &*try!(self.settings()))
+    }
+
     /// Sets the state of the RTS (Request To Send) control signal.
     ///
     /// Setting a value of `true` asserts the RTS control signal. `false` clears the signal.
@@ -592,19 +1179,265 @@ pub trait SerialPort: io::Read + io::Write {
     /// * `NoDevice` if the device was disconnected.
     /// * `Io` for any other type of I/O error.
     fn read_cd(&mut self) -> ::Result<bool>;
-}
 
-impl<T> SerialPort for T
-    where T: SerialDevice
-{
-    fn timeout(&self) -> Duration {
-        T::timeout(self)
+    /// Sets the states of the DTR and RTS control signals together.
+    ///
+    /// Devices that are reset or put into a bootloader by a combination of DTR and RTS may
+    /// misbehave when the signals are changed one at a time. Implementations should change both
+    /// signals in a single operation where possible. The default implementation sets DTR and then
+    /// RTS with separate calls to `set_dtr()` and `set_rts()`.
+    ///
+    /// ## Errors
+    ///
+    /// This function returns an error if the control signals could not be set to the desired
+    /// states on the underlying hardware:
+    ///
+    /// * `NoDevice` if the device was disconnected.
+    /// * `Io` for any other type of I/O error.
+    fn set_modem_control(&mut self, control: ModemControl) -> ::Result<()> {
+        try!(self.set_dtr(control.dtr));
+        self.set_rts(control.rts)
+    }
+
+    /// Reads the state of the DTR (Data Terminal Ready) control signal.
+    ///
+    /// This function returns a boolean that indicates whether the DTR control signal is asserted.
+    ///
+    /// ## Errors
+    ///
+    /// This function returns an error if the state of the DTR control signal could not be read
+    /// from the underlying hardware:
+    ///
+    /// * `Unsupported` if the device doesn't report the state of its output signals.
+    /// * `NoDevice` if the device was disconnected.
+    /// * `Io` for any other type of I/O error.
+    fn read_dtr(&mut self) -> ::Result<bool> {
+        Err(unsupported("read_dtr"))
+    }
+
+    /// Reads the state of the RTS (Request To Send) control signal.
+    ///
+    /// This function returns a boolean that indicates whether the RTS control signal is asserted.
+    ///
+    /// ## Errors
+    ///
+    /// This function returns an error if the state of the RTS control signal could not be read
+    /// from the underlying hardware:
+    ///
+    /// * `Unsupported` if the device doesn't report the state of its output signals.
+    /// * `NoDevice` if the device was disconnected.
+    /// * `Io` for any other type of I/O error.
+    fn read_rts(&mut self) -> ::Result<bool> {
+        Err(unsupported("read_rts"))
+    }
+
+    /// Sets or clears the break condition.
+    ///
+    /// While the break condition is set, the transmit line is held in the spacing state and no
+    /// data is transmitted.
+    ///
+    /// ## Errors
+    ///
+    /// This function returns an error if the break condition could not be changed:
+    ///
+    /// * `Unsupported` if the device doesn't support sending breaks.
+    /// * `NoDevice` if the device was disconnected.
+    /// * `Io` for any other type of I/O error.
+    fn set_break(&mut self, _enabled: bool) -> ::Result<()> {
+        Err(unsupported("set_break"))
+    }
+
+    /// Suspends transmission, as if an XOFF character had been received.
+    ///
+    /// ## Errors
+    ///
+    /// This function returns an error if transmission could not be suspended:
+    ///
+    /// * `Unsupported` if the device doesn't support suspending transmission.
+    /// * `NoDevice` if the device was disconnected.
+    /// * `Io` for any other type of I/O error.
+    fn suspend_output(&mut self) -> ::Result<()> {
+        Err(unsupported("suspend_output"))
+    }
+
+    /// Resumes transmission that was suspended by `suspend_output()` or a received XOFF character.
+    ///
+    /// ## Errors
+    ///
+    /// This function returns an error if transmission could not be resumed:
+    ///
+    /// * `Unsupported` if the device doesn't support resuming transmission.
+    /// * `NoDevice` if the device was disconnected.
+    /// * `Io` for any other type of I/O error.
+    fn resume_output(&mut self) -> ::Result<()> {
+        Err(unsupported("resume_output"))
+    }
+
+    /// Transmits an XOFF character to ask the other device to suspend transmission.
+    ///
+    /// The character is sent even if transmission is suspended.
+    ///
+    /// ## Errors
+    ///
+    /// This function returns an error if the XOFF character could not be sent:
+    ///
+    /// * `Unsupported` if the device doesn't support sending flow control characters.
+    /// * `NoDevice` if the device was disconnected.
+    /// * `Io` for any other type of I/O error.
+    fn send_xoff(&mut self) -> ::Result<()> {
+        Err(unsupported("send_xoff"))
+    }
+
+    /// Transmits an XON character to ask the other device to resume transmission.
+    ///
+    /// The character is sent even if transmission is suspended.
+    ///
+    /// ## Errors
+    ///
+    /// This function returns an error if the XON character could not be sent:
+    ///
+    /// * `Unsupported` if the device doesn't support sending flow control characters.
+    /// * `NoDevice` if the device was disconnected.
+    /// * `Io` for any other type of I/O error.
+    fn send_xon(&mut self) -> ::Result<()> {
+        Err(unsupported("send_xon"))
+    }
+
+    /// Returns `true` if transmission is suspended by a received XOFF character.
+    ///
+    /// ## Errors
+    ///
+    /// This function returns an error if the state of transmission could not be read:
+    ///
+    /// * `Unsupported` if the device doesn't report whether transmission is suspended.
+    /// * `NoDevice` if the device was disconnected.
+    /// * `Io` for any other type of I/O error.
+    fn is_output_stopped(&mut self) -> ::Result<bool> {
+        Err(unsupported("is_output_stopped"))
+    }
+
+    /// Waits until all written data has been transmitted, including the last stop bit.
+    ///
+    /// Unlike `flush()`, which waits for as long as it takes, this returns an error if `timeout`
+    /// expires first, e.g., because transmission is suspended by flow control.
+    ///
+    /// ## Errors
+    ///
+    /// This function returns an error if the data wasn't transmitted in time or the state of the
+    /// transmitter could not be read:
+    ///
+    /// * `Io(TimedOut)` if the timeout expired, or `Io(WouldBlock)` with a `NonBlocking` timeout.
+    /// * `Unsupported` if the device doesn't report the state of the transmitter.
+    /// * `NoDevice` if the device was disconnected.
+    /// * `Io` for any other type of I/O error.
+    fn wait_transmit_complete(&mut self, _timeout: Timeout) -> ::Result<()> {
+        Err(unsupported("wait_transmit_complete"))
+    }
+
+    /// Reads bytes into `buf` with a timeout that applies only to this call.
+    ///
+    /// The port's read timeout is restored before returning.
+    ///
+    /// ## Errors
+    ///
+    /// This function returns an error of kind `Io(TimedOut)` if no data was received before the
+    /// timeout expired, or any error that occurred while reading or changing the timeout.
+    fn read_with_timeout(&mut self, buf: &mut [u8], timeout: Duration) -> ::Result<usize> {
+        let saved = self.read_timeout();
+        let result = self.set_read_timeout(Timeout::Duration(timeout)).and_then(|_| read_retrying(self, buf));
+        restore_timeout(self, Self::set_read_timeout, saved, result)
+    }
+
+    /// Reads the exact number of bytes required to fill `buf`, unless `deadline` passes first.
+    ///
+    /// Unlike `io::Read::read_exact()`, the deadline bounds the whole operation rather than each
+    /// underlying read. The port's read timeout is restored before returning.
+    ///
+    /// ## Errors
+    ///
+    /// This function returns an error of kind `Io(TimedOut)` if the deadline passed before `buf`
+    /// was filled, `Io(UnexpectedEof)` if the device reported end of file, or any error that
+    /// occurred while reading or changing the timeout. The number of bytes that were read into
+    /// `buf` is available from the error's [`transferred()`](struct.Error.html#method.transferred)
+    /// method.
+    fn read_exact_deadline(&mut self, buf: &mut [u8], deadline: Instant) -> ::Result<()> {
+        let saved = self.read_timeout();
+        let result = read_exact_until(self, buf, deadline);
+        restore_timeout(self, Self::set_read_timeout, saved, result)
+    }
+
+    /// Writes all of `buf`, unless `deadline` passes first.
+    ///
+    /// The deadline bounds the whole operation rather than each underlying write. The port's write
+    /// timeout is restored before returning.
+    ///
+    /// ## Errors
+    ///
+    /// This function returns an error of kind `Io(TimedOut)` if the deadline passed before all of
+    /// `buf` was written, `Io(WriteZero)` if the device stopped accepting data, or any error that
+    /// occurred while writing or changing the timeout. The number of bytes that were written is
+    /// available from the error's [`transferred()`](struct.Error.html#method.transferred) method.
+    fn write_all_deadline(&mut self, buf: &[u8], deadline: Instant) -> ::Result<()> {
+        let saved = self.write_timeout();
+        let result = write_all_until(self, buf, deadline);
+        restore_timeout(self, Self::set_write_timeout, saved, result)
+    }
+
+    /// Reads bytes and appends them to `buf` until the byte `delim` is received, unless `deadline`
+    /// passes first.
+    ///
+    /// The delimiter is appended to `buf`. Bytes are read one at a time, so no bytes following the
+    /// delimiter are consumed. Returns the number of bytes appended to `buf`. The port's read
+    /// timeout is restored before returning.
+    ///
+    /// ## Errors
+    ///
+    /// This function returns an error of kind `Io(TimedOut)` if the deadline passed before the
+    /// delimiter was received, or any error that occurred while reading or changing the timeout.
+    /// Bytes received before the error are left in `buf`, and their number is available from the
+    /// error's [`transferred()`](struct.Error.html#method.transferred) method.
+    fn read_until(&mut self, delim: u8, buf: &mut Vec<u8>, deadline: Instant) -> ::Result<usize> {
+        let saved = self.read_timeout();
+        let result = read_until_delim(self, delim, buf, deadline);
+        restore_timeout(self, Self::set_read_timeout, saved, result)
+    }
+}
+
+impl<T> SerialPort for T
+    where T: SerialDevice
+{
+    fn read_timeout(&self) -> Timeout {
+        T::read_timeout(self)
+    }
+
+    fn write_timeout(&self) -> Timeout {
+        T::write_timeout(self)
     }
 
-    fn set_timeout(&mut self, timeout: Duration) -> ::Result<()> {
+    fn set_read_timeout(&mut self, timeout: Timeout) -> ::Result<()> {
+        T::set_read_timeout(self, timeout)
+    }
+
+    fn set_write_timeout(&mut self, timeout: Timeout) -> ::Result<()> {
+        T::set_write_timeout(self, timeout)
+    }
+
+    fn timeout(&self) -> Timeout {
+        T::timeout(self)
+    }
+
+    fn set_timeout(&mut self, timeout: Timeout) -> ::Result<()> {
         T::set_timeout(self, timeout)
     }
 
+    fn inter_byte_timeout(&self) -> Option<u32> {
+        T::inter_byte_timeout(self)
+    }
+
+    fn set_inter_byte_timeout(&mut self, chars: Option<u32>) -> ::Result<()> {
+        T::set_inter_byte_timeout(self, chars)
+    }
+
     fn configure(&mut self, settings: &PortSettings) -> ::Result<()> {
         let mut device_settings = try!(T::read_settings(self));
 
@@ -623,6 +1456,10 @@ impl<T> SerialPort for T
         T::write_settings(self, &device_settings)
     }
 
+    fn settings<'a>(&'a self) -> ::Result<Box<SerialPortSettings + 'a>> {
+        Ok(Box::new(try!(T::read_settings(self))))
+    }
+
     fn set_rts(&mut self, level: bool) -> ::Result<()> {
         T::set_rts(self, level)
     }
@@ -646,6 +1483,46 @@ impl<T> SerialPort for T
     fn read_cd(&mut self) -> ::Result<bool> {
         T::read_cd(self)
     }
+
+    fn set_modem_control(&mut self, control: ModemControl) -> ::Result<()> {
+        T::set_modem_control(self, control)
+    }
+
+    fn read_dtr(&mut self) -> ::Result<bool> {
+        T::read_dtr(self)
+    }
+
+    fn read_rts(&mut self) -> ::Result<bool> {
+        T::read_rts(self)
+    }
+
+    fn set_break(&mut self, enabled: bool) -> ::Result<()> {
+        T::set_break(self, enabled)
+    }
+
+    fn suspend_output(&mut self) -> ::Result<()> {
+        T::suspend_output(self)
+    }
+
+    fn resume_output(&mut self) -> ::Result<()> {
+        T::resume_output(self)
+    }
+
+    fn send_xoff(&mut self) -> ::Result<()> {
+        T::send_xoff(self)
+    }
+
+    fn send_xon(&mut self) -> ::Result<()> {
+        T::send_xon(self)
+    }
+
+    fn is_output_stopped(&mut self) -> ::Result<bool> {
+        T::is_output_stopped(self)
+    }
+
+    fn wait_transmit_complete(&mut self, timeout: Timeout) -> ::Result<()> {
+        T::wait_transmit_complete(self, timeout)
+    }
 }
 
 /// A trait for objects that implement serial port configurations.
@@ -708,6 +1585,68 @@ pub trait SerialPortSettings {
 
     /// Sets the flow control mode.
     fn set_flow_control(&mut self, flow_control: FlowControl);
+
+    /// Returns the characters used for software flow control as a tuple of `(xon, xoff)`.
+    ///
+    /// The default implementation returns the standard characters, [`XON`](constant.XON.html)
+    /// and [`XOFF`](constant.XOFF.html).
+    fn xon_xoff_chars(&self) -> (u8, u8) {
+        (XON, XOFF)
+    }
+
+    /// Sets the characters used for software flow control.
+    ///
+    /// `xon` resumes transmission and `xoff` suspends it. The characters are only used with
+    /// `FlowSoftware`.
+    ///
+    /// ## Errors
+    ///
+    /// This function returns an `InvalidInput` error if `xon` and `xoff` are the same character.
+    /// The default implementation only supports the standard characters and returns an
+    /// `InvalidInput` error for any others.
+    fn set_xon_xoff_chars(&mut self, xon: u8, xoff: u8) -> ::Result<()> {
+        if (xon, xoff) != (XON, XOFF) {
+            return Err(Error::new(ErrorKind::InvalidInput, "Custom XON/XOFF characters are not supported"));
+        }
+
+        Ok(())
+    }
+
+    /// Returns the time it takes to transmit one character with the current settings.
+    ///
+    /// A character consists of a start bit, the data bits, the parity bit if parity is enabled,
+    /// and the stop bits. This function returns `None` if any of these settings or the baud rate
+    /// could not be determined.
+    fn char_time(&self) -> Option<Duration> {
+        let speed = match self.baud_rate() {
+            Some(baud_rate) if baud_rate.speed() > 0 => baud_rate.speed() as u64,
+            _ => return None,
+        };
+
+        let data_bits = match self.char_size() {
+            Some(Bits5) => 5,
+            Some(Bits6) => 6,
+            Some(Bits7) => 7,
+            Some(Bits8) => 8,
+            None => return None,
+        };
+
+        let parity_bits = match self.parity() {
+            Some(ParityNone) => 0,
+            Some(_) => 1,
+            None => return None,
+        };
+
+        let stop_bits = match self.stop_bits() {
+            Some(Stop1) => 1,
+            Some(Stop2) => 2,
+            None => return None,
+        };
+
+        let bits = 1 + data_bits + parity_bits + stop_bits;
+
+        Some(Duration::from_nanos(bits * 1_000_000_000 / speed))
+    }
 }
 
 /// A device-indepenent implementation of serial port settings.
@@ -772,6 +1711,164 @@ impl SerialPortSettings for PortSettings {
     }
 }
 
+// Returns the error for an operation that a port doesn't implement.
+fn unsupported(operation: &str) -> Error {
+    Error::new(ErrorKind::Unsupported, "Operation not supported").with_operation(operation)
+}
+
+// Restores a port's timeout after an operation. An error from the operation takes precedence over
+// an error while restoring the timeout.
+fn restore_timeout<P, T>(port: &mut P, set_timeout: fn(&mut P, Timeout) -> ::Result<()>, timeout: Timeout, result: ::Result<T>) -> ::Result<T>
+    where P: SerialPort + ?Sized
+{
+    let restored = set_timeout(port, timeout);
+    let value = try!(result);
+    try!(restored);
+    Ok(value)
+}
+
+fn read_retrying<P: SerialPort + ?Sized>(port: &mut P, buf: &mut [u8]) -> ::Result<usize> {
+    loop {
+        match port.read(buf) {
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => (),
+            result => return result.map_err(Error::from),
+        }
+    }
+}
+
+// Returns the time remaining until `deadline`, or a timeout error if it has passed.
+fn remaining(deadline: Instant, transferred: usize) -> ::Result<Duration> {
+    let now = Instant::now();
+
+    if now < deadline {
+        Ok(deadline - now)
+    }
+    else {
+        Err(timed_out(transferred))
+    }
+}
+
+// Returns an `InvalidInput` error listing the settings of `actual` that differ from `requested`.
+fn verify_settings<S: SerialPortSettings + ?Sized>(requested: &PortSettings, actual: &S) -> ::Result<()> {
+    let mut mismatches = Vec::new();
+
+    if actual.baud_rate().map(|baud_rate| baud_rate.speed()) != Some(requested.baud_rate.speed()) {
+        mismatches.push(format!("baud rate: requested {:?}, got {:?}", requested.baud_rate, actual.baud_rate()));
+    }
+
+    if actual.char_size() != Some(requested.char_size) {
+        mismatches.push(format!("char size: requested {:?}, got {:?}", requested.char_size, actual.char_size()));
+    }
+
+    if actual.parity() != Some(requested.parity) {
+        mismatches.push(format!("parity: requested {:?}, got {:?}", requested.parity, actual.parity()));
+    }
+
+    if actual.stop_bits() != Some(requested.stop_bits) {
+        mismatches.push(format!("stop bits: requested {:?}, got {:?}", requested.stop_bits, actual.stop_bits()));
+    }
+
+    if actual.flow_control() != Some(requested.flow_control) {
+        mismatches.push(format!("flow control: requested {:?}, got {:?}", requested.flow_control, actual.flow_control()));
+    }
+
+    if mismatches.is_empty() {
+        Ok(())
+    }
+    else {
+        Err(Error::new(ErrorKind::InvalidInput, format!("Settings were not applied: {}", mismatches.join("; "))))
+    }
+}
+
+fn timed_out(transferred: usize) -> Error {
+    Error::new(ErrorKind::Io(io::ErrorKind::TimedOut),
+               format!("Operation timed out after transferring {} bytes", transferred))
+        .with_transferred(transferred)
+}
+
+fn read_exact_until<P: SerialPort + ?Sized>(port: &mut P, buf: &mut [u8], deadline: Instant) -> ::Result<()> {
+    let mut transferred = 0;
+
+    while transferred < buf.len() {
+        let timeout = try!(remaining(deadline, transferred));
+        try!(port.set_read_timeout(Timeout::Duration(timeout)).map_err(|e| e.with_transferred(transferred)));
+
+        match port.read(&mut buf[transferred..]) {
+            Ok(0) => {
+                return Err(Error::new(ErrorKind::Io(io::ErrorKind::UnexpectedEof), "Unexpected end of file")
+                           .with_transferred(transferred));
+            }
+            Ok(n) => transferred += n,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => (),
+            Err(ref e) if e.kind() == io::ErrorKind::TimedOut => return Err(timed_out(transferred)),
+            Err(e) => return Err(Error::from(e).with_transferred(transferred)),
+        }
+    }
+
+    Ok(())
+}
+
+fn write_all_until<P: SerialPort + ?Sized>(port: &mut P, buf: &[u8], deadline: Instant) -> ::Result<()> {
+    let mut transferred = 0;
+
+    while transferred < buf.len() {
+        let timeout = try!(remaining(deadline, transferred));
+        try!(port.set_write_timeout(Timeout::Duration(timeout)).map_err(|e| e.with_transferred(transferred)));
+
+        match port.write(&buf[transferred..]) {
+            Ok(0) => {
+                return Err(Error::new(ErrorKind::Io(io::ErrorKind::WriteZero), "Failed to write whole buffer")
+                           .with_transferred(transferred));
+            }
+            Ok(n) => transferred += n,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => (),
+            Err(ref e) if e.kind() == io::ErrorKind::TimedOut => return Err(timed_out(transferred)),
+            Err(e) => return Err(Error::from(e).with_transferred(transferred)),
+        }
+    }
+
+    Ok(())
+}
+
+fn read_until_delim<P>(port: &mut P, delim: u8, buf: &mut Vec<u8>, deadline: Instant) -> ::Result<usize>
+    where P: SerialPort + ?Sized
+{
+    let start = buf.len();
+    let mut byte = [0u8];
+    let mut timeout: Option<Duration> = None;
+
+    loop {
+        let transferred = buf.len() - start;
+        let remaining = try!(remaining(deadline, transferred));
+
+        // timeouts have a resolution of one millisecond, so smaller changes aren't worth setting
+        let changed = match timeout {
+            Some(timeout) => remaining + Duration::from_millis(1) <= timeout,
+            None => true,
+        };
+
+        if changed {
+            try!(port.set_read_timeout(Timeout::Duration(remaining)).map_err(|e| e.with_transferred(transferred)));
+            timeout = Some(remaining);
+        }
+
+        match port.read(&mut byte) {
+            Ok(0) => {
+                return Err(Error::new(ErrorKind::Io(io::ErrorKind::UnexpectedEof), "Unexpected end of file")
+                           .with_transferred(transferred));
+            }
+            Ok(_) => buf.push(byte[0]),
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(ref e) if e.kind() == io::ErrorKind::TimedOut => return Err(timed_out(transferred)),
+            Err(e) => return Err(Error::from(e).with_transferred(transferred)),
+        }
+
+        if byte[0] == delim {
+            return Ok(buf.len() - start);
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -786,6 +1883,48 @@ mod tests {
         }
     }
 
+    #[test]
+    fn baud_clock_finds_nearest_integer_divisor() {
+        let rate = BaudClock::new(115200).nearest(BaudOther(50000)).unwrap();
+        assert_eq!(rate, EffectiveBaudRate { requested: 50000, actual: 57600 });
+    }
+
+    #[test]
+    fn baud_clock_uses_fractional_divisors() {
+        let clock = BaudClock { base: 3_000_000, max_divisor: 16383, fraction_bits: 3 };
+
+        assert_eq!(clock.nearest(BaudOther(250000)).unwrap().actual, 250000);
+        assert_eq!(clock.nearest(BaudOther(230400)).unwrap().actual, 230769);
+    }
+
+    #[test]
+    fn baud_clock_limits_divisor() {
+        assert_eq!(BaudClock::new(115200).nearest(BaudOther(250000)).unwrap().actual, 115200);
+        assert_eq!(BaudClock::new(115200).nearest(BaudOther(1)).unwrap().actual, 2);
+        assert_eq!(BaudClock::new(115200).nearest(BaudOther(0)), None);
+    }
+
+    #[test]
+    fn baud_clock_rejects_divisors_that_overflow() {
+        let clock = BaudClock { base: 3_000_000, max_divisor: 16383, fraction_bits: 64 };
+        assert_eq!(clock.nearest(Baud9600), None);
+
+        let clock = BaudClock { base: u32::max_value(), max_divisor: u32::max_value(), fraction_bits: 40 };
+        assert_eq!(clock.nearest(Baud9600), None);
+
+        let clock = BaudClock { base: u32::max_value(), max_divisor: u32::max_value(), fraction_bits: 32 };
+        assert_eq!(clock.nearest(BaudOther(1)), None);
+
+        let clock = BaudClock { base: 3_000_000, max_divisor: 16383, fraction_bits: 30 };
+        assert_eq!(clock.nearest(BaudOther(250000)).unwrap().actual, 250000);
+    }
+
+    #[test]
+    fn effective_baud_rate_computes_error() {
+        assert_eq!(EffectiveBaudRate { requested: 9600, actual: 9600 }.error_percent(), 0.0);
+        assert_eq!(EffectiveBaudRate { requested: 50000, actual: 57600 }.error_percent(), 15.2);
+    }
+
     #[test]
     fn port_settings_manipulates_baud_rate() {
         let mut settings: PortSettings = default_port_settings();
@@ -820,4 +1959,359 @@ mod tests {
         settings.set_flow_control(FlowSoftware);
         assert_eq!(settings.flow_control(), Some(FlowSoftware));
     }
+
+    #[test]
+    fn port_settings_uses_standard_xon_xoff_chars() {
+        let mut settings = default_port_settings();
+
+        assert_eq!(settings.xon_xoff_chars(), (XON, XOFF));
+        assert!(settings.set_xon_xoff_chars(XON, XOFF).is_ok());
+        assert_eq!(settings.set_xon_xoff_chars(b'!', b'?').unwrap_err().kind(), ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn port_settings_computes_char_time() {
+        let mut settings: PortSettings = default_port_settings();
+        assert_eq!(settings.char_time(), Some(Duration::from_nanos(1_041_666)));
+
+        settings.set_parity(ParityEven);
+        settings.set_stop_bits(Stop2);
+        assert_eq!(settings.char_time(), Some(Duration::from_nanos(1_250_000)));
+    }
+
+    #[test]
+    fn port_settings_has_no_char_time_for_zero_baud_rate() {
+        let mut settings: PortSettings = default_port_settings();
+        settings.set_baud_rate(BaudOther(0)).unwrap();
+        assert_eq!(settings.char_time(), None);
+    }
+
+    #[test]
+    fn timeout_converts_to_duration() {
+        assert_eq!(Timeout::Infinite.as_duration(), None);
+        assert_eq!(Timeout::NonBlocking.as_duration(), Some(Duration::from_secs(0)));
+        assert_eq!(Timeout::from(Duration::from_millis(10)).as_duration(), Some(Duration::from_millis(10)));
+    }
+
+    #[test]
+    fn timeout_computes_deadline() {
+        let start = Instant::now();
+        let deadline = Timeout::Duration(Duration::from_secs(10)).deadline().unwrap();
+
+        assert!(deadline >= start + Duration::from_secs(10));
+        assert!(Timeout::NonBlocking.deadline().unwrap() <= Instant::now());
+        assert_eq!(Timeout::Infinite.deadline(), None);
+    }
+
+    #[test]
+    fn timeout_treats_huge_duration_as_infinite() {
+        assert_eq!(Timeout::Duration(Duration::from_secs(u64::max_value())).deadline(), None);
+    }
+
+    #[test]
+    fn error_displays_operation_and_path() {
+        let error = Error::new(ErrorKind::InvalidInput, "Invalid argument")
+            .with_operation("TCSETS2")
+            .with_path("/dev/ttyUSB0");
+
+        assert_eq!(error.to_string(), "TCSETS2 on /dev/ttyUSB0: Invalid argument");
+    }
+
+    #[test]
+    fn error_keeps_first_path() {
+        let error = Error::new(ErrorKind::NoDevice, "No such device")
+            .with_path("/dev/ttyUSB0")
+            .with_path("/dev/ttyUSB1");
+
+        assert_eq!(error.path(), Some(Path::new("/dev/ttyUSB0")));
+    }
+
+    #[test]
+    fn error_preserves_raw_os_error() {
+        let error = Error::from(io::Error::from_raw_os_error(22));
+
+        assert_eq!(error.raw_os_error(), Some(22));
+        assert!(error.source().is_some());
+    }
+
+    #[test]
+    fn error_round_trips_through_io_error() {
+        let error = Error::new(ErrorKind::NoDevice, "No such device").with_transferred(3);
+        let error = Error::from(io::Error::from(error));
+
+        assert_eq!(error.kind(), ErrorKind::NoDevice);
+        assert_eq!(error.transferred(), Some(3));
+    }
+
+    #[test]
+    fn unsupported_error_round_trips_through_io_error() {
+        let error = Error::new(ErrorKind::Unsupported, "Operation not supported")
+            .with_operation("TIOCSRS485");
+        let io_error = io::Error::from(error);
+        assert_eq!(io_error.kind(), io::ErrorKind::Other);
+
+        let error = Error::from(io_error);
+        assert_eq!(error.kind(), ErrorKind::Unsupported);
+        assert_eq!(error.operation(), Some("TIOCSRS485"));
+    }
+
+    #[test]
+    fn io_error_preserves_serial_error() {
+        let error = Error::from(io::Error::from_raw_os_error(22)).with_operation("TCSETS2");
+        let io_error = io::Error::from(error);
+
+        let inner = io_error.get_ref().and_then(|e| e.downcast_ref::<Error>()).unwrap();
+        assert_eq!(inner.operation(), Some("TCSETS2"));
+        assert_eq!(inner.raw_os_error(), Some(22));
+    }
+
+    // A port that receives scripted chunks of data and accepts a limited number of bytes. Once no
+    // data remains to be read, or no more data can be written, operations wait for the timeout.
+    struct ScriptedPort {
+        read_timeout: Timeout,
+        write_timeout: Timeout,
+        input: ::std::collections::VecDeque<Vec<u8>>,
+        output: Vec<u8>,
+        capacity: usize,
+        settings: PortSettings,
+        settings_written: usize,
+        read_timeouts_set: usize,
+    }
+
+    impl ScriptedPort {
+        fn new(input: &[&[u8]]) -> Self {
+            ScriptedPort {
+                read_timeout: Timeout::Duration(Duration::from_millis(1000)),
+                write_timeout: Timeout::Duration(Duration::from_millis(2000)),
+                input: input.iter().map(|chunk| chunk.to_vec()).collect(),
+                output: Vec::new(),
+                capacity: 0,
+                settings: default_port_settings(),
+                settings_written: 0,
+                read_timeouts_set: 0,
+            }
+        }
+
+        fn wait_for_timeout(&self, timeout: Timeout) -> io::Error {
+            ::std::thread::sleep(timeout.as_duration().unwrap());
+            io::Error::new(io::ErrorKind::TimedOut, "Operation timed out")
+        }
+    }
+
+    impl io::Read for ScriptedPort {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let mut chunk = match self.input.pop_front() {
+                Some(chunk) => chunk,
+                None => return Err(self.wait_for_timeout(self.read_timeout)),
+            };
+
+            let len = ::std::cmp::min(buf.len(), chunk.len());
+            buf[..len].copy_from_slice(&chunk[..len]);
+
+            if len < chunk.len() {
+                self.input.push_front(chunk.split_off(len));
+            }
+
+            Ok(len)
+        }
+    }
+
+    impl io::Write for ScriptedPort {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if self.capacity == 0 {
+                return Err(self.wait_for_timeout(self.write_timeout));
+            }
+
+            let len = ::std::cmp::min(::std::cmp::min(buf.len(), self.capacity), 2);
+            self.output.extend_from_slice(&buf[..len]);
+            self.capacity -= len;
+            Ok(len)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl SerialPort for ScriptedPort {
+        fn read_timeout(&self) -> Timeout { self.read_timeout }
+        fn write_timeout(&self) -> Timeout { self.write_timeout }
+
+        fn set_read_timeout(&mut self, timeout: Timeout) -> ::Result<()> {
+            self.read_timeout = timeout;
+            self.read_timeouts_set += 1;
+            Ok(())
+        }
+
+        fn set_write_timeout(&mut self, timeout: Timeout) -> ::Result<()> { self.write_timeout = timeout; Ok(()) }
+        fn inter_byte_timeout(&self) -> Option<u32> { None }
+        fn set_inter_byte_timeout(&mut self, _: Option<u32>) -> ::Result<()> { Ok(()) }
+
+        fn configure(&mut self, settings: &PortSettings) -> ::Result<()> {
+            self.settings = *settings;
+            self.settings_written += 1;
+            Ok(())
+        }
+
+        fn reconfigure(&mut self, setup: &Fn(&mut SerialPortSettings) -> ::Result<()>) -> ::Result<()> {
+            let mut settings = self.settings;
+            try!(setup(&mut settings));
+            self.settings = settings;
+            self.settings_written += 1;
+            Ok(())
+        }
+
+        fn settings<'a>(&'a self) -> ::Result<Box<SerialPortSettings + 'a>> {
+            Ok(Box::new(self.settings))
+        }
+
+        fn set_rts(&mut self, _: bool) -> ::Result<()> { Ok(()) }
+        fn set_dtr(&mut self, _: bool) -> ::Result<()> { Ok(()) }
+        fn read_cts(&mut self) -> ::Result<bool> { Ok(false) }
+        fn read_dsr(&mut self) -> ::Result<bool> { Ok(false) }
+        fn read_ri(&mut self) -> ::Result<bool> { Ok(false) }
+        fn read_cd(&mut self) -> ::Result<bool> { Ok(false) }
+    }
+
+    fn deadline() -> Instant {
+        Instant::now() + Duration::from_millis(50)
+    }
+
+    #[test]
+    fn read_with_timeout_restores_timeout() {
+        let mut port = ScriptedPort::new(&[]);
+
+        let err = port.read_with_timeout(&mut [0u8; 4], Duration::from_millis(10)).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Io(io::ErrorKind::TimedOut));
+        assert_eq!(port.read_timeout(), Timeout::Duration(Duration::from_millis(1000)));
+    }
+
+    #[test]
+    fn read_exact_deadline_reads_chunks() {
+        let mut port = ScriptedPort::new(&[b"ab", b"cd"]);
+        let mut buf = [0u8; 4];
+
+        port.read_exact_deadline(&mut buf, deadline()).unwrap();
+        assert_eq!(&buf, b"abcd");
+    }
+
+    #[test]
+    fn read_exact_deadline_reports_bytes_read_on_timeout() {
+        let mut port = ScriptedPort::new(&[b"ab"]);
+        let mut buf = [0u8; 4];
+
+        let start = Instant::now();
+        let err = port.read_exact_deadline(&mut buf, deadline()).unwrap_err();
+
+        assert_eq!(err.kind(), ErrorKind::Io(io::ErrorKind::TimedOut));
+        assert_eq!(err.transferred(), Some(2));
+        assert!(start.elapsed() < Duration::from_millis(500));
+        assert_eq!(port.read_timeout(), Timeout::Duration(Duration::from_millis(1000)));
+    }
+
+    #[test]
+    fn write_all_deadline_reports_bytes_written_on_timeout() {
+        let mut port = ScriptedPort::new(&[]);
+        port.capacity = 5;
+
+        let err = port.write_all_deadline(b"abcdefgh", deadline()).unwrap_err();
+
+        assert_eq!(err.kind(), ErrorKind::Io(io::ErrorKind::TimedOut));
+        assert_eq!(err.transferred(), Some(5));
+        assert_eq!(port.output, b"abcde");
+        assert_eq!(port.write_timeout(), Timeout::Duration(Duration::from_millis(2000)));
+    }
+
+    #[test]
+    fn flow_control_operations_are_unsupported_by_default() {
+        let mut port = ScriptedPort::new(&[]);
+
+        let err = port.suspend_output().unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Unsupported);
+        assert_eq!(err.operation(), Some("suspend_output"));
+        assert_eq!(port.is_output_stopped().unwrap_err().kind(), ErrorKind::Unsupported);
+    }
+
+    #[test]
+    fn wait_transmit_complete_is_unsupported_by_default() {
+        let mut port = ScriptedPort::new(&[]);
+
+        let err = port.wait_transmit_complete(Timeout::Infinite).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Unsupported);
+        assert_eq!(err.operation(), Some("wait_transmit_complete"));
+    }
+
+    #[test]
+    fn verify_settings_accepts_applied_settings() {
+        let mut actual = default_port_settings();
+        actual.baud_rate = BaudOther(9600);
+
+        assert!(verify_settings(&default_port_settings(), &actual).is_ok());
+    }
+
+    #[test]
+    fn verify_settings_lists_settings_that_differ() {
+        let mut requested = default_port_settings();
+        requested.baud_rate = BaudOther(3000000);
+        requested.parity = ParityEven;
+
+        let err = verify_settings(&requested, &default_port_settings()).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+        assert_eq!(err.to_string(), "Settings were not applied: \
+                                     baud rate: requested BaudOther(3000000), got Some(Baud9600); \
+                                     parity: requested ParityEven, got Some(ParityNone)");
+    }
+
+    #[test]
+    fn configure_verified_does_not_rewrite_settings() {
+        let mut port = ScriptedPort::new(&[]);
+        let mut settings = default_port_settings();
+        settings.baud_rate = Baud115200;
+
+        port.configure_verified(&settings).unwrap();
+        assert_eq!(port.settings, settings);
+        assert_eq!(port.settings_written, 1);
+    }
+
+    #[test]
+    fn set_timeout_sets_read_and_write_timeouts() {
+        let mut port = ScriptedPort::new(&[]);
+
+        port.set_timeout(Timeout::Infinite).unwrap();
+        assert_eq!(port.read_timeout(), Timeout::Infinite);
+        assert_eq!(port.write_timeout(), Timeout::Infinite);
+        assert_eq!(port.timeout(), Timeout::Infinite);
+    }
+
+    #[test]
+    fn read_until_stops_at_delimiter() {
+        let mut port = ScriptedPort::new(&[b"OK\r\nrest"]);
+        let mut buf = Vec::new();
+
+        assert_eq!(port.read_until(b'\n', &mut buf, deadline()).unwrap(), 4);
+        assert_eq!(buf, b"OK\r\n");
+        assert_eq!(port.input.pop_front(), Some(b"rest".to_vec()));
+    }
+
+    #[test]
+    fn read_until_keeps_partial_line_on_timeout() {
+        let mut port = ScriptedPort::new(&[b"OK"]);
+        let mut buf = Vec::new();
+
+        let err = port.read_until(b'\n', &mut buf, deadline()).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Io(io::ErrorKind::TimedOut));
+        assert_eq!(err.transferred(), Some(2));
+        assert_eq!(err.to_string(), "Operation timed out after transferring 2 bytes");
+        assert_eq!(buf, b"OK");
+    }
+
+    #[test]
+    fn read_until_sets_timeout_only_when_it_changes() {
+        let mut port = ScriptedPort::new(&[b"OK\r\n"]);
+        let mut buf = Vec::new();
+
+        // once for the deadline and once to restore the timeout
+        assert_eq!(port.read_until(b'\n', &mut buf, deadline()).unwrap(), 4);
+        assert_eq!(port.read_timeouts_set, 2);
+    }
 }
@@ -0,0 +1,89 @@
+// Copyright (c) 2015 David Cuddeback
+//
+// Permission is hereby granted, free of charge, to any person obtaining
+// a copy of this software and associated documentation files (the
+// "Software"), to deal in the Software without restriction, including
+// without limitation the rights to use, copy, modify, merge, publish,
+// distribute, sublicense, and/or sell copies of the Software, and to
+// permit persons to whom the Software is furnished to do so, subject to
+// the following conditions:
+//
+// The above copyright notice and this permission notice shall be
+// included in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND,
+// EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF
+// MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE
+// LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION
+// WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+//! Checks shared by the tests of serial port implementations.
+//!
+//! This module is only available with the `testing` feature.
+
+use std::io;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use {SerialPort, Timeout};
+
+/// Checks that reads from `port` follow the contract documented for [`Timeout`](../enum.Timeout.html).
+///
+/// `send` must make the given bytes available to be read from `port`, e.g., by writing them to the
+/// other side of a pseudoterminal or a null modem cable. Its last call is made from another thread
+/// while `port` is waiting in a read with an infinite timeout. No data may be pending on `port`
+/// when this function is called.
+///
+/// ## Panics
+///
+/// This function panics if `port` doesn't follow the contract.
+pub fn check_timeout_contract<P, F>(port: &mut P, mut send: F)
+    where P: SerialPort,
+          F: FnMut(&[u8]) + Send + 'static
+{
+    let timeout = Duration::from_millis(50);
+    let mut buf = [0u8; 16];
+
+    for &t in &[Timeout::Infinite, Timeout::NonBlocking, Timeout::Duration(timeout)] {
+        port.set_read_timeout(t).unwrap();
+        assert_eq!(port.read(&mut []).unwrap(), 0, "read into empty buffer with {:?} timeout", t);
+    }
+
+    port.set_read_timeout(Timeout::NonBlocking).unwrap();
+    let err = port.read(&mut buf).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::WouldBlock, "non-blocking read without data");
+
+    port.set_read_timeout(Timeout::Duration(timeout)).unwrap();
+    let start = Instant::now();
+    let err = port.read(&mut buf).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::TimedOut, "read without data");
+    assert!(start.elapsed() >= timeout / 2, "read returned {:?} before its timeout", start.elapsed());
+
+    // data that's available is returned without waiting to fill the buffer
+    send(b"abc");
+    port.set_read_timeout(Timeout::Duration(Duration::from_secs(1))).unwrap();
+
+    let mut received = Vec::new();
+    while received.len() < 3 {
+        let start = Instant::now();
+        let len = port.read(&mut buf).unwrap();
+
+        assert!(len > 0, "read returned Ok(0) with data available");
+        assert!(start.elapsed() < Duration::from_secs(1), "read waited for its timeout with data available");
+        received.extend_from_slice(&buf[..len]);
+    }
+    assert_eq!(received, b"abc");
+
+    port.set_read_timeout(Timeout::Infinite).unwrap();
+
+    let sender = thread::spawn(move || {
+        thread::sleep(timeout);
+        send(b"x");
+    });
+
+    assert_eq!(port.read(&mut buf).unwrap(), 1, "read with infinite timeout");
+    assert_eq!(buf[0], b'x');
+    sender.join().unwrap();
+}
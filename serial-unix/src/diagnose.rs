@@ -0,0 +1,377 @@
+// Copyright (c) 2015 David Cuddeback
+//
+// Permission is hereby granted, free of charge, to any person obtaining
+// a copy of this software and associated documentation files (the
+// "Software"), to deal in the Software without restriction, including
+// without limitation the rights to use, copy, modify, merge, publish,
+// distribute, sublicense, and/or sell copies of the Software, and to
+// permit persons to whom the Software is furnished to do so, subject to
+// the following conditions:
+//
+// The above copyright notice and this permission notice shall be
+// included in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND,
+// EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF
+// MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE
+// LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION
+// WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+use core;
+use libc;
+
+use std::ffi::{CStr, CString};
+use std::fmt;
+use std::fs;
+use std::io::prelude::*;
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::ptr;
+
+use std::os::unix::prelude::*;
+
+/// A report on why a TTY device might be unavailable.
+///
+/// A `Diagnosis` is obtained by calling [`diagnose()`](fn.diagnose.html). It lists the processes
+/// that have the device open, whether the current user is allowed to open it, and whether a
+/// UUCP-style lock file exists for it.
+#[derive(Debug,Clone,PartialEq,Eq)]
+pub struct Diagnosis {
+    /// Processes that currently have the device open.
+    ///
+    /// Processes are found by scanning `/proc/*/fd`. Processes owned by other users can only be
+    /// found when running with sufficient privileges.
+    pub holders: Vec<Holder>,
+
+    /// Whether the current user has read and write permission on the device.
+    pub accessible: bool,
+
+    /// The name of the group that owns the device, or its numeric ID if it has no name.
+    pub group: String,
+
+    /// Whether the current user is a member of the group that owns the device.
+    pub in_group: bool,
+
+    /// The UUCP-style lock file for the device, if one exists.
+    pub lock_file: Option<LockFile>,
+}
+
+/// A process that has a TTY device open.
+#[derive(Debug,Clone,PartialEq,Eq)]
+pub struct Holder {
+    /// The process ID.
+    pub pid: u32,
+
+    /// The command name of the process, as reported by `/proc/<pid>/comm`.
+    pub command: String,
+}
+
+/// A UUCP-style lock file found for a TTY device.
+#[derive(Debug,Clone,PartialEq,Eq)]
+pub struct LockFile {
+    /// The path of the lock file, e.g., `/var/lock/LCK..ttyUSB0`.
+    pub path: PathBuf,
+
+    /// The process ID recorded in the lock file, if it could be read.
+    pub pid: Option<u32>,
+
+    /// Whether the process that created the lock file has exited, or the lock file records a PID
+    /// that can't belong to a process.
+    pub stale: bool,
+}
+
+/// Diagnoses why a TTY device might be unavailable.
+///
+/// This function is intended to explain errors such as "Device or resource busy" or "Permission
+/// denied" that occur while opening a serial port. It does not open the device itself. Lock files
+/// are looked for in `/var/lock`. To look in another directory, use
+/// [`diagnose_with_lock_dir()`](fn.diagnose_with_lock_dir.html).
+///
+/// ```no_run
+/// use std::path::Path;
+///
+/// let diagnosis = serial_unix::diagnose(Path::new("/dev/ttyUSB0")).unwrap();
+///
+/// for holder in &diagnosis.holders {
+///     println!("in use by {} ({})", holder.pid, holder.command);
+/// }
+/// ```
+///
+/// ## Errors
+///
+/// This function returns an error if the device's metadata could not be read:
+///
+/// * `NoDevice` if the device does not exist.
+/// * `Io` for any other error.
+pub fn diagnose(path: &Path) -> core::Result<Diagnosis> {
+    diagnose_with_lock_dir(path, Path::new(super::lock::DEFAULT_LOCK_DIR))
+}
+
+/// Diagnoses why a TTY device might be unavailable, looking for its lock file in `lock_dir`.
+///
+/// This is like [`diagnose()`](fn.diagnose.html) for devices locked with a lock directory set by
+/// `OpenOptions::lock_dir()`.
+///
+/// ## Errors
+///
+/// This function returns an error if the device's metadata could not be read:
+///
+/// * `NoDevice` if the device does not exist.
+/// * `Io` for any other error.
+pub fn diagnose_with_lock_dir(path: &Path, lock_dir: &Path) -> core::Result<Diagnosis> {
+    let metadata = try!(fs::metadata(path).map_err(|e| {
+        let errno = e.raw_os_error().unwrap_or(libc::EIO);
+        super::error::from_raw_os_error(errno).with_operation("stat").with_path(path)
+    }));
+
+    let gid = metadata.gid() as libc::gid_t;
+
+    Ok(Diagnosis {
+        holders: find_holders(&metadata),
+        accessible: is_accessible(path),
+        group: group_name(gid).unwrap_or_else(|| gid.to_string()),
+        in_group: current_groups().contains(&gid),
+        lock_file: find_lock_file(lock_dir, path),
+    })
+}
+
+impl Diagnosis {
+    /// Returns `true` if the diagnosis found nothing that would prevent opening the device.
+    pub fn is_clear(&self) -> bool {
+        let locked = self.lock_file.as_ref().map(|l| !l.stale) == Some(true);
+        self.holders.is_empty() && self.accessible && !locked
+    }
+}
+
+impl fmt::Display for Diagnosis {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut findings = Vec::<String>::new();
+
+        if !self.holders.is_empty() {
+            let holders = self.holders.iter()
+                .map(|h| format!("{} ({})", h.pid, h.command))
+                .collect::<Vec<_>>();
+
+            findings.push(format!("in use by {}", holders.join(", ")));
+        }
+
+        if let Some(ref lock_file) = self.lock_file {
+            findings.push(match (lock_file.pid, lock_file.stale) {
+                (Some(pid), false) => format!("locked by {} in {}", pid, lock_file.path.display()),
+                (Some(pid), true)  => format!("stale lock file {} left by {}", lock_file.path.display(), pid),
+                (None, true)       => format!("stale lock file {} with an invalid PID", lock_file.path.display()),
+                (None, false)      => format!("unreadable lock file {}", lock_file.path.display()),
+            });
+        }
+
+        if !self.accessible {
+            if self.in_group {
+                findings.push(String::from("permission denied"));
+            }
+            else {
+                findings.push(format!("permission denied; user is not in group {}", self.group));
+            }
+        }
+
+        if findings.is_empty() {
+            f.write_str("no problems found")
+        }
+        else {
+            f.write_str(&findings.join("; "))
+        }
+    }
+}
+
+fn find_holders(device: &fs::Metadata) -> Vec<Holder> {
+    let mut holders = Vec::new();
+
+    let processes = match fs::read_dir("/proc") {
+        Ok(processes) => processes,
+        Err(_) => return holders,
+    };
+
+    for process in processes.filter_map(|p| p.ok()) {
+        let pid = match process.file_name().to_str().and_then(|s| s.parse::<u32>().ok()) {
+            Some(pid) => pid,
+            None => continue,
+        };
+
+        let fds = match fs::read_dir(process.path().join("fd")) {
+            Ok(fds) => fds,
+            Err(_) => continue,
+        };
+
+        let is_holder = fds.filter_map(|fd| fd.ok()).any(|fd| {
+            match fs::metadata(fd.path()) {
+                Ok(metadata) => metadata.dev() == device.dev() && metadata.ino() == device.ino(),
+                Err(_) => false,
+            }
+        });
+
+        if is_holder {
+            holders.push(Holder {
+                pid: pid,
+                command: read_command(&process.path()),
+            });
+        }
+    }
+
+    holders
+}
+
+fn read_command(process: &Path) -> String {
+    let mut command = String::new();
+
+    match fs::File::open(process.join("comm")).and_then(|mut f| f.read_to_string(&mut command)) {
+        Ok(_) => command.trim().to_string(),
+        Err(_) => String::from("?"),
+    }
+}
+
+fn is_accessible(path: &Path) -> bool {
+    use libc::{R_OK, W_OK};
+
+    match CString::new(path.as_os_str().as_bytes()) {
+        Ok(cstr) => unsafe { libc::access(cstr.as_ptr(), R_OK | W_OK) == 0 },
+        Err(_) => false,
+    }
+}
+
+fn current_groups() -> Vec<libc::gid_t> {
+    unsafe {
+        let count = libc::getgroups(0, ptr::null_mut());
+        let mut groups = vec![0 as libc::gid_t; if count > 0 { count as usize } else { 0 }];
+
+        let count = libc::getgroups(groups.len() as libc::c_int, groups.as_mut_ptr());
+        groups.truncate(if count > 0 { count as usize } else { 0 });
+
+        groups.push(libc::getegid());
+        groups
+    }
+}
+
+fn group_name(gid: libc::gid_t) -> Option<String> {
+    let mut group: libc::group = unsafe { mem::zeroed() };
+    let mut buf = vec![0 as libc::c_char; 4096];
+    let mut result: *mut libc::group = ptr::null_mut();
+
+    unsafe {
+        if libc::getgrgid_r(gid, &mut group, buf.as_mut_ptr(), buf.len(), &mut result) != 0 || result.is_null() {
+            return None;
+        }
+
+        Some(CStr::from_ptr(group.gr_name).to_string_lossy().into_owned())
+    }
+}
+
+fn find_lock_file(lock_dir: &Path, device: &Path) -> Option<LockFile> {
+    let path = super::lock::lock_path(lock_dir, device)?;
+    let owner = super::lock::read_owner(&path).ok()?;
+
+    Some(LockFile {
+        path: path,
+        pid: owner.pid(),
+        stale: owner.is_stale(),
+    })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::io::prelude::*;
+    use std::path::{Path, PathBuf};
+    use std::process;
+
+    use super::{Diagnosis, Holder, LockFile};
+    use testing::temp_dir;
+
+    fn clear_diagnosis() -> Diagnosis {
+        Diagnosis {
+            holders: vec![],
+            accessible: true,
+            group: String::from("dialout"),
+            in_group: true,
+            lock_file: None,
+        }
+    }
+
+    #[test]
+    fn find_lock_file_detects_live_lock() {
+        let dir = temp_dir("diagnose-live");
+        let mut file = fs::File::create(dir.join("LCK..ttyUSB0")).unwrap();
+        writeln!(file, "{:10}", process::id()).unwrap();
+
+        let lock_file = super::find_lock_file(&dir, Path::new("/dev/ttyUSB0")).unwrap();
+        assert_eq!(lock_file.pid, Some(process::id()));
+        assert!(!lock_file.stale);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn find_lock_file_ignores_missing_lock() {
+        let dir = temp_dir("diagnose-missing");
+        assert_eq!(super::find_lock_file(&dir, Path::new("/dev/ttyUSB0")), None);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn diagnose_finds_current_process_as_holder() {
+        let dir = temp_dir("diagnose-holder");
+        let path = dir.join("device");
+        let _file = fs::File::create(&path).unwrap();
+
+        let diagnosis = super::diagnose(&path).unwrap();
+        assert!(diagnosis.holders.iter().any(|h| h.pid == process::id()));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn diagnose_with_lock_dir_finds_lock_file() {
+        let dir = temp_dir("diagnose-lock-dir");
+        let path = dir.join("ttyUSB0");
+        let _file = fs::File::create(&path).unwrap();
+
+        let mut lock = fs::File::create(dir.join("LCK..ttyUSB0")).unwrap();
+        writeln!(lock, "{:10}", process::id()).unwrap();
+
+        let diagnosis = super::diagnose_with_lock_dir(&path, &dir).unwrap();
+        assert_eq!(diagnosis.lock_file.unwrap().path, dir.join("LCK..ttyUSB0"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn diagnosis_displays_findings() {
+        let mut diagnosis = clear_diagnosis();
+        diagnosis.holders.push(Holder { pid: 42, command: String::from("ModemManager") });
+        diagnosis.accessible = false;
+        diagnosis.in_group = false;
+        diagnosis.lock_file = Some(LockFile {
+            path: PathBuf::from("/var/lock/LCK..ttyUSB0"),
+            pid: Some(42),
+            stale: false,
+        });
+
+        assert_eq!(diagnosis.to_string(),
+                   "in use by 42 (ModemManager); locked by 42 in /var/lock/LCK..ttyUSB0; \
+                    permission denied; user is not in group dialout");
+    }
+
+    #[test]
+    fn diagnosis_is_clear_with_stale_lock() {
+        let mut diagnosis = clear_diagnosis();
+        diagnosis.lock_file = Some(LockFile {
+            path: PathBuf::from("/var/lock/LCK..ttyUSB0"),
+            pid: Some(42),
+            stale: true,
+        });
+
+        assert!(diagnosis.is_clear());
+        assert_eq!(diagnosis.to_string(), "stale lock file /var/lock/LCK..ttyUSB0 left by 42");
+    }
+}
@@ -32,6 +32,11 @@ pub fn last_os_error() -> core::Error {
 }
 
 pub fn from_raw_os_error(errno: i32) -> core::Error {
+    from_raw_os_error_with_detail(errno, None)
+}
+
+// Like `from_raw_os_error()`, but appends `detail` to the system's description of the error.
+pub fn from_raw_os_error_with_detail(errno: i32, detail: Option<&str>) -> core::Error {
     use libc::{EBUSY, EISDIR, ELOOP, ENOTDIR, ENOENT, ENODEV, ENXIO, EACCES, EINVAL, ENAMETOOLONG, EINTR, EWOULDBLOCK};
 
     let kind = match errno {
@@ -43,7 +48,12 @@ pub fn from_raw_os_error(errno: i32) -> core::Error {
         _           => core::ErrorKind::Io(io::ErrorKind::Other),
     };
 
-    core::Error::new(kind, error_string(errno))
+    let description = match detail {
+        Some(detail) => format!("{} ({})", error_string(errno), detail),
+        None => error_string(errno),
+    };
+
+    core::Error::new(kind, description).with_source(io::Error::from_raw_os_error(errno))
 }
 
 // the rest of this module is borrowed from libstd
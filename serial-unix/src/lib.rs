@@ -24,15 +24,42 @@
 extern crate serial_core as core;
 extern crate libc;
 
+pub use diagnose::*;
+pub use marked::*;
 pub use tty::*;
 
+#[cfg(target_os = "linux")]
+pub use monitor::*;
+
+#[cfg(target_os = "linux")]
+pub use rs485::Rs485Config;
+
+#[cfg(target_os = "linux")]
+pub use serial_info::SerialInfo;
+
+mod diagnose;
 mod error;
+mod lock;
+mod marked;
+
+#[cfg(target_os = "linux")]
+mod monitor;
+
 mod poll;
 
+#[cfg(target_os = "linux")]
+mod rs485;
+
+#[cfg(target_os = "linux")]
+mod serial_info;
+
 #[cfg(not(target_os = "linux"))]
 mod termios;
 
 #[cfg(target_os = "linux")]
 mod termios2;
 
+#[cfg(test)]
+mod testing;
+
 mod tty;
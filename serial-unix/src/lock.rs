@@ -0,0 +1,524 @@
+// Copyright (c) 2015 David Cuddeback
+//
+// Permission is hereby granted, free of charge, to any person obtaining
+// a copy of this software and associated documentation files (the
+// "Software"), to deal in the Software without restriction, including
+// without limitation the rights to use, copy, modify, merge, publish,
+// distribute, sublicense, and/or sell copies of the Software, and to
+// permit persons to whom the Software is furnished to do so, subject to
+// the following conditions:
+//
+// The above copyright notice and this permission notice shall be
+// included in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND,
+// EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF
+// MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE
+// LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION
+// WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+//! UUCP-style lock files, as used by minicom, picocom and other legacy tools.
+//!
+//! A lock file for `/dev/ttyUSB0` is named `LCK..ttyUSB0` and contains the PID of the process
+//! that owns the device, formatted as ten right-aligned ASCII digits followed by a newline.
+
+use core;
+use libc;
+
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::ptr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use std::os::unix::prelude::*;
+
+pub const DEFAULT_LOCK_DIR: &str = "/var/lock";
+
+/// A lock file held by the current process. The lock file is removed when dropped.
+#[derive(Debug)]
+pub struct LockGuard {
+    path: PathBuf,
+}
+
+impl LockGuard {
+    /// Creates a lock file for `device` in `lock_dir`.
+    ///
+    /// Lock files left behind by processes that no longer exist are removed. If the device is
+    /// locked by a running process, a `NoDevice` error is returned.
+    ///
+    /// The lock file is written to a temporary file first and then linked into place, so other
+    /// processes never see an incomplete lock file.
+    pub fn acquire(lock_dir: &Path, device: &Path) -> core::Result<Self> {
+        use libc::EINVAL;
+
+        let path = match lock_path(lock_dir, device) {
+            Some(path) => path,
+            None => return Err(super::error::from_raw_os_error(EINVAL).with_path(device)),
+        };
+
+        let temp = temp_path(lock_dir, "LTMP");
+        try!(create(&temp).map_err(|e| io_error(e, &temp)));
+
+        let result = link(&temp, &path);
+        let _ = fs::remove_file(&temp);
+
+        result.map(|()| LockGuard { path: path })
+    }
+
+    /// Returns the path of the lock file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for LockGuard {
+    fn drop(&mut self) {
+        // don't remove a lock file that was replaced by another process
+        if let Ok(Owner::Process(pid)) = read_owner(&self.path) {
+            if pid == process::id() {
+                let _ = fs::remove_file(&self.path);
+            }
+        }
+    }
+}
+
+/// Returns the path of the lock file for `device` in `lock_dir`.
+///
+/// Symbolic links to the device, such as `/dev/serial/by-id/...`, are resolved, so the lock file is
+/// named after the device node like the lock files of other programs.
+pub fn lock_path(lock_dir: &Path, device: &Path) -> Option<PathBuf> {
+    let device = fs::canonicalize(device).unwrap_or_else(|_| device.to_path_buf());
+
+    device.file_name().map(|name| {
+        let mut lock_name = OsString::from("LCK..");
+        lock_name.push(name);
+
+        lock_dir.join(lock_name)
+    })
+}
+
+// Links the complete lock file `temp` to `path`.
+fn link(temp: &Path, path: &Path) -> core::Result<()> {
+    use libc::EEXIST;
+
+    // A stale lock file is removed at most once. If the lock file reappears, another process
+    // won the race to lock the device.
+    for attempt in 0..2 {
+        match fs::hard_link(temp, path) {
+            Ok(()) => return Ok(()),
+            Err(ref e) if e.raw_os_error() == Some(EEXIST) => (),
+            Err(e) => return Err(io_error(e, path)),
+        }
+
+        let owner = match read_owner(path) {
+            Ok(owner) => owner,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound && attempt == 0 => continue,
+            Err(e) => return Err(io_error(e, path)),
+        };
+
+        match owner {
+            Owner::Process(pid) if process_exists(pid) => {
+                return Err(locked_error(path, &format!("locked by process {}", pid)));
+            }
+            Owner::Unknown => return Err(locked_error(path, "unreadable lock file")),
+            _ if attempt == 0 => try!(remove_stale(path).map_err(|e| io_error(e, path))),
+            _ => break,
+        }
+    }
+
+    Err(locked_error(path, "locked by another process"))
+}
+
+// Removes the lock file at `path` if it's stale.
+//
+// Processes that find the same stale lock file take turns with an exclusive `flock()` on it. While
+// holding the `flock()`, the lock file is checked to be stale and to still be linked at `path`, so
+// only the verified stale file is ever removed. If another process holds the `flock()`, the lock
+// file is left for that process to remove.
+fn remove_stale(path: &Path) -> io::Result<()> {
+    use libc::{EWOULDBLOCK, LOCK_EX, LOCK_NB};
+
+    let mut file = match fs::File::open(path) {
+        Ok(file) => file,
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e),
+    };
+
+    if unsafe { libc::flock(file.as_raw_fd(), LOCK_EX | LOCK_NB) } < 0 {
+        let error = io::Error::last_os_error();
+
+        return match error.raw_os_error() {
+            Some(EWOULDBLOCK) => Ok(()),
+            _ => Err(error),
+        };
+    }
+
+    if !try!(read_file_owner(&mut file)).is_stale() {
+        return Ok(());
+    }
+
+    let metadata = try!(file.metadata());
+    let id = (metadata.dev(), metadata.ino());
+
+    // another process may have removed the stale lock file and locked the device in the meantime
+    match fs::symlink_metadata(path) {
+        Ok(ref linked) if (linked.dev(), linked.ino()) == id => fs::remove_file(path),
+        Ok(_) => Ok(()),
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+// Returns a path in `dir` that's unique to this call, for a temporary file.
+fn temp_path(dir: &Path, prefix: &str) -> PathBuf {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+    let count = COUNT.fetch_add(1, Ordering::SeqCst);
+    dir.join(format!("{}.{}.{}", prefix, process::id(), count))
+}
+
+/// The owner recorded in a lock file.
+#[derive(Debug,Copy,Clone,PartialEq,Eq)]
+pub enum Owner {
+    /// The PID of the process that created the lock file.
+    Process(u32),
+
+    /// A PID that can't belong to a process, such as 0 or a PID out of the range of `pid_t`. The
+    /// lock file is stale.
+    Invalid,
+
+    /// The lock file doesn't contain a PID. It may still be being written by another program.
+    Unknown,
+}
+
+impl Owner {
+    /// Returns the PID recorded in the lock file, if it's valid.
+    pub fn pid(&self) -> Option<u32> {
+        match *self {
+            Owner::Process(pid) => Some(pid),
+            Owner::Invalid | Owner::Unknown => None,
+        }
+    }
+
+    /// Returns `true` if the lock file was left behind by a process that no longer exists.
+    pub fn is_stale(&self) -> bool {
+        match *self {
+            Owner::Process(pid) => !process_exists(pid),
+            Owner::Invalid => true,
+            Owner::Unknown => false,
+        }
+    }
+}
+
+/// Reads the owner recorded in a lock file.
+pub fn read_owner(path: &Path) -> io::Result<Owner> {
+    fs::File::open(path).and_then(|mut file| read_file_owner(&mut file))
+}
+
+fn read_file_owner(file: &mut fs::File) -> io::Result<Owner> {
+    let mut contents = Vec::new();
+    try!(file.read_to_end(&mut contents));
+
+    Ok(parse_pid(&contents))
+}
+
+/// Returns `true` if a process with the given PID exists.
+///
+/// Returns `false` for PIDs that can't belong to a process, which `kill()` would interpret as a
+/// process group.
+pub fn process_exists(pid: u32) -> bool {
+    use libc::EPERM;
+
+    if pid as libc::pid_t <= 0 {
+        return false;
+    }
+
+    unsafe {
+        libc::kill(pid as libc::pid_t, 0) == 0 || super::error::errno() == EPERM
+    }
+}
+
+// Lock files contain the owner's PID either as ASCII text (HDB UUCP format) or as a native 4-byte
+// integer (older binary format). PIDs of 0 and above the range of `pid_t` are rejected.
+fn parse_pid(contents: &[u8]) -> Owner {
+    let text = String::from_utf8_lossy(contents);
+
+    let pid = match text.trim().parse::<u32>() {
+        Ok(pid) => pid,
+        Err(_) if contents.len() == 4 => unsafe {
+            ptr::read_unaligned(contents.as_ptr() as *const u32)
+        },
+        Err(_) => return Owner::Unknown,
+    };
+
+    if pid as libc::pid_t <= 0 {
+        Owner::Invalid
+    }
+    else {
+        Owner::Process(pid)
+    }
+}
+
+fn create(path: &Path) -> io::Result<()> {
+    let mut file = try!(fs::OpenOptions::new().write(true).create_new(true).mode(0o644).open(path));
+
+    if let Err(e) = writeln!(file, "{:10}", process::id()) {
+        let _ = fs::remove_file(path);
+        return Err(e);
+    }
+
+    Ok(())
+}
+
+fn io_error(error: io::Error, path: &Path) -> core::Error {
+    let errno = error.raw_os_error().unwrap_or(libc::EIO);
+    super::error::from_raw_os_error(errno).with_path(path)
+}
+
+fn locked_error(path: &Path, detail: &str) -> core::Error {
+    super::error::from_raw_os_error_with_detail(libc::EBUSY, Some(detail)).with_path(path)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use core;
+
+    use std::ffi::OsString;
+    use std::fs;
+    use std::io::prelude::*;
+    use std::path::{Path, PathBuf};
+    use std::process;
+    use std::slice;
+    use std::sync::{Arc, Barrier};
+    use std::thread;
+
+    use libc;
+
+    use std::os::unix;
+    use std::os::unix::prelude::*;
+
+    use super::{LockGuard, Owner};
+    use testing::temp_dir;
+
+    // A PID above the kernel's maximum PID, so it never belongs to a running process.
+    const DEAD_PID: u32 = 0x7ffffffe;
+
+    fn read_lock(dir: &Path) -> String {
+        let mut contents = String::new();
+        fs::File::open(dir.join("LCK..ttyUSB0")).unwrap().read_to_string(&mut contents).unwrap();
+        contents
+    }
+
+    #[test]
+    fn lock_path_uses_device_name() {
+        let path = super::lock_path(Path::new("/var/lock"), Path::new("/dev/ttyUSB0"));
+        assert_eq!(path, Some(PathBuf::from("/var/lock/LCK..ttyUSB0")));
+    }
+
+    #[test]
+    fn parse_pid_reads_ascii_format() {
+        assert_eq!(super::parse_pid(b"      1234\n"), Owner::Process(1234));
+    }
+
+    #[test]
+    fn parse_pid_reads_binary_format() {
+        let pid = 1234u32;
+        let bytes = unsafe { slice::from_raw_parts(&pid as *const u32 as *const u8, 4) };
+        assert_eq!(super::parse_pid(bytes), Owner::Process(1234));
+    }
+
+    #[test]
+    fn parse_pid_rejects_garbage() {
+        assert_eq!(super::parse_pid(b"not a pid\n"), Owner::Unknown);
+    }
+
+    #[test]
+    fn parse_pid_rejects_pid_zero() {
+        assert_eq!(super::parse_pid(b"         0\n"), Owner::Invalid);
+        assert_eq!(super::parse_pid(&[0, 0, 0, 0]), Owner::Invalid);
+    }
+
+    #[test]
+    fn parse_pid_rejects_pid_out_of_range() {
+        assert_eq!(super::parse_pid(b"4294967295\n"), Owner::Invalid);
+        assert_eq!(super::parse_pid(&[0xff, 0xff, 0xff, 0xff]), Owner::Invalid);
+    }
+
+    #[test]
+    fn process_exists_rejects_process_groups() {
+        assert!(!super::process_exists(0));
+        assert!(!super::process_exists(0xffffffff));
+    }
+
+    #[test]
+    fn acquire_writes_pid_in_standard_format() {
+        let dir = temp_dir("lock-format");
+
+        let lock = LockGuard::acquire(&dir, Path::new("/dev/ttyUSB0")).unwrap();
+        assert_eq!(read_lock(&dir), format!("{:10}\n", process::id()));
+
+        drop(lock);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn acquire_fails_when_locked_by_live_process() {
+        let dir = temp_dir("lock-live");
+
+        let _lock = LockGuard::acquire(&dir, Path::new("/dev/ttyUSB0")).unwrap();
+        let err = LockGuard::acquire(&dir, Path::new("/dev/ttyUSB0")).unwrap_err();
+        assert_eq!(err.kind(), core::ErrorKind::NoDevice);
+        assert!(err.to_string().contains(&format!("locked by process {}", process::id())));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn acquire_replaces_stale_lock() {
+        let dir = temp_dir("lock-stale");
+        let mut file = fs::File::create(dir.join("LCK..ttyUSB0")).unwrap();
+        writeln!(file, "{:10}", DEAD_PID).unwrap();
+
+        let _lock = LockGuard::acquire(&dir, Path::new("/dev/ttyUSB0")).unwrap();
+        assert_eq!(read_lock(&dir), format!("{:10}\n", process::id()));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn acquire_replaces_lock_with_invalid_pid() {
+        let dir = temp_dir("lock-invalid");
+        let mut file = fs::File::create(dir.join("LCK..ttyUSB0")).unwrap();
+        file.write_all(&[0xff, 0xff, 0xff, 0xff]).unwrap();
+
+        let _lock = LockGuard::acquire(&dir, Path::new("/dev/ttyUSB0")).unwrap();
+        assert_eq!(read_lock(&dir), format!("{:10}\n", process::id()));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn acquire_removes_temporary_file() {
+        let dir = temp_dir("lock-temp");
+
+        let _lock = LockGuard::acquire(&dir, Path::new("/dev/ttyUSB0")).unwrap();
+        let names: Vec<_> = fs::read_dir(&dir).unwrap().map(|entry| entry.unwrap().file_name()).collect();
+        assert_eq!(names, vec![OsString::from("LCK..ttyUSB0")]);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn acquire_grants_stale_lock_to_one_contender() {
+        let dir = temp_dir("lock-contenders");
+        let mut file = fs::File::create(dir.join("LCK..ttyUSB0")).unwrap();
+        writeln!(file, "{:10}", DEAD_PID).unwrap();
+
+        let barrier = Arc::new(Barrier::new(8));
+
+        let threads: Vec<_> = (0..8).map(|_| {
+            let dir = dir.clone();
+            let barrier = barrier.clone();
+
+            thread::spawn(move || {
+                barrier.wait();
+                LockGuard::acquire(&dir, Path::new("/dev/ttyUSB0"))
+            })
+        }).collect();
+
+        let locks: Vec<_> = threads.into_iter().map(|thread| thread.join().unwrap()).collect();
+        assert_eq!(locks.iter().filter(|lock| lock.is_ok()).count(), 1);
+
+        drop(locks);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn remove_stale_keeps_lock_that_replaced_stale_lock() {
+        let dir = temp_dir("lock-replaced");
+        let path = dir.join("LCK..ttyUSB0");
+
+        let mut file = fs::File::create(&path).unwrap();
+        writeln!(file, "{:10}", DEAD_PID).unwrap();
+
+        // another contender removes the stale lock and locks the device before this one
+        fs::remove_file(&path).unwrap();
+        let _lock = LockGuard::acquire(&dir, Path::new("/dev/ttyUSB0")).unwrap();
+
+        super::remove_stale(&path).unwrap();
+        assert_eq!(read_lock(&dir), format!("{:10}\n", process::id()));
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn remove_stale_leaves_stale_lock_to_contender_removing_it() {
+        let dir = temp_dir("lock-interleaved");
+        let path = dir.join("LCK..ttyUSB0");
+
+        let mut file = fs::File::create(&path).unwrap();
+        writeln!(file, "{:10}", DEAD_PID).unwrap();
+
+        // a second contender is removing the stale lock
+        let stale = fs::File::open(&path).unwrap();
+        assert_eq!(unsafe { libc::flock(stale.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) }, 0);
+
+        super::remove_stale(&path).unwrap();
+        assert_eq!(read_lock(&dir), format!("{:10}\n", DEAD_PID));
+
+        // the second contender removes the stale lock, then a third one locks the device
+        fs::remove_file(&path).unwrap();
+        drop(stale);
+        let _lock = LockGuard::acquire(&dir, Path::new("/dev/ttyUSB0")).unwrap();
+
+        super::remove_stale(&path).unwrap();
+        assert_eq!(read_lock(&dir), format!("{:10}\n", process::id()));
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn lock_path_resolves_symlinks() {
+        let dir = temp_dir("lock-symlink");
+        fs::File::create(dir.join("ttyUSB0")).unwrap();
+        unix::fs::symlink(dir.join("ttyUSB0"), dir.join("usb-FTDI_FT232R_A9M9DV3R-if00-port0")).unwrap();
+
+        let path = super::lock_path(Path::new("/var/lock"), &dir.join("usb-FTDI_FT232R_A9M9DV3R-if00-port0"));
+        assert_eq!(path, Some(PathBuf::from("/var/lock/LCK..ttyUSB0")));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn drop_removes_lock_file() {
+        let dir = temp_dir("lock-drop");
+
+        drop(LockGuard::acquire(&dir, Path::new("/dev/ttyUSB0")).unwrap());
+        assert!(!dir.join("LCK..ttyUSB0").exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn drop_keeps_lock_file_of_other_process() {
+        let dir = temp_dir("lock-other");
+
+        let lock = LockGuard::acquire(&dir, Path::new("/dev/ttyUSB0")).unwrap();
+        let mut file = fs::File::create(dir.join("LCK..ttyUSB0")).unwrap();
+        writeln!(file, "{:10}", DEAD_PID).unwrap();
+
+        drop(lock);
+        assert!(dir.join("LCK..ttyUSB0").exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,294 @@
+// Copyright (c) 2015 David Cuddeback
+//
+// Permission is hereby granted, free of charge, to any person obtaining
+// a copy of this software and associated documentation files (the
+// "Software"), to deal in the Software without restriction, including
+// without limitation the rights to use, copy, modify, merge, publish,
+// distribute, sublicense, and/or sell copies of the Software, and to
+// permit persons to whom the Software is furnished to do so, subject to
+// the following conditions:
+//
+// The above copyright notice and this permission notice shall be
+// included in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND,
+// EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF
+// MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE
+// LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION
+// WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+use core;
+
+use std::collections::VecDeque;
+use std::io;
+use std::io::prelude::*;
+
+use core::{SerialDevice, SerialPortSettings};
+
+use super::{BreakHandling, ParityErrorHandling, TTYPort, UnixSettingsExt};
+
+// The byte that starts a marked sequence when PARMRK is set.
+const MARK: u8 = 0xff;
+
+/// An item read from a TTY device with line errors marked in the data.
+#[derive(Debug,Clone,PartialEq,Eq)]
+pub enum ReadItem {
+    /// Bytes received without errors.
+    Data(Vec<u8>),
+
+    /// A byte received with a parity error.
+    ParityError(u8),
+
+    /// A byte received with a framing error.
+    FramingError,
+
+    /// A break condition.
+    Break,
+}
+
+/// A decoder for data read from a TTY device with parity errors marked (`PARMRK`).
+///
+/// With `PARMRK`, the kernel reads a byte with an error as the sequence `\xff \0 <byte>` and a
+/// break as `\xff \0 \0`. A valid `\xff` byte is read as `\xff \xff`. Sequences may be split across
+/// reads, so the decoder keeps incomplete sequences until the rest is decoded.
+///
+/// TTY devices mark framing errors the same way as parity errors. When the port doesn't use
+/// parity, every marked byte must be a framing error, so it's decoded as
+/// `ReadItem::FramingError`. Otherwise it's decoded as `ReadItem::ParityError`.
+#[derive(Debug,Clone)]
+pub struct MarkDecoder {
+    parity: bool,
+    pending: usize,
+}
+
+impl MarkDecoder {
+    /// Creates a decoder for a port that does (`parity = true`) or doesn't use parity.
+    pub fn new(parity: bool) -> Self {
+        MarkDecoder {
+            parity: parity,
+            pending: 0,
+        }
+    }
+
+    /// Decodes `input`, appending the decoded items to `items`.
+    ///
+    /// Consecutive bytes without errors are appended as a single `ReadItem::Data`, which is merged
+    /// with a `ReadItem::Data` at the end of `items`.
+    pub fn decode(&mut self, input: &[u8], items: &mut Vec<ReadItem>) {
+        for &byte in input {
+            match (self.pending, byte) {
+                (0, MARK) => self.pending = 1,
+                (0, _) => push_data(items, byte),
+                (1, MARK) => {
+                    self.pending = 0;
+                    push_data(items, MARK);
+                }
+                (1, 0) => self.pending = 2,
+                (1, _) => {
+                    // not a valid sequence, so the mark is passed through as data
+                    self.pending = 0;
+                    push_data(items, MARK);
+                    push_data(items, byte);
+                }
+                (_, 0) => {
+                    self.pending = 0;
+                    items.push(ReadItem::Break);
+                }
+                (_, _) => {
+                    self.pending = 0;
+                    items.push(self.error(byte));
+                }
+            }
+        }
+    }
+
+    /// Returns `true` if the decoder holds the start of an incomplete sequence.
+    pub fn is_pending(&self) -> bool {
+        self.pending != 0
+    }
+
+    fn error(&self, byte: u8) -> ReadItem {
+        if self.parity {
+            ReadItem::ParityError(byte)
+        }
+        else {
+            ReadItem::FramingError
+        }
+    }
+}
+
+fn push_data(items: &mut Vec<ReadItem>, byte: u8) {
+    if let Some(&mut ReadItem::Data(ref mut data)) = items.last_mut() {
+        data.push(byte);
+        return;
+    }
+
+    items.push(ReadItem::Data(vec![byte]));
+}
+
+/// A reader that reports where line errors and breaks occur in the data received by a port.
+///
+/// ```no_run
+/// use std::path::Path;
+/// use serial_unix::{MarkedReader, ReadItem, TTYPort};
+///
+/// let port = TTYPort::open(Path::new("/dev/ttyUSB0")).unwrap();
+/// let mut reader = MarkedReader::new(port).unwrap();
+///
+/// loop {
+///     match reader.read_item().unwrap() {
+///         ReadItem::Break => println!("break"),
+///         item => println!("{:?}", item),
+///     }
+/// }
+/// ```
+pub struct MarkedReader<R> {
+    inner: R,
+    decoder: MarkDecoder,
+    items: VecDeque<ReadItem>,
+    buffer: Vec<u8>,
+}
+
+impl MarkedReader<TTYPort> {
+    /// Configures `port` to mark line errors and wraps it in a reader.
+    ///
+    /// This enables parity checking (`INPCK`) and marking of errors (`PARMRK`), disables stripping
+    /// of the eighth bit, and reads breaks into the data. Parity checking doesn't require the port
+    /// to use parity. Framing errors and breaks are detected either way.
+    ///
+    /// ## Errors
+    ///
+    /// This function returns an error if the port's settings could not be changed.
+    pub fn new(mut port: TTYPort) -> core::Result<Self> {
+        let mut settings = try!(port.read_settings());
+
+        settings.set_parity_check(true);
+        settings.set_parity_error_handling(ParityErrorHandling::Mark);
+        settings.set_strip_high_bit(false);
+        settings.set_break_handling(BreakHandling::Read);
+
+        try!(port.write_settings(&settings));
+
+        let parity = settings.parity().unwrap_or(core::ParityNone) != core::ParityNone;
+        Ok(MarkedReader::with_decoder(port, MarkDecoder::new(parity)))
+    }
+}
+
+impl<R: Read> MarkedReader<R> {
+    /// Wraps a reader that's already configured to mark line errors.
+    pub fn with_decoder(inner: R, decoder: MarkDecoder) -> Self {
+        MarkedReader {
+            inner: inner,
+            decoder: decoder,
+            items: VecDeque::new(),
+            buffer: vec![0; 1024],
+        }
+    }
+
+    /// Reads the next item.
+    ///
+    /// Errors from the underlying reader, such as an expired timeout, are returned as they are.
+    /// Data that was already read is kept and returned by later calls.
+    ///
+    /// ## Errors
+    ///
+    /// This function returns an `UnexpectedEof` error if the underlying reader reaches end of file.
+    pub fn read_item(&mut self) -> io::Result<ReadItem> {
+        let mut items = Vec::new();
+
+        while self.items.is_empty() {
+            let len = try!(self.inner.read(&mut self.buffer));
+
+            if len == 0 {
+                return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "end of file"));
+            }
+
+            self.decoder.decode(&self.buffer[..len], &mut items);
+            self.items.extend(items.drain(..));
+        }
+
+        Ok(self.items.pop_front().unwrap())
+    }
+
+    /// Returns a reference to the underlying reader.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the underlying reader.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Returns the underlying reader. Items that were decoded but not returned are discarded.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use super::{MarkDecoder, MarkedReader, ReadItem};
+
+    fn decode(parity: bool, input: &[u8]) -> Vec<ReadItem> {
+        let mut items = Vec::new();
+        MarkDecoder::new(parity).decode(input, &mut items);
+        items
+    }
+
+    #[test]
+    fn decode_passes_data_through() {
+        assert_eq!(decode(true, b"abc"), vec![ReadItem::Data(b"abc".to_vec())]);
+    }
+
+    #[test]
+    fn decode_unescapes_mark_byte() {
+        assert_eq!(decode(true, b"a\xff\xffb"), vec![ReadItem::Data(b"a\xffb".to_vec())]);
+    }
+
+    #[test]
+    fn decode_reports_errors_in_position() {
+        assert_eq!(decode(true, b"a\xff\0xb\xff\0\0c"), vec![
+            ReadItem::Data(b"a".to_vec()),
+            ReadItem::ParityError(b'x'),
+            ReadItem::Data(b"b".to_vec()),
+            ReadItem::Break,
+            ReadItem::Data(b"c".to_vec()),
+        ]);
+    }
+
+    #[test]
+    fn decode_reports_framing_errors_without_parity() {
+        assert_eq!(decode(false, b"\xff\0x"), vec![ReadItem::FramingError]);
+    }
+
+    #[test]
+    fn decode_keeps_sequences_split_across_reads() {
+        let mut decoder = MarkDecoder::new(true);
+        let mut items = Vec::new();
+
+        decoder.decode(b"a\xff", &mut items);
+        assert!(decoder.is_pending());
+        decoder.decode(b"\0", &mut items);
+        decoder.decode(b"\0", &mut items);
+        assert!(!decoder.is_pending());
+
+        assert_eq!(items, vec![ReadItem::Data(b"a".to_vec()), ReadItem::Break]);
+    }
+
+    #[test]
+    fn marked_reader_reads_items_in_order() {
+        let input: &[u8] = b"ab\xff\0\0c";
+        let mut reader = MarkedReader::with_decoder(input, MarkDecoder::new(true));
+
+        assert_eq!(reader.read_item().unwrap(), ReadItem::Data(b"ab".to_vec()));
+        assert_eq!(reader.read_item().unwrap(), ReadItem::Break);
+        assert_eq!(reader.read_item().unwrap(), ReadItem::Data(b"c".to_vec()));
+        assert_eq!(reader.read_item().unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
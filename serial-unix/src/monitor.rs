@@ -0,0 +1,587 @@
+// Copyright (c) 2015 David Cuddeback
+//
+// Permission is hereby granted, free of charge, to any person obtaining
+// a copy of this software and associated documentation files (the
+// "Software"), to deal in the Software without restriction, including
+// without limitation the rights to use, copy, modify, merge, publish,
+// distribute, sublicense, and/or sell copies of the Software, and to
+// permit persons to whom the Software is furnished to do so, subject to
+// the following conditions:
+//
+// The above copyright notice and this permission notice shall be
+// included in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND,
+// EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF
+// MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE
+// LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION
+// WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+use core;
+use libc;
+
+use std::collections::{HashMap, VecDeque};
+use std::ffi::{CString, OsStr};
+use std::fs;
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::ptr;
+use std::time::Duration;
+
+use std::os::unix::prelude::*;
+
+use libc::{c_int, c_void, size_t};
+
+// Name prefixes of device nodes created by serial port drivers.
+const SERIAL_PREFIXES: &[&str] = &[
+    "ttyS", "ttyUSB", "ttyACM", "ttyAMA", "ttyAML", "ttymxc", "ttyO", "ttySAC", "ttyTHS", "ttyHS",
+    "ttyMSM", "ttyGS", "ttyXRUSB", "rfcomm",
+];
+
+/// Information about a serial port device.
+///
+/// The symbolic links that udev creates in `/dev/serial/by-id` and `/dev/serial/by-path` identify
+/// which adapter a device node belongs to. udev usually creates them shortly after the device node
+/// appears, so they may be missing from the `Added` event and reported by a later `Changed` event.
+#[derive(Debug,Clone,PartialEq,Eq)]
+pub struct PortInfo {
+    /// The path of the device node, e.g., `/dev/ttyUSB0`.
+    pub path: PathBuf,
+
+    /// The symbolic link to the device in `/dev/serial/by-id`, which names the adapter by its USB
+    /// vendor, product and serial number, e.g.,
+    /// `/dev/serial/by-id/usb-FTDI_FT232R_A9M9DV3R-if00-port0`.
+    pub by_id: Option<PathBuf>,
+
+    /// The symbolic link to the device in `/dev/serial/by-path`, which names the port the adapter
+    /// is connected to, e.g., `/dev/serial/by-path/pci-0000:00:14.0-usb-0:2:1.0-port0`.
+    pub by_path: Option<PathBuf>,
+}
+
+/// An event reported by a [`DeviceMonitor`](struct.DeviceMonitor.html).
+#[derive(Debug,Clone,PartialEq,Eq)]
+pub enum DeviceEvent {
+    /// A serial port device was added.
+    Added(PortInfo),
+
+    /// A serial port device was removed.
+    ///
+    /// The symbolic links of the device are reported as they were last seen by the monitor.
+    Removed(PortInfo),
+
+    /// A symbolic link to a serial port device was added to `/dev/serial/by-id`.
+    Changed(PortInfo),
+
+    /// The kernel's event queue overflowed and events were lost.
+    ///
+    /// Devices may have been added or removed without a corresponding event. Callers that track
+    /// the set of present devices should rescan the monitored directory.
+    Overflow,
+}
+
+/// A monitor for serial port devices being added and removed.
+///
+/// `DeviceMonitor` watches a device directory with inotify and reports device nodes whose names
+/// match common serial port drivers, e.g., `ttyUSB0` or `ttyACM0`. The `serial/by-id`
+/// subdirectory is watched as well, once it exists, to report the symbolic links that identify each
+/// device. Other subdirectories are not watched.
+///
+/// Events can be received by iterating over the monitor, which blocks until the next event. For
+/// integration into an event loop, the monitor implements `AsRawFd`. The file descriptor becomes
+/// readable when events are pending, which can then be received with `next_event()`.
+///
+/// ```no_run
+/// for event in serial_unix::DeviceMonitor::new().unwrap() {
+///     println!("{:?}", event.unwrap());
+/// }
+/// ```
+pub struct DeviceMonitor {
+    fd: RawFd,
+    root: PathBuf,
+    canonical_root: PathBuf,
+    watches: HashMap<c_int, Watch>,
+    ports: HashMap<PathBuf, PortInfo>,
+    events: VecDeque<DeviceEvent>,
+}
+
+// The directories watched by a `DeviceMonitor`.
+#[derive(Debug,Copy,Clone,PartialEq,Eq)]
+enum Watch {
+    Root,
+    Serial,
+    ById,
+}
+
+impl DeviceMonitor {
+    /// Creates a monitor for serial port devices in `/dev`.
+    ///
+    /// ## Errors
+    ///
+    /// This function returns an error if the inotify watch could not be created:
+    ///
+    /// * `NoDevice` if `/dev` could not be watched.
+    /// * `Io` for any other error.
+    pub fn new() -> core::Result<Self> {
+        DeviceMonitor::with_root("/dev")
+    }
+
+    /// Creates a monitor for serial port devices in the directory `root`.
+    ///
+    /// ## Errors
+    ///
+    /// This function returns an error if the inotify watch could not be created:
+    ///
+    /// * `NoDevice` if `root` could not be watched.
+    /// * `InvalidInput` if `root` is not a valid path.
+    /// * `Io` for any other error.
+    pub fn with_root<P: AsRef<Path>>(root: P) -> core::Result<Self> {
+        use libc::{IN_CLOEXEC, IN_CREATE, IN_DELETE, IN_MOVED_FROM, IN_MOVED_TO, IN_ONLYDIR, EINVAL};
+
+        let root = root.as_ref().to_path_buf();
+
+        let cstr = match CString::new(root.as_os_str().as_bytes()) {
+            Ok(s) => s,
+            Err(_) => return Err(super::error::from_raw_os_error(EINVAL).with_path(&root)),
+        };
+
+        let fd = unsafe { libc::inotify_init1(IN_CLOEXEC) };
+        if fd < 0 {
+            return Err(super::error::last_os_error().with_operation("inotify_init1"));
+        }
+
+        let mut monitor = DeviceMonitor {
+            fd: fd,
+            canonical_root: fs::canonicalize(&root).unwrap_or_else(|_| root.clone()),
+            root: root,
+            watches: HashMap::new(),
+            ports: HashMap::new(),
+            events: VecDeque::new(),
+        };
+
+        let mask = IN_CREATE | IN_DELETE | IN_MOVED_FROM | IN_MOVED_TO | IN_ONLYDIR;
+
+        let wd = unsafe { libc::inotify_add_watch(monitor.fd, cstr.as_ptr(), mask) };
+        if wd < 0 {
+            return Err(super::error::last_os_error().with_operation("inotify_add_watch").with_path(&monitor.root));
+        }
+
+        monitor.watches.insert(wd, Watch::Root);
+        monitor.watch_links();
+
+        Ok(monitor)
+    }
+
+    /// Returns the directory being monitored.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Waits for the next device event.
+    ///
+    /// ## Errors
+    ///
+    /// This function returns an error if events could not be read from inotify.
+    pub fn next_event(&mut self) -> core::Result<DeviceEvent> {
+        loop {
+            if let Some(event) = self.events.pop_front() {
+                return Ok(event);
+            }
+
+            try!(self.read_events());
+        }
+    }
+
+    /// Waits up to `timeout` for the next device event.
+    ///
+    /// Returns `Ok(None)` if no event occurred before the timeout expired.
+    ///
+    /// ## Errors
+    ///
+    /// This function returns an error if events could not be read from inotify.
+    pub fn next_event_timeout(&mut self, timeout: Duration) -> core::Result<Option<DeviceEvent>> {
+        use std::io;
+        use std::time::Instant;
+
+        let deadline = match core::Timeout::Duration(timeout).deadline() {
+            Some(deadline) => deadline,
+            None => return self.next_event().map(Some),
+        };
+
+        loop {
+            if let Some(event) = self.events.pop_front() {
+                return Ok(Some(event));
+            }
+
+            let now = Instant::now();
+            if now >= deadline {
+                return Ok(None);
+            }
+
+            match super::poll::wait_read_fd(self.fd, Some(deadline - now)) {
+                Ok(()) => try!(self.read_events()),
+                Err(ref e) if e.kind() == io::ErrorKind::TimedOut => return Ok(None),
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => (),
+                Err(e) => return Err(core::Error::from(e)),
+            }
+        }
+    }
+
+    fn read_events(&mut self) -> core::Result<()> {
+        use libc::EINTR;
+
+        let mut buffer = [0u8; 4096];
+
+        let len = unsafe {
+            libc::read(self.fd, buffer.as_mut_ptr() as *mut c_void, buffer.len() as size_t)
+        };
+
+        if len < 0 {
+            if super::error::errno() == EINTR {
+                return Ok(());
+            }
+
+            return Err(super::error::last_os_error().with_operation("read").with_path(&self.root));
+        }
+
+        for (wd, mask, name) in parse_events(&buffer[..len as usize]) {
+            self.event(wd, mask, name);
+        }
+
+        Ok(())
+    }
+
+    fn event(&mut self, wd: c_int, mask: u32, name: &OsStr) {
+        use libc::{IN_CREATE, IN_DELETE, IN_ISDIR, IN_MOVED_FROM, IN_MOVED_TO, IN_Q_OVERFLOW};
+
+        // an overflow event is not associated with any watch
+        if mask & IN_Q_OVERFLOW != 0 {
+            self.events.push_back(DeviceEvent::Overflow);
+            return;
+        }
+
+        let watch = match self.watches.get(&wd) {
+            Some(&watch) => watch,
+            None => return,
+        };
+
+        match watch {
+            Watch::Root if mask & IN_ISDIR != 0 && name == "serial" => self.watch_links(),
+            Watch::Root if is_serial_device(name) => {
+                if mask & (IN_CREATE | IN_MOVED_TO) != 0 {
+                    let info = self.port_info(name);
+                    self.ports.insert(info.path.clone(), info.clone());
+                    self.events.push_back(DeviceEvent::Added(info));
+                }
+                else if mask & (IN_DELETE | IN_MOVED_FROM) != 0 {
+                    let path = self.root.join(name);
+                    let info = self.ports.remove(&path).unwrap_or_else(|| PortInfo::new(path));
+                    self.events.push_back(DeviceEvent::Removed(info));
+                }
+            }
+            Watch::Root => (),
+            Watch::Serial if name == "by-id" => self.watch_links(),
+            Watch::Serial => (),
+            Watch::ById => self.link_added(&self.root.join("serial/by-id").join(name)),
+        }
+    }
+
+    // Watches `serial` and `serial/by-id` in the root directory, if they exist, and reports the
+    // links that were created before `serial/by-id` was watched.
+    fn watch_links(&mut self) {
+        use libc::{IN_CREATE, IN_MOVED_TO, IN_ONLYDIR};
+
+        let mask = IN_CREATE | IN_MOVED_TO | IN_ONLYDIR;
+
+        let serial = self.root.join("serial");
+        let by_id = serial.join("by-id");
+
+        for &(ref dir, watch) in &[(serial.clone(), Watch::Serial), (by_id.clone(), Watch::ById)] {
+            let cstr = match CString::new(dir.as_os_str().as_bytes()) {
+                Ok(s) => s,
+                Err(_) => return,
+            };
+
+            let wd = unsafe { libc::inotify_add_watch(self.fd, cstr.as_ptr(), mask) };
+            if wd < 0 {
+                return;
+            }
+
+            self.watches.insert(wd, watch);
+        }
+
+        if let Ok(entries) = fs::read_dir(&by_id) {
+            for entry in entries.filter_map(|entry| entry.ok()) {
+                self.link_added(&entry.path());
+            }
+        }
+    }
+
+    // Reports a `Changed` event if `link` points to a serial port device in the root directory and
+    // the device's links have changed.
+    fn link_added(&mut self, link: &Path) {
+        let target = match fs::canonicalize(link) {
+            Ok(target) => target,
+            Err(_) => return,
+        };
+
+        if target.parent() != Some(self.canonical_root.as_path()) {
+            return;
+        }
+
+        let name = match target.file_name() {
+            Some(name) if is_serial_device(name) => name,
+            _ => return,
+        };
+
+        let info = self.port_info(name);
+
+        if self.ports.get(&info.path) != Some(&info) {
+            self.ports.insert(info.path.clone(), info.clone());
+            self.events.push_back(DeviceEvent::Changed(info));
+        }
+    }
+
+    // Returns information about the device `name` in the root directory.
+    fn port_info(&self, name: &OsStr) -> PortInfo {
+        let path = self.root.join(name);
+
+        let target = match fs::canonicalize(&path) {
+            Ok(target) => target,
+            Err(_) => return PortInfo::new(path),
+        };
+
+        PortInfo {
+            path: path,
+            by_id: find_link(&self.root.join("serial/by-id"), &target),
+            by_path: find_link(&self.root.join("serial/by-path"), &target),
+        }
+    }
+}
+
+impl PortInfo {
+    fn new(path: PathBuf) -> Self {
+        PortInfo {
+            path: path,
+            by_id: None,
+            by_path: None,
+        }
+    }
+}
+
+impl Drop for DeviceMonitor {
+    fn drop(&mut self) {
+        unsafe {
+            libc::close(self.fd);
+        }
+    }
+}
+
+impl AsRawFd for DeviceMonitor {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd
+    }
+}
+
+impl Iterator for DeviceMonitor {
+    type Item = core::Result<DeviceEvent>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.next_event())
+    }
+}
+
+// Splits a buffer read from an inotify file descriptor into each event's watch descriptor, mask and
+// file name.
+fn parse_events(buffer: &[u8]) -> Vec<(c_int, u32, &OsStr)> {
+    let header_len = mem::size_of::<libc::inotify_event>();
+
+    let mut events = Vec::new();
+    let mut offset = 0;
+
+    while offset + header_len <= buffer.len() {
+        let header: libc::inotify_event = unsafe {
+            ptr::read_unaligned(buffer[offset..].as_ptr() as *const libc::inotify_event)
+        };
+
+        let name_start = offset + header_len;
+        let name_end = name_start + header.len as usize;
+
+        if name_end > buffer.len() {
+            break;
+        }
+
+        // the name is padded with NUL bytes
+        let name = &buffer[name_start..name_end];
+        let name = &name[..name.iter().position(|&b| b == 0).unwrap_or(name.len())];
+
+        events.push((header.wd, header.mask, OsStr::from_bytes(name)));
+        offset = name_end;
+    }
+
+    events
+}
+
+// Finds the symbolic link in `dir` that points to `target`.
+fn find_link(dir: &Path, target: &Path) -> Option<PathBuf> {
+    let entries = fs::read_dir(dir).ok()?;
+
+    entries.filter_map(|entry| entry.ok())
+           .map(|entry| entry.path())
+           .find(|link| match fs::canonicalize(link) {
+               Ok(path) => path == target,
+               Err(_) => false,
+           })
+}
+
+fn is_serial_device(name: &OsStr) -> bool {
+    let name = match name.to_str() {
+        Some(name) => name,
+        None => return false,
+    };
+
+    SERIAL_PREFIXES.iter().any(|prefix| {
+        name.starts_with(prefix) && name[prefix.len()..].starts_with(|c: char| c.is_ascii_digit())
+    })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::OsStr;
+    use std::fs;
+    use std::time::Duration;
+
+    use std::os::unix;
+
+    use super::{DeviceEvent, DeviceMonitor, PortInfo};
+    use testing::temp_dir;
+
+    #[test]
+    fn is_serial_device_matches_serial_drivers() {
+        assert!(super::is_serial_device(OsStr::new("ttyUSB0")));
+        assert!(super::is_serial_device(OsStr::new("ttyACM12")));
+        assert!(super::is_serial_device(OsStr::new("ttyS3")));
+    }
+
+    #[test]
+    fn is_serial_device_rejects_other_devices() {
+        assert!(!super::is_serial_device(OsStr::new("tty0")));
+        assert!(!super::is_serial_device(OsStr::new("ttyUSB")));
+        assert!(!super::is_serial_device(OsStr::new("sda1")));
+    }
+
+    #[test]
+    fn device_monitor_reports_added_and_removed_devices() {
+        let dir = temp_dir("monitor");
+        let mut monitor = DeviceMonitor::with_root(&dir).unwrap();
+
+        fs::File::create(dir.join("sda1")).unwrap();
+        fs::File::create(dir.join("ttyUSB0")).unwrap();
+        fs::remove_file(dir.join("ttyUSB0")).unwrap();
+
+        let info = PortInfo::new(dir.join("ttyUSB0"));
+        let timeout = Duration::from_secs(1);
+
+        assert_eq!(monitor.next_event_timeout(timeout).unwrap(), Some(DeviceEvent::Added(info.clone())));
+        assert_eq!(monitor.next_event_timeout(timeout).unwrap(), Some(DeviceEvent::Removed(info)));
+        assert_eq!(monitor.next_event_timeout(Duration::from_millis(10)).unwrap(), None);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn device_monitor_reports_queue_overflow() {
+        let dir = temp_dir("monitor-overflow");
+        let mut monitor = DeviceMonitor::with_root(&dir).unwrap();
+
+        monitor.event(-1, ::libc::IN_Q_OVERFLOW, OsStr::new(""));
+        assert_eq!(monitor.next_event().unwrap(), DeviceEvent::Overflow);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn device_monitor_resolves_links() {
+        let dir = temp_dir("monitor-links");
+        fs::create_dir_all(dir.join("serial/by-id")).unwrap();
+        fs::create_dir_all(dir.join("serial/by-path")).unwrap();
+
+        let mut monitor = DeviceMonitor::with_root(&dir).unwrap();
+
+        let by_id = dir.join("serial/by-id/usb-FTDI_FT232R_A9M9DV3R-if00-port0");
+        let by_path = dir.join("serial/by-path/pci-0000:00:14.0-usb-0:2:1.0-port0");
+
+        fs::File::create(dir.join("ttyUSB0")).unwrap();
+        unix::fs::symlink("../../ttyUSB0", &by_id).unwrap();
+        unix::fs::symlink("../../ttyUSB0", &by_path).unwrap();
+
+        let info = PortInfo {
+            path: dir.join("ttyUSB0"),
+            by_id: Some(by_id),
+            by_path: Some(by_path),
+        };
+
+        let timeout = Duration::from_secs(1);
+        assert_eq!(monitor.next_event_timeout(timeout).unwrap(), Some(DeviceEvent::Added(info.clone())));
+
+        // the links are gone by the time the device node is removed
+        fs::remove_dir_all(dir.join("serial")).unwrap();
+        fs::remove_file(dir.join("ttyUSB0")).unwrap();
+
+        assert_eq!(monitor.next_event_timeout(timeout).unwrap(), Some(DeviceEvent::Removed(info)));
+        assert_eq!(monitor.next_event_timeout(Duration::from_millis(10)).unwrap(), None);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn device_monitor_reports_links_created_after_device() {
+        let dir = temp_dir("monitor-late-links");
+        let mut monitor = DeviceMonitor::with_root(&dir).unwrap();
+        let timeout = Duration::from_secs(1);
+
+        fs::File::create(dir.join("ttyUSB0")).unwrap();
+        let info = PortInfo::new(dir.join("ttyUSB0"));
+        assert_eq!(monitor.next_event_timeout(timeout).unwrap(), Some(DeviceEvent::Added(info)));
+
+        // /dev/serial/by-id is created along with the first link
+        let by_id = dir.join("serial/by-id/usb-FTDI_FT232R_A9M9DV3R-if00-port0");
+        fs::create_dir_all(dir.join("serial/by-id")).unwrap();
+        unix::fs::symlink("../../ttyUSB0", &by_id).unwrap();
+
+        let info = PortInfo {
+            path: dir.join("ttyUSB0"),
+            by_id: Some(by_id),
+            by_path: None,
+        };
+
+        assert_eq!(monitor.next_event_timeout(timeout).unwrap(), Some(DeviceEvent::Changed(info)));
+        assert_eq!(monitor.next_event_timeout(Duration::from_millis(10)).unwrap(), None);
+
+        // a link created once /dev/serial/by-id is watched
+        let by_id = dir.join("serial/by-id/usb-FTDI_FT232R_A50285BI-if00-port0");
+        fs::File::create(dir.join("ttyUSB1")).unwrap();
+        assert!(monitor.next_event_timeout(timeout).unwrap().is_some());
+        unix::fs::symlink("../../ttyUSB1", &by_id).unwrap();
+
+        let info = PortInfo {
+            path: dir.join("ttyUSB1"),
+            by_id: Some(by_id),
+            by_path: None,
+        };
+
+        assert_eq!(monitor.next_event_timeout(timeout).unwrap(), Some(DeviceEvent::Changed(info)));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn device_monitor_fails_for_missing_root() {
+        let dir = temp_dir("monitor-missing");
+        assert!(DeviceMonitor::with_root(dir.join("missing")).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -50,15 +50,16 @@ const POLLERR:  c_short = 0x0008;
 const POLLHUP:  c_short = 0x0010;
 const POLLNVAL: c_short = 0x0020;
 
-pub fn wait_read_fd(fd: c_int, timeout: Duration) -> io::Result<()> {
+// A timeout of `None` waits indefinitely.
+pub fn wait_read_fd(fd: c_int, timeout: Option<Duration>) -> io::Result<()> {
     wait_fd(fd, POLLIN, timeout)
 }
 
-pub fn wait_write_fd(fd: c_int, timeout: Duration) -> io::Result<()> {
+pub fn wait_write_fd(fd: c_int, timeout: Option<Duration>) -> io::Result<()> {
     wait_fd(fd, POLLOUT, timeout)
 }
 
-fn wait_fd(fd: c_int, events: c_short, timeout: Duration) -> io::Result<()> {
+fn wait_fd(fd: c_int, events: c_short, timeout: Option<Duration>) -> io::Result<()> {
     use libc::{EINTR, EPIPE, EIO};
 
     let mut pollfd = pollfd {
@@ -97,7 +98,7 @@ fn wait_fd(fd: c_int, events: c_short, timeout: Duration) -> io::Result<()> {
 
 #[cfg(target_os = "linux")]
 #[inline]
-fn do_poll(pollfd: &mut pollfd, timeout: Duration) -> c_int {
+fn do_poll(pollfd: &mut pollfd, timeout: Option<Duration>) -> c_int {
     use std::ptr;
 
     use libc::c_void;
@@ -111,24 +112,32 @@ fn do_poll(pollfd: &mut pollfd, timeout: Duration) -> c_int {
         fn ppoll(fds: *mut pollfd, nfds: nfds_t, timeout_ts: *mut libc::timespec, sigmask: *const sigset_t) -> c_int;
     }
 
-    let mut timeout_ts = libc::timespec {
+    let mut timeout_ts = timeout.map(|timeout| libc::timespec {
         tv_sec: timeout.as_secs() as libc::time_t,
         tv_nsec: timeout.subsec_nanos() as libc::c_long,
+    });
+
+    let timeout_ptr = match timeout_ts {
+        Some(ref mut timeout_ts) => timeout_ts as *mut libc::timespec,
+        None => ptr::null_mut(),
     };
 
     unsafe {
-        ppoll(pollfd, 1, &mut timeout_ts, ptr::null())
+        ppoll(pollfd, 1, timeout_ptr, ptr::null())
     }
 }
 
 #[cfg(not(target_os = "linux"))]
 #[inline]
-fn do_poll(pollfd: &mut pollfd, timeout: Duration) -> c_int {
+fn do_poll(pollfd: &mut pollfd, timeout: Option<Duration>) -> c_int {
     extern "C" {
         fn poll(fds: *mut pollfd, nfds: nfds_t, timeout: c_int) -> c_int;
     }
 
-    let milliseconds = timeout.as_secs() * 1000 + timeout.subsec_nanos() as u64 / 1_000_000;
+    let milliseconds = match timeout {
+        Some(timeout) => timeout.as_secs() * 1000 + timeout.subsec_nanos() as u64 / 1_000_000,
+        None => return unsafe { poll(pollfd, 1, -1) },
+    };
 
     unsafe {
         poll(pollfd, 1, milliseconds as c_int)
@@ -0,0 +1,210 @@
+// Copyright (c) 2015 David Cuddeback
+//
+// Permission is hereby granted, free of charge, to any person obtaining
+// a copy of this software and associated documentation files (the
+// "Software"), to deal in the Software without restriction, including
+// without limitation the rights to use, copy, modify, merge, publish,
+// distribute, sublicense, and/or sell copies of the Software, and to
+// permit persons to whom the Software is furnished to do so, subject to
+// the following conditions:
+//
+// The above copyright notice and this permission notice shall be
+// included in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND,
+// EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF
+// MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE
+// LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION
+// WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+
+use std::cmp;
+use std::time::Duration;
+
+#[cfg(any(target_arch = "mips",
+          target_arch = "mips64"))]
+pub const TIOCGRS485: u32 = 0x4020542e;
+
+#[cfg(any(target_arch = "mips",
+          target_arch = "mips64"))]
+pub const TIOCSRS485: u32 = 0xc020542f;
+
+#[cfg(target_arch = "sparc64")]
+pub const TIOCGRS485: u32 = 0x40205441;
+
+#[cfg(target_arch = "sparc64")]
+pub const TIOCSRS485: u32 = 0xc0205442;
+
+#[cfg(not(any(target_arch = "mips",
+              target_arch = "mips64",
+              target_arch = "sparc64")))]
+pub const TIOCGRS485: u32 = 0x542e;
+
+#[cfg(not(any(target_arch = "mips",
+              target_arch = "mips64",
+              target_arch = "sparc64")))]
+pub const TIOCSRS485: u32 = 0x542f;
+
+const SER_RS485_ENABLED: u32 = 1 << 0;
+const SER_RS485_RTS_ON_SEND: u32 = 1 << 1;
+const SER_RS485_RTS_AFTER_SEND: u32 = 1 << 2;
+const SER_RS485_RX_DURING_TX: u32 = 1 << 4;
+
+/// RS-485 settings of a UART.
+///
+/// When RS-485 mode is enabled, the driver asserts RTS to enable the line driver while it transmits,
+/// optionally waiting before and after transmitting to give the transceiver time to switch
+/// direction. The RTS levels are the logic levels of the signal, which most UARTs invert on the
+/// pin.
+///
+/// The settings are applied with [`TTYPort::set_rs485()`](struct.TTYPort.html#method.set_rs485).
+/// Drivers may round the delays or ignore settings they don't support, so
+/// [`TTYPort::get_rs485()`](struct.TTYPort.html#method.get_rs485) can be used to read back what was
+/// applied.
+#[derive(Debug,Copy,Clone,PartialEq,Eq,Default)]
+pub struct Rs485Config {
+    /// Whether RS-485 mode is enabled.
+    pub enabled: bool,
+
+    /// The level of RTS while transmitting.
+    pub rts_on_send: bool,
+
+    /// The level of RTS after transmitting.
+    pub rts_after_send: bool,
+
+    /// The delay between asserting RTS and transmitting. It's set in milliseconds, rounded up.
+    pub delay_before_send: Duration,
+
+    /// The delay between transmitting and releasing RTS. It's set in milliseconds, rounded up.
+    pub delay_after_send: Duration,
+
+    /// Whether data is received while transmitting, e.g., to read back what was sent.
+    pub rx_during_tx: bool,
+}
+
+// The kernel's `struct serial_rs485`. Fields after the delays are padding or only used for
+// addressing, which isn't supported.
+#[repr(C)]
+#[derive(Debug,Copy,Clone,PartialEq,Eq,Default)]
+#[allow(non_camel_case_types)]
+pub struct serial_rs485 {
+    pub flags: u32,
+    pub delay_rts_before_send: u32,
+    pub delay_rts_after_send: u32,
+    pub padding: [u32; 5],
+}
+
+// Encodes `config` as the kernel's `struct serial_rs485`.
+pub fn encode(config: &Rs485Config) -> serial_rs485 {
+    let mut flags = 0;
+
+    set_flag(&mut flags, SER_RS485_ENABLED, config.enabled);
+    set_flag(&mut flags, SER_RS485_RTS_ON_SEND, config.rts_on_send);
+    set_flag(&mut flags, SER_RS485_RTS_AFTER_SEND, config.rts_after_send);
+    set_flag(&mut flags, SER_RS485_RX_DURING_TX, config.rx_during_tx);
+
+    serial_rs485 {
+        flags: flags,
+        delay_rts_before_send: millis(config.delay_before_send),
+        delay_rts_after_send: millis(config.delay_after_send),
+        padding: [0; 5],
+    }
+}
+
+// Decodes the kernel's `struct serial_rs485`, ignoring flags that aren't supported.
+pub fn decode(raw: &serial_rs485) -> Rs485Config {
+    Rs485Config {
+        enabled: raw.flags & SER_RS485_ENABLED != 0,
+        rts_on_send: raw.flags & SER_RS485_RTS_ON_SEND != 0,
+        rts_after_send: raw.flags & SER_RS485_RTS_AFTER_SEND != 0,
+        delay_before_send: Duration::from_millis(raw.delay_rts_before_send as u64),
+        delay_after_send: Duration::from_millis(raw.delay_rts_after_send as u64),
+        rx_during_tx: raw.flags & SER_RS485_RX_DURING_TX != 0,
+    }
+}
+
+fn set_flag(flags: &mut u32, flag: u32, enabled: bool) {
+    if enabled {
+        *flags |= flag;
+    }
+}
+
+// Converts `duration` to whole milliseconds, rounding up.
+fn millis(duration: Duration) -> u32 {
+    let nanos = duration.subsec_nanos() as u64;
+    let millis = duration.as_secs().saturating_mul(1000).saturating_add((nanos + 999_999) / 1_000_000);
+
+    cmp::min(millis, u32::max_value() as u64) as u32
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::mem;
+    use std::time::Duration;
+
+    use super::{decode, encode, serial_rs485, Rs485Config};
+
+    #[test]
+    fn serial_rs485_matches_kernel_size() {
+        assert_eq!(mem::size_of::<serial_rs485>(), 32);
+    }
+
+    #[test]
+    fn encode_sets_flags() {
+        let config = Rs485Config {
+            enabled: true,
+            rts_on_send: true,
+            rx_during_tx: true,
+            ..Rs485Config::default()
+        };
+
+        assert_eq!(encode(&config).flags, 0x13);
+        assert_eq!(encode(&Rs485Config::default()), serial_rs485::default());
+    }
+
+    #[test]
+    fn encode_rounds_delays_up_to_millis() {
+        let config = Rs485Config {
+            delay_before_send: Duration::from_micros(1500),
+            delay_after_send: Duration::from_millis(2),
+            ..Rs485Config::default()
+        };
+
+        let raw = encode(&config);
+        assert_eq!(raw.delay_rts_before_send, 2);
+        assert_eq!(raw.delay_rts_after_send, 2);
+    }
+
+    #[test]
+    fn encode_saturates_long_delays() {
+        let config = Rs485Config {
+            delay_before_send: Duration::from_secs(u64::max_value()),
+            ..Rs485Config::default()
+        };
+
+        assert_eq!(encode(&config).delay_rts_before_send, u32::max_value());
+    }
+
+    #[test]
+    fn decode_reverses_encode() {
+        let config = Rs485Config {
+            enabled: true,
+            rts_on_send: false,
+            rts_after_send: true,
+            delay_before_send: Duration::from_millis(10),
+            delay_after_send: Duration::from_millis(3),
+            rx_during_tx: false,
+        };
+
+        assert_eq!(decode(&encode(&config)), config);
+    }
+
+    #[test]
+    fn decode_ignores_unsupported_flags() {
+        let raw = serial_rs485 { flags: 0x1 | 0x20, ..serial_rs485::default() };
+        assert_eq!(decode(&raw), Rs485Config { enabled: true, ..Rs485Config::default() });
+    }
+}
@@ -0,0 +1,251 @@
+// Copyright (c) 2015 David Cuddeback
+//
+// Permission is hereby granted, free of charge, to any person obtaining
+// a copy of this software and associated documentation files (the
+// "Software"), to deal in the Software without restriction, including
+// without limitation the rights to use, copy, modify, merge, publish,
+// distribute, sublicense, and/or sell copies of the Software, and to
+// permit persons to whom the Software is furnished to do so, subject to
+// the following conditions:
+//
+// The above copyright notice and this permission notice shall be
+// included in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND,
+// EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF
+// MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE
+// LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION
+// WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+
+use core;
+
+use libc::{c_char, c_int, c_uint, c_ulong, c_ushort};
+
+#[cfg(any(target_arch = "mips",
+          target_arch = "mips64"))]
+pub const TIOCGSERIAL: u32 = 0x5484;
+
+#[cfg(any(target_arch = "mips",
+          target_arch = "mips64"))]
+pub const TIOCSSERIAL: u32 = 0x5485;
+
+#[cfg(not(any(target_arch = "mips",
+              target_arch = "mips64")))]
+pub const TIOCGSERIAL: u32 = 0x541e;
+
+#[cfg(not(any(target_arch = "mips",
+              target_arch = "mips64")))]
+pub const TIOCSSERIAL: u32 = 0x541f;
+
+const ASYNC_LOW_LATENCY: u32 = 1 << 13;
+
+// The flags that select how 38,400 baud is interpreted, and the value that selects
+// `custom_divisor`.
+const ASYNC_SPD_MASK: u32 = 0x1030;
+const ASYNC_SPD_CUST: u32 = 0x0030;
+
+// The baud rate that's replaced by the custom divisor with `ASYNC_SPD_CUST`.
+const SPD_CUST_SPEED: usize = 38400;
+
+/// Information about a serial port reported by its driver.
+///
+/// This is the driver's `struct serial_struct`, read with
+/// [`TTYPort::serial_info()`](struct.TTYPort.html#method.serial_info). Most fields are only
+/// meaningful for UARTs on the system bus. USB serial drivers typically report a port type of zero
+/// and only some of the fields.
+#[derive(Debug,Copy,Clone,PartialEq,Eq)]
+pub struct SerialInfo {
+    /// The type of UART, e.g., `4` for a 16550A (`PORT_16550A`).
+    pub port_type: i32,
+
+    /// The line number of the port.
+    pub line: i32,
+
+    /// The interrupt used by the port.
+    pub irq: i32,
+
+    /// The `ASYNC_*` flags of the port.
+    pub flags: u32,
+
+    /// The size of the UART's transmit FIFO in bytes.
+    pub xmit_fifo_size: u32,
+
+    /// The divisor of `baud_base` used with `ASYNC_SPD_CUST`.
+    pub custom_divisor: u32,
+
+    /// The maximum baud rate of the port, which is the UART's clock divided by 16 on most UARTs.
+    pub baud_base: u32,
+
+    /// The time to wait for output to drain when the port is closed, in hundredths of a second.
+    /// `0` waits indefinitely and `65535` doesn't wait.
+    pub closing_wait: u16,
+}
+
+impl SerialInfo {
+    /// Returns `true` if the driver is in low latency mode (`ASYNC_LOW_LATENCY`).
+    pub fn is_low_latency(&self) -> bool {
+        self.flags & ASYNC_LOW_LATENCY != 0
+    }
+}
+
+// The kernel's `struct serial_struct`.
+#[repr(C)]
+#[derive(Copy,Clone)]
+#[allow(non_camel_case_types)]
+pub struct serial_struct {
+    pub type_: c_int,
+    pub line: c_int,
+    pub port: c_uint,
+    pub irq: c_int,
+    pub flags: c_int,
+    pub xmit_fifo_size: c_int,
+    pub custom_divisor: c_int,
+    pub baud_base: c_int,
+    pub close_delay: c_ushort,
+    pub io_type: c_char,
+    pub reserved_char: [c_char; 1],
+    pub hub6: c_int,
+    pub closing_wait: c_ushort,
+    pub closing_wait2: c_ushort,
+    pub iomem_base: *mut u8,
+    pub iomem_reg_shift: c_ushort,
+    pub port_high: c_uint,
+    pub iomap_base: c_ulong,
+}
+
+// Decodes the fields of the kernel's `struct serial_struct` that are exposed by `SerialInfo`.
+pub fn decode(raw: &serial_struct) -> SerialInfo {
+    SerialInfo {
+        port_type: raw.type_,
+        line: raw.line,
+        irq: raw.irq,
+        flags: raw.flags as u32,
+        xmit_fifo_size: raw.xmit_fifo_size as u32,
+        custom_divisor: raw.custom_divisor as u32,
+        baud_base: raw.baud_base as u32,
+        closing_wait: raw.closing_wait,
+    }
+}
+
+// Returns the baud rate that a UART produces for `speed` by dividing its base rate, or `None` if the
+// driver doesn't report a known type of UART. With `ASYNC_SPD_CUST`, the driver divides the base
+// rate by `custom_divisor` instead of producing 38,400 baud.
+pub fn divided_baud_rate(info: &SerialInfo, speed: usize) -> Option<usize> {
+    // drivers that aren't for a known type of UART, such as USB adapters, may report a base rate
+    // that isn't divided by an integer divisor
+    if info.port_type == 0 || info.baud_base == 0 {
+        return None;
+    }
+
+    let custom = info.flags & ASYNC_SPD_MASK == ASYNC_SPD_CUST && info.custom_divisor > 0;
+
+    if speed == SPD_CUST_SPEED && custom {
+        let (base, divisor) = (info.baud_base as u64, info.custom_divisor as u64);
+        return Some(((base + divisor / 2) / divisor) as usize);
+    }
+
+    let clock = core::BaudClock::new(info.baud_base);
+    clock.nearest(core::BaudRate::from_speed(speed)).map(|rate| rate.actual)
+}
+
+// Sets or clears `ASYNC_LOW_LATENCY`, leaving the other fields as they are.
+pub fn set_low_latency(raw: &mut serial_struct, enabled: bool) {
+    if enabled {
+        raw.flags |= ASYNC_LOW_LATENCY as c_int;
+    }
+    else {
+        raw.flags &= !(ASYNC_LOW_LATENCY as c_int);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::mem;
+
+    use super::{decode, divided_baud_rate, serial_struct, set_low_latency, SerialInfo};
+
+    fn raw_serial_struct() -> serial_struct {
+        let mut raw: serial_struct = unsafe { mem::zeroed() };
+
+        raw.type_ = 4;
+        raw.line = 1;
+        raw.irq = 3;
+        raw.flags = 0x10000040;
+        raw.xmit_fifo_size = 16;
+        raw.custom_divisor = 0;
+        raw.baud_base = 115200;
+        raw.closing_wait = 3000;
+        raw
+    }
+
+    #[cfg(target_pointer_width = "64")]
+    #[test]
+    fn serial_struct_matches_kernel_size() {
+        assert_eq!(mem::size_of::<serial_struct>(), 72);
+    }
+
+    #[cfg(target_pointer_width = "32")]
+    #[test]
+    fn serial_struct_matches_kernel_size() {
+        assert_eq!(mem::size_of::<serial_struct>(), 60);
+    }
+
+    #[test]
+    fn decode_copies_fields() {
+        assert_eq!(decode(&raw_serial_struct()), SerialInfo {
+            port_type: 4,
+            line: 1,
+            irq: 3,
+            flags: 0x10000040,
+            xmit_fifo_size: 16,
+            custom_divisor: 0,
+            baud_base: 115200,
+            closing_wait: 3000,
+        });
+    }
+
+    #[test]
+    fn divided_baud_rate_divides_base_rate() {
+        let info = decode(&raw_serial_struct());
+
+        assert_eq!(divided_baud_rate(&info, 115200), Some(115200));
+        assert_eq!(divided_baud_rate(&info, 50000), Some(57600));
+    }
+
+    #[test]
+    fn divided_baud_rate_uses_custom_divisor() {
+        let mut raw = raw_serial_struct();
+        raw.flags |= 0x0030;
+        raw.custom_divisor = 7;
+
+        let info = decode(&raw);
+        assert_eq!(divided_baud_rate(&info, 38400), Some(16457));
+        assert_eq!(divided_baud_rate(&info, 9600), Some(9600));
+    }
+
+    #[test]
+    fn divided_baud_rate_is_unknown_for_unknown_uart() {
+        let mut raw = raw_serial_struct();
+        raw.type_ = 0;
+        raw.baud_base = 24000000;
+
+        assert_eq!(divided_baud_rate(&decode(&raw), 250000), None);
+    }
+
+    #[test]
+    fn set_low_latency_changes_only_its_flag() {
+        let mut raw = raw_serial_struct();
+
+        set_low_latency(&mut raw, true);
+        assert_eq!(raw.flags, 0x10002040);
+        assert!(decode(&raw).is_low_latency());
+
+        set_low_latency(&mut raw, false);
+        assert_eq!(raw.flags, 0x10000040);
+        assert!(!decode(&raw).is_low_latency());
+    }
+}
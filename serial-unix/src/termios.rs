@@ -40,7 +40,7 @@ pub fn read(fd: RawFd) -> core::Result<termios> {
 
     unsafe {
         if libc::tcgetattr(fd, &mut termios) < 0 {
-            return Err(super::error::last_os_error());
+            return Err(super::error::last_os_error().with_operation("tcgetattr"));
         }
     }
 
@@ -52,7 +52,7 @@ pub fn write(fd: RawFd, termios: &termios) -> core::Result<()> {
 
     unsafe {
         if libc::tcsetattr(fd, TCSANOW, termios) < 0 {
-            return Err(super::error::last_os_error());
+            return Err(super::error::last_os_error().with_operation("tcsetattr"));
         }
     }
 
@@ -64,7 +64,7 @@ pub fn flush(fd: RawFd) -> core::Result<()> {
 
     unsafe {
         if libc::tcflush(fd, TCIOFLUSH) < 0 {
-            return Err(super::error::last_os_error());
+            return Err(super::error::last_os_error().with_operation("tcflush"));
         }
     }
 
@@ -86,13 +86,13 @@ pub fn set_speed(termios: &mut termios, speed: Speed) -> core::Result<()> {
     match speed {
         Speed::Standard(baud) => unsafe {
             if libc::cfsetspeed(termios, baud) < 0 {
-                return Err(super::error::last_os_error());
+                return Err(super::error::last_os_error().with_operation("cfsetspeed"));
             }
         },
         Speed::Custom(s) => {
             unsafe {
                 if libc::cfsetspeed(termios, s as _) < 0 {
-                    return Err(super::error::last_os_error());
+                    return Err(super::error::last_os_error().with_operation("cfsetspeed"));
                 }
             }
         }
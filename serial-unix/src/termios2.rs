@@ -87,7 +87,7 @@ pub fn read(fd: RawFd) -> core::Result<termios> {
 
     unsafe {
         if libc::ioctl(fd, TCGETS2, &mut termios) < 0 {
-            return Err(super::error::last_os_error());
+            return Err(super::error::last_os_error().with_operation("TCGETS2"));
         }
     }
 
@@ -97,7 +97,7 @@ pub fn read(fd: RawFd) -> core::Result<termios> {
 pub fn write(fd: RawFd, termios: &termios) -> core::Result<()> {
     unsafe {
         if libc::ioctl(fd, TCSETS2, termios) < 0 {
-            return Err(super::error::last_os_error());
+            return Err(super::error::last_os_error().with_operation("TCSETS2"));
         }
     }
 
@@ -109,7 +109,7 @@ pub fn flush(fd: RawFd) -> core::Result<()> {
 
     unsafe {
         if libc::tcflush(fd, TCIOFLUSH) < 0 {
-            return Err(super::error::last_os_error());
+            return Err(super::error::last_os_error().with_operation("tcflush"));
         }
     }
 
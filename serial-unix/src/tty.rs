@@ -22,18 +22,20 @@
 use core;
 use libc;
 
+use std::cmp;
 use std::ffi::CString;
 use std::fmt;
 use std::io;
 use std::mem;
-use std::path::Path;
-use std::time::Duration;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
 
 use std::os::unix::prelude::*;
 
 use libc::{c_int, c_void, size_t};
 
-use core::{SerialDevice, SerialPortSettings};
+use core::{SerialDevice, SerialPortSettings, Timeout};
 
 #[cfg(not(target_os = "linux"))]
 use termios;
@@ -41,68 +43,475 @@ use termios;
 #[cfg(target_os = "linux")]
 use termios2 as termios;
 
+use lock::LockGuard;
+
+#[cfg(target_os = "linux")]
+use rs485;
+
+#[cfg(target_os = "linux")]
+use rs485::Rs485Config;
+
+#[cfg(target_os = "linux")]
+use serial_info;
+
+#[cfg(target_os = "linux")]
+use serial_info::SerialInfo;
+
+#[cfg(all(target_os = "linux",
+          any(target_arch = "mips",
+              target_arch = "mips64",
+              target_arch = "powerpc",
+              target_arch = "powerpc64",
+              target_arch = "sparc64")))]
+const TIOCGEXCL: u32 = 0x40045440;
+
+#[cfg(all(target_os = "linux",
+          not(any(target_arch = "mips",
+                  target_arch = "mips64",
+                  target_arch = "powerpc",
+                  target_arch = "powerpc64",
+                  target_arch = "sparc64"))))]
+const TIOCGEXCL: u32 = 0x80045440;
+
+#[cfg(not(target_os = "linux"))]
+const TIOCOUTQ: u32 = 0x40047473;
+
+#[cfg(all(target_os = "linux",
+          any(target_arch = "mips",
+              target_arch = "mips64")))]
+const TIOCSERGETLSR: u32 = 0x548e;
+
+#[cfg(all(target_os = "linux",
+          not(any(target_arch = "mips",
+                  target_arch = "mips64"))))]
+const TIOCSERGETLSR: u32 = 0x5459;
+
+// Bit of the line status returned by `TIOCSERGETLSR` that's set when the transmitter is empty.
+#[cfg(target_os = "linux")]
+const TIOCSER_TEMT: c_int = 0x01;
+
+// Shortest and longest intervals between checks of the transmitter while waiting for transmission
+// to complete. Between them, the interval is the time the queued data takes to transmit.
+const TRANSMIT_POLL_MIN_INTERVAL_MS: u64 = 1;
+const TRANSMIT_POLL_MAX_INTERVAL_MS: u64 = 100;
+
+// Maximum number of bytes written at once with DTR/DSR flow control.
+const DTR_DSR_CHUNK_LEN: usize = 16;
+
+// Shortest and longest intervals between checks of DSR while transmission is suspended by DTR/DSR
+// flow control. The interval doubles while DSR stays deasserted.
+const DSR_POLL_MIN_INTERVAL_MS: u64 = 1;
+const DSR_POLL_MAX_INTERVAL_MS: u64 = 32;
+
 
 /// A TTY-based serial port implementation.
 ///
 /// The port will be closed when the value is dropped.
+///
+/// ## DTR/DSR flow control
+///
+/// TTY devices don't support DTR/DSR flow control in the driver, so `FlowDtrDsr` is emulated by
+/// `write()`. Before writing, DSR is read with an ioctl, and while it's not asserted, it's read
+/// again after an interval that starts at one millisecond and doubles up to 32 milliseconds, until
+/// the write timeout expires. Each write then transfers at most 16 bytes, so `write_all()` checks
+/// DSR again for every 16 bytes. Data that has already been written is sent by the driver even if
+/// DSR is deasserted. Only output is flow controlled: DTR is left as it is, which is asserted when
+/// the device is opened.
+///
+/// The emulation is part of `TTYSettings` but not of the terminal attributes, so settings created
+/// with `TTYSettings::from_termios()` have DTR/DSR flow control disabled.
+///
+/// ## Software flow control
+///
+/// Output can be suspended and resumed with `suspend_output()` and `resume_output()`, which call
+/// `tcflow()`. TTY drivers don't report whether output has been stopped, either by
+/// `suspend_output()` or by an XOFF character received with `FlowSoftware`, so
+/// `is_output_stopped()` returns an error of kind `Unsupported`.
 pub struct TTYPort {
     fd: RawFd,
-    timeout: Duration,
+    path: PathBuf,
+    read_timeout: Timeout,
+    write_timeout: Timeout,
+    inter_byte_timeout: Option<u32>,
+    char_time: Option<Duration>,
+    min_read_len: u8,
+    dtr_dsr: bool,
+    exclusive: bool,
+    lock: Option<LockGuard>,
 }
 
-impl TTYPort {
-    /// Opens a TTY device as a serial port.
+/// Options for opening a TTY device.
+///
+/// `OpenOptions` selects how a `TTYPort` coordinates access to the device with other processes.
+/// By default, the device is opened for exclusive access with the `TIOCEXCL` ioctl, and neither an
+/// advisory lock nor a lock file is taken, which is equivalent to calling `TTYPort::open()`.
+///
+/// ```no_run
+/// use std::path::Path;
+///
+/// let port = serial_unix::OpenOptions::new()
+///     .uucp_lock(true)
+///     .open(Path::new("/dev/ttyUSB0"))
+///     .unwrap();
+/// ```
+#[derive(Debug,Clone)]
+pub struct OpenOptions {
+    exclusive: bool,
+    advisory_lock: bool,
+    uucp_lock: bool,
+    lock_dir: PathBuf,
+}
+
+impl OpenOptions {
+    /// Creates a new set of options with the default configuration.
+    pub fn new() -> Self {
+        OpenOptions {
+            exclusive: true,
+            advisory_lock: false,
+            uucp_lock: false,
+            lock_dir: PathBuf::from(super::lock::DEFAULT_LOCK_DIR),
+        }
+    }
+
+    /// Sets whether to put the device in exclusive mode with the `TIOCEXCL` ioctl.
     ///
-    /// `path` should be the path to a TTY device, e.g., `/dev/ttyS0`.
+    /// While a device is in exclusive mode, further attempts to open it fail, except for processes
+    /// running as root. Exclusive mode is enabled by default.
+    pub fn exclusive(&mut self, exclusive: bool) -> &mut Self {
+        self.exclusive = exclusive;
+        self
+    }
+
+    /// Sets whether to take an advisory lock on the device with `flock(LOCK_EX | LOCK_NB)`.
     ///
-    /// ```no_run
-    /// use std::path::Path;
+    /// Advisory locks only serialize access between processes that also take the lock, but they
+    /// apply to root and behave consistently for pseudoterminals, which makes them suitable for
+    /// cooperating processes in containers. The lock is released when the port is dropped.
+    pub fn advisory_lock(&mut self, advisory_lock: bool) -> &mut Self {
+        self.advisory_lock = advisory_lock;
+        self
+    }
+
+    /// Sets whether to acquire a UUCP-style lock file before opening the device.
     ///
-    /// serial_unix::TTYPort::open(Path::new("/dev/ttyS0")).unwrap();
-    /// ```
+    /// Lock files coordinate access with minicom, picocom and other tools that use them, and
+    /// unlike `TIOCEXCL` they are also honored by processes running as root. The lock file is
+    /// named after the device, e.g., `LCK..ttyUSB0`, and contains the PID of the current process.
+    /// Lock files left behind by processes that no longer exist are removed. The lock file is
+    /// removed when the port is dropped.
+    pub fn uucp_lock(&mut self, uucp_lock: bool) -> &mut Self {
+        self.uucp_lock = uucp_lock;
+        self
+    }
+
+    /// Sets the directory in which UUCP-style lock files are created.
+    ///
+    /// The default directory is `/var/lock`.
+    pub fn lock_dir<P: AsRef<Path>>(&mut self, lock_dir: P) -> &mut Self {
+        self.lock_dir = lock_dir.as_ref().to_path_buf();
+        self
+    }
+
+    /// Opens a TTY device as a serial port with the configured options.
+    ///
+    /// `path` should be the path to a TTY device, e.g., `/dev/ttyS0`.
     ///
     /// ## Errors
     ///
     /// * `NoDevice` if the device could not be opened. This could indicate that the device is
-    ///   already in use.
+    ///   already in use, or if another process holds the device's advisory lock or lock file. If
+    ///   the device is busy or permission is denied, the error's description includes a
+    ///   [`Diagnosis`](struct.Diagnosis.html) of the device.
     /// * `InvalidInput` if `port` is not a valid device name.
     /// * `Io` for any other error while opening or initializing the device.
-    pub fn open(path: &Path) -> core::Result<Self> {
-        use libc::{O_RDWR, O_NOCTTY, O_NONBLOCK, TIOCEXCL, F_SETFL, EINVAL};
+    pub fn open(&self, path: &Path) -> core::Result<TTYPort> {
+        use libc::{O_RDWR, O_NOCTTY, O_NONBLOCK, TIOCEXCL, F_SETFL, EINVAL, EWOULDBLOCK};
+        use libc::{LOCK_EX, LOCK_NB};
 
         let cstr = match CString::new(path.as_os_str().as_bytes()) {
             Ok(s) => s,
-            Err(_) => return Err(super::error::from_raw_os_error(EINVAL)),
+            Err(_) => return Err(super::error::from_raw_os_error(EINVAL).with_path(path)),
+        };
+
+        // lock the device before opening it, so a locked device is left untouched
+        let lock = if self.uucp_lock {
+            Some(try!(LockGuard::acquire(&self.lock_dir, path)))
+        }
+        else {
+            None
         };
 
         let fd = unsafe { libc::open(cstr.as_ptr(), O_RDWR | O_NOCTTY | O_NONBLOCK, 0) };
         if fd < 0 {
-            return Err(super::error::last_os_error());
+            return Err(open_error(path, &self.lock_dir));
         }
 
         let mut port = TTYPort {
             fd: fd,
-            timeout: Duration::from_millis(100),
+            path: path.to_path_buf(),
+            read_timeout: Timeout::Duration(Duration::from_millis(100)),
+            write_timeout: Timeout::Duration(Duration::from_millis(100)),
+            inter_byte_timeout: None,
+            char_time: None,
+            min_read_len: 0,
+            dtr_dsr: false,
+            exclusive: false,
+            lock: lock,
         };
 
         unsafe {
             // get exclusive access to device
-            if libc::ioctl(port.fd, TIOCEXCL as _) < 0 {
-                return Err(super::error::last_os_error());
+            if self.exclusive {
+                if libc::ioctl(port.fd, TIOCEXCL as _) < 0 {
+                    return Err(port.last_os_error("TIOCEXCL"));
+                }
+
+                port.exclusive = true;
+            }
+
+            // serialize access with cooperating processes
+            if self.advisory_lock && libc::flock(port.fd, LOCK_EX | LOCK_NB) < 0 {
+                let errno = super::error::errno();
+
+                if errno == EWOULDBLOCK {
+                    return Err(core::Error::new(core::ErrorKind::NoDevice, "Device is locked by another process")
+                               .with_source(io::Error::from_raw_os_error(errno))
+                               .with_operation("flock")
+                               .with_path(path));
+                }
+
+                return Err(port.last_os_error("flock"));
             }
 
             // clear O_NONBLOCK flag
             if libc::fcntl(port.fd, F_SETFL, 0) < 0 {
-                return Err(super::error::last_os_error());
+                return Err(port.last_os_error("fcntl"));
             }
         }
 
-        // apply initial settings
-        let settings = try!(port.read_settings());
+        // apply initial settings, with reads timed by poll() rather than VMIN and VTIME, and without
+        // translating input
+        let mut settings = try!(port.read_settings());
+        settings.set_min_read_len(0);
+        settings.termios.c_cc[libc::VTIME] = 0;
+        settings.termios.c_iflag &= !(libc::INLCR | libc::IGNCR | libc::ICRNL | libc::IGNBRK);
         try!(port.write_settings(&settings));
 
         Ok(port)
     }
+}
+
+impl Default for OpenOptions {
+    fn default() -> Self {
+        OpenOptions::new()
+    }
+}
+
+impl TTYPort {
+    /// Opens a TTY device as a serial port.
+    ///
+    /// `path` should be the path to a TTY device, e.g., `/dev/ttyS0`.
+    ///
+    /// ```no_run
+    /// use std::path::Path;
+    ///
+    /// serial_unix::TTYPort::open(Path::new("/dev/ttyS0")).unwrap();
+    /// ```
+    ///
+    /// To open a device with other options, such as a UUCP-style lock file, use
+    /// [`OpenOptions`](struct.OpenOptions.html).
+    ///
+    /// ## Errors
+    ///
+    /// * `NoDevice` if the device could not be opened. This could indicate that the device is
+    ///   already in use. If the device is busy or permission is denied, the error's description
+    ///   includes a [`Diagnosis`](struct.Diagnosis.html) of the device.
+    /// * `InvalidInput` if `port` is not a valid device name.
+    /// * `Io` for any other error while opening or initializing the device.
+    pub fn open(path: &Path) -> core::Result<Self> {
+        OpenOptions::new().open(path)
+    }
+
+    /// Returns whether the device is in exclusive mode.
+    ///
+    /// On Linux, the device's mode is queried with the `TIOCGEXCL` ioctl, so this also reflects
+    /// changes made by other processes. On other systems, this returns whether the port put the
+    /// device in exclusive mode when it was opened.
+    ///
+    /// ## Errors
+    ///
+    /// This function returns an error if the mode could not be queried:
+    ///
+    /// * `NoDevice` if the device was disconnected.
+    /// * `Io` for any other type of I/O error.
+    #[cfg(target_os = "linux")]
+    pub fn is_exclusive(&self) -> core::Result<bool> {
+        let mut exclusive: c_int = 0;
+
+        if unsafe { libc::ioctl(self.fd, TIOCGEXCL as _, &mut exclusive) } < 0 {
+            return Err(self.last_os_error("TIOCGEXCL"));
+        }
+
+        Ok(exclusive != 0)
+    }
+
+    /// Returns whether the device is in exclusive mode.
+    ///
+    /// On Linux, the device's mode is queried with the `TIOCGEXCL` ioctl, so this also reflects
+    /// changes made by other processes. On other systems, this returns whether the port put the
+    /// device in exclusive mode when it was opened.
+    #[cfg(not(target_os = "linux"))]
+    pub fn is_exclusive(&self) -> core::Result<bool> {
+        Ok(self.exclusive)
+    }
+
+    /// Returns the RS-485 settings of the device.
+    ///
+    /// ## Errors
+    ///
+    /// This function returns an error if the settings could not be read:
+    ///
+    /// * `Unsupported` if the device's driver doesn't support RS-485 mode.
+    /// * `NoDevice` if the device was disconnected.
+    /// * `Io` for any other type of I/O error.
+    #[cfg(target_os = "linux")]
+    pub fn get_rs485(&self) -> core::Result<Rs485Config> {
+        let mut raw = rs485::serial_rs485::default();
+
+        if unsafe { libc::ioctl(self.fd, rs485::TIOCGRS485 as _, &mut raw) } < 0 {
+            return Err(self.ioctl_error("TIOCGRS485"));
+        }
+
+        Ok(rs485::decode(&raw))
+    }
+
+    /// Applies RS-485 settings to the device.
+    ///
+    /// While RS-485 mode is enabled, the driver controls RTS, so it shouldn't be set with
+    /// `set_rts()`.
+    ///
+    /// ```no_run
+    /// use std::path::Path;
+    /// use serial_unix::{Rs485Config, TTYPort};
+    ///
+    /// let mut port = TTYPort::open(Path::new("/dev/ttyS1")).unwrap();
+    ///
+    /// port.set_rs485(&Rs485Config {
+    ///     enabled: true,
+    ///     rts_on_send: true,
+    ///     ..Rs485Config::default()
+    /// }).unwrap();
+    /// ```
+    ///
+    /// ## Errors
+    ///
+    /// This function returns an error if the settings could not be applied:
+    ///
+    /// * `Unsupported` if the device's driver doesn't support RS-485 mode.
+    /// * `InvalidInput` if the driver rejected the settings.
+    /// * `NoDevice` if the device was disconnected.
+    /// * `Io` for any other type of I/O error.
+    #[cfg(target_os = "linux")]
+    pub fn set_rs485(&mut self, config: &Rs485Config) -> core::Result<()> {
+        let raw = rs485::encode(config);
+
+        if unsafe { libc::ioctl(self.fd, rs485::TIOCSRS485 as _, &raw) } < 0 {
+            return Err(self.ioctl_error("TIOCSRS485"));
+        }
+
+        Ok(())
+    }
+
+    /// Returns information about the device reported by its driver.
+    ///
+    /// ## Errors
+    ///
+    /// This function returns an error if the information could not be read:
+    ///
+    /// * `Unsupported` if the device's driver doesn't report it.
+    /// * `NoDevice` if the device was disconnected.
+    /// * `Io` for any other type of I/O error.
+    #[cfg(target_os = "linux")]
+    pub fn serial_info(&self) -> core::Result<SerialInfo> {
+        self.read_serial_struct().map(|raw| serial_info::decode(&raw))
+    }
+
+    /// Sets whether the driver is in low latency mode (`ASYNC_LOW_LATENCY`).
+    ///
+    /// In low latency mode, the driver passes received data on without buffering it. For example,
+    /// FTDI adapters lower their latency timer from 16 milliseconds to 1 millisecond.
+    ///
+    /// ## Errors
+    ///
+    /// This function returns an error if the mode could not be changed:
+    ///
+    /// * `Unsupported` if the device's driver doesn't support changing its settings.
+    /// * `NoDevice` if the device was disconnected.
+    /// * `Io` for any other type of I/O error.
+    #[cfg(target_os = "linux")]
+    pub fn set_low_latency(&mut self, enabled: bool) -> core::Result<()> {
+        let mut raw = try!(self.read_serial_struct());
+        serial_info::set_low_latency(&mut raw, enabled);
+
+        if unsafe { libc::ioctl(self.fd, serial_info::TIOCSSERIAL as _, &raw) } < 0 {
+            return Err(self.ioctl_error("TIOCSSERIAL"));
+        }
+
+        Ok(())
+    }
+
+    /// Returns the baud rate that the device produces for a requested baud rate.
+    ///
+    /// This should be called after writing settings with the requested baud rate. On Linux, if the
+    /// driver reports a known type of UART, the actual baud rate is computed from the UART's base
+    /// rate assuming an integer divisor, as used by 16550-compatible UARTs, or from its custom
+    /// divisor if `ASYNC_SPD_CUST` is set. Otherwise, the actual baud rate is read back from the
+    /// device's settings, which drivers such as `ftdi_sio`, `ch341` and `cp210x` update to the rate
+    /// they produce.
+    ///
+    /// ```no_run
+    /// extern crate serial_core;
+    /// extern crate serial_unix;
+    ///
+    /// use std::path::Path;
+    /// use serial_core::prelude::*;
+    ///
+    /// # fn main() {
+    /// let mut port = serial_unix::TTYPort::open(Path::new("/dev/ttyS0")).unwrap();
+    /// port.reconfigure(&|settings| settings.set_baud_rate(serial_core::BaudOther(250000))).unwrap();
+    ///
+    /// let rate = port.effective_baud_rate(serial_core::BaudOther(250000)).unwrap();
+    /// println!("{} baud ({:+.2}%)", rate.actual, rate.error_percent());
+    /// # }
+    /// ```
+    ///
+    /// ## Errors
+    ///
+    /// This function returns an error if the baud rate could not be determined:
+    ///
+    /// * `NoDevice` if the device was disconnected.
+    /// * `Io` for any other type of I/O error, including a baud rate that isn't recognized.
+    pub fn effective_baud_rate(&self, requested: core::BaudRate) -> core::Result<core::EffectiveBaudRate> {
+        let settings = try!(SerialDevice::read_settings(self));
+
+        let actual = match settings.baud_rate() {
+            Some(baud_rate) => baud_rate.speed(),
+            None => {
+                return Err(core::Error::new(core::ErrorKind::Io(io::ErrorKind::InvalidData), "Unrecognized baud rate")
+                               .with_path(&self.path));
+            }
+        };
+
+        Ok(core::EffectiveBaudRate {
+            requested: requested.speed(),
+            actual: try!(self.divided_baud_rate(&settings, actual)),
+        })
+    }
+
+    /// Returns the path of the lock file held for the device, if any.
+    pub fn lock_file(&self) -> Option<&Path> {
+        self.lock.as_ref().map(|lock| lock.path())
+    }
 
     fn set_pin(&mut self, pin: c_int, level: bool) -> core::Result<()> {
         use libc::{TIOCMBIS, TIOCMBIC};
@@ -117,67 +526,168 @@ impl TTYPort {
         };
 
         if retval < 0 {
-            return Err(super::error::last_os_error());
+            return Err(self.last_os_error(if level { "TIOCMBIS" } else { "TIOCMBIC" }));
         }
 
         Ok(())
     }
 
+    #[cfg(target_os = "linux")]
+    fn read_serial_struct(&self) -> core::Result<serial_info::serial_struct> {
+        let mut raw: serial_info::serial_struct = unsafe { mem::zeroed() };
+
+        if unsafe { libc::ioctl(self.fd, serial_info::TIOCGSERIAL as _, &mut raw) } < 0 {
+            return Err(self.ioctl_error("TIOCGSERIAL"));
+        }
+
+        Ok(raw)
+    }
+
+    // Returns the baud rate produced by dividing the UART's base rate to approximate `speed`, or the
+    // rate read back from `settings` if the driver doesn't report a known type of UART, e.g., a USB
+    // adapter or a pseudo terminal.
+    #[cfg(target_os = "linux")]
+    fn divided_baud_rate(&self, settings: &TTYSettings, speed: usize) -> core::Result<usize> {
+        let divided = match self.serial_info() {
+            Ok(info) => serial_info::divided_baud_rate(&info, speed),
+            Err(ref e) if e.kind() == core::ErrorKind::Unsupported => None,
+            Err(e) => return Err(e),
+        };
+
+        Ok(divided.unwrap_or_else(|| read_back_baud_rate(settings, speed)))
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn divided_baud_rate(&self, _settings: &TTYSettings, speed: usize) -> core::Result<usize> {
+        Ok(speed)
+    }
+
     fn read_pin(&mut self, pin: c_int) -> core::Result<bool> {
+        Ok(try!(self.read_pins()) & pin != 0)
+    }
+
+    fn read_pins(&mut self) -> core::Result<c_int> {
         use libc::{TIOCMGET};
 
         unsafe {
             let mut pins: c_int = mem::uninitialized();
 
             if libc::ioctl(self.fd, TIOCMGET, &mut pins) < 0 {
-                return Err(super::error::last_os_error());
+                return Err(self.last_os_error("TIOCMGET"));
             }
 
-            Ok(pins & pin != 0)
+            Ok(pins)
         }
     }
-}
 
-impl Drop for TTYPort {
-    fn drop(&mut self) {
-        use libc::{TIOCNXCL};
+    fn write_pins(&mut self, pins: c_int) -> core::Result<()> {
+        use libc::{TIOCMSET};
 
-        unsafe {
-            libc::ioctl(self.fd, TIOCNXCL as _);
-            libc::close(self.fd);
+        if unsafe { libc::ioctl(self.fd, TIOCMSET, &pins) } < 0 {
+            return Err(self.last_os_error("TIOCMSET"));
         }
+
+        Ok(())
     }
-}
 
-impl AsRawFd for TTYPort {
-    fn as_raw_fd(&self) -> RawFd {
-        self.fd
+    fn flow(&mut self, action: c_int, operation: &str) -> core::Result<()> {
+        if unsafe { libc::tcflow(self.fd, action) } < 0 {
+            return Err(self.last_os_error(operation));
+        }
+
+        Ok(())
     }
-}
 
-impl io::Read for TTYPort {
-    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        try!(super::poll::wait_read_fd(self.fd, self.timeout));
+    // Waits for DSR to be asserted for DTR/DSR flow control, up to the write timeout.
+    fn wait_dsr(&mut self) -> io::Result<()> {
+        let deadline = self.write_timeout.deadline();
+        let mut interval = Duration::from_millis(DSR_POLL_MIN_INTERVAL_MS);
 
-        let len = unsafe {
-            libc::read(self.fd, buf.as_ptr() as *mut c_void, buf.len() as size_t)
-        };
+        while !try!(self.read_pin(libc::TIOCM_DSR)) {
+            let sleep = match deadline {
+                Some(deadline) => {
+                    let now = Instant::now();
 
-        if len >= 0 {
-            Ok(len as usize)
+                    if now >= deadline {
+                        return Err(io::Error::new(self.write_timeout.error_kind(), "Timed out waiting for DSR"));
+                    }
+
+                    cmp::min(interval, deadline - now)
+                }
+                None => interval,
+            };
+
+            thread::sleep(sleep);
+            interval = cmp::min(interval * 2, Duration::from_millis(DSR_POLL_MAX_INTERVAL_MS));
         }
-        else {
-            Err(io::Error::last_os_error())
+
+        Ok(())
+    }
+
+    // Returns the number of bytes in the kernel's output buffer.
+    fn queued_output(&self) -> core::Result<usize> {
+        #[cfg(target_os = "linux")]
+        use libc::TIOCOUTQ;
+
+        let mut queued: c_int = 0;
+
+        if unsafe { libc::ioctl(self.fd, TIOCOUTQ as _, &mut queued) } < 0 {
+            return Err(self.last_os_error("TIOCOUTQ"));
         }
+
+        Ok(cmp::max(queued, 0) as usize)
     }
-}
 
-impl io::Write for TTYPort {
-    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-        try!(super::poll::wait_write_fd(self.fd, self.timeout));
+    // Returns the duration of one character, or `None` if the baud rate or character format is
+    // unknown.
+    fn char_time(&self) -> core::Result<Option<Duration>> {
+        Ok(try!(SerialDevice::read_settings(self)).char_time())
+    }
+
+    // Returns the write timeout extended by the time the kernel's output buffer takes to transmit,
+    // so that flushing at low baud rates doesn't time out while data is still being sent.
+    fn flush_timeout(&self) -> core::Result<Timeout> {
+        let timeout = match self.write_timeout {
+            Timeout::Duration(timeout) => timeout,
+            timeout => return Ok(timeout),
+        };
 
+        let queued = try!(self.queued_output());
+
+        Ok(match try!(self.char_time()) {
+            Some(char_time) => match timeout.checked_add(transmit_time(char_time, queued)) {
+                Some(timeout) => Timeout::Duration(timeout),
+                None => Timeout::Infinite,
+            },
+            None => self.write_timeout,
+        })
+    }
+
+    #[cfg(target_os = "linux")]
+    fn is_transmitter_empty(&self) -> core::Result<bool> {
+        use libc::{EINVAL, ENOTTY, EOPNOTSUPP};
+
+        let mut status: c_int = 0;
+
+        if unsafe { libc::ioctl(self.fd, TIOCSERGETLSR as _, &mut status) } < 0 {
+            return match super::error::errno() {
+                // the driver doesn't report the line status, so only the output buffer is checked
+                EINVAL | ENOTTY | EOPNOTSUPP => Ok(true),
+                _ => Err(self.last_os_error("TIOCSERGETLSR")),
+            };
+        }
+
+        Ok(status & TIOCSER_TEMT != 0)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn is_transmitter_empty(&self) -> core::Result<bool> {
+        Ok(true)
+    }
+
+    fn read_available(&mut self, buf: &mut [u8]) -> io::Result<usize> {
         let len = unsafe {
-            libc::write(self.fd, buf.as_ptr() as *mut c_void, buf.len() as size_t)
+            libc::read(self.fd, buf.as_ptr() as *mut c_void, buf.len() as size_t)
         };
 
         if len >= 0 {
@@ -188,61 +698,259 @@ impl io::Write for TTYPort {
         }
     }
 
-    fn flush(&mut self) -> io::Result<()> {
-        unsafe {
-            if libc::tcdrain(self.fd) < 0 {
-                Err(io::Error::last_os_error())
-            }
-            else {
-                Ok(())
-            }
+    // Returns the time the line must be idle to end a read, if an inter-byte timeout applies.
+    fn inter_byte_gap(&self) -> Option<Duration> {
+        match (self.inter_byte_timeout, self.char_time) {
+            (Some(chars), Some(char_time)) if self.read_timeout != Timeout::NonBlocking => Some(char_time * chars),
+            _ => None,
         }
     }
-}
-
-impl SerialDevice for TTYPort {
-    type Settings = TTYSettings;
-
-    fn read_settings(&self) -> core::Result<TTYSettings> {
-        use libc::{CREAD, CLOCAL}; // cflags
-        use libc::{ICANON, ECHO, ECHOE, ECHOK, ECHONL, ISIG, IEXTEN}; // lflags
-        use libc::{OPOST}; // oflags
-        use libc::{INLCR, IGNCR, ICRNL, IGNBRK}; // iflags
-        use libc::{VMIN, VTIME}; // c_cc indexes
 
-        let mut termios = try!(termios::read(self.fd));
+    // Returns the last OS error, annotated with the failed operation and the device's path.
+    fn last_os_error(&self, operation: &str) -> core::Error {
+        super::error::last_os_error().with_operation(operation).with_path(&self.path)
+    }
 
-        // setup TTY for binary serial port access
-        termios.c_cflag |= CREAD | CLOCAL;
-        termios.c_lflag &= !(ICANON | ECHO | ECHOE | ECHOK | ECHONL | ISIG | IEXTEN);
-        termios.c_oflag &= !OPOST;
-        termios.c_iflag &= !(INLCR | IGNCR | ICRNL | IGNBRK);
+    // Like `last_os_error()`, but reports ioctls that the driver doesn't support as `Unsupported`.
+    #[cfg(target_os = "linux")]
+    fn ioctl_error(&self, operation: &str) -> core::Error {
+        use libc::{ENOTTY, EOPNOTSUPP};
 
-        termios.c_cc[VMIN] = 0;
-        termios.c_cc[VTIME] = 0;
+        match super::error::errno() {
+            errno @ ENOTTY | errno @ EOPNOTSUPP => {
+                let description = super::error::error_string(errno);
 
-        Ok(TTYSettings::new(termios))
+                core::Error::new(core::ErrorKind::Unsupported, description)
+                    .with_source(io::Error::from_raw_os_error(errno))
+                    .with_operation(operation)
+                    .with_path(&self.path)
+            }
+            _ => self.last_os_error(operation),
+        }
     }
+}
 
-    fn write_settings(&mut self, settings: &TTYSettings) -> core::Result<()> {
-        try!(termios::write(self.fd, &settings.termios));
-        try!(termios::flush(self.fd));
+// Returns the modem control bits `pins` with DTR and RTS set to the levels in `control`.
+fn modem_control_pins(pins: c_int, control: core::ModemControl) -> c_int {
+    use libc::{TIOCM_DTR, TIOCM_RTS};
 
-        Ok(())
-    }
+    let mut pins = pins & !(TIOCM_DTR | TIOCM_RTS);
 
-    fn timeout(&self) -> Duration {
-        self.timeout
+    if control.dtr {
+        pins |= TIOCM_DTR;
     }
 
-    fn set_timeout(&mut self, timeout: Duration) -> core::Result<()> {
-        self.timeout = timeout;
-        Ok(())
+    if control.rts {
+        pins |= TIOCM_RTS;
     }
 
-    fn set_rts(&mut self, level: bool) -> core::Result<()> {
-        self.set_pin(libc::TIOCM_RTS, level)
-    }
+    pins
+}
+
+// Returns the error from a failed call to `open()`. Errors that suggest the device is in use or
+// inaccessible are annotated with a diagnosis of the device, which looks for lock files in
+// `lock_dir`.
+fn open_error(path: &Path, lock_dir: &Path) -> core::Error {
+    use libc::{EBUSY, EACCES, EPERM};
+
+    let errno = super::error::errno();
+
+    let diagnosis = match errno {
+        EBUSY | EACCES | EPERM => super::diagnose::diagnose_with_lock_dir(path, lock_dir).ok().map(|d| d.to_string()),
+        _ => None,
+    };
+
+    super::error::from_raw_os_error_with_detail(errno, diagnosis.as_ref().map(|s| &s[..]))
+        .with_operation("open")
+        .with_path(path)
+}
+
+// Reports a timeout as `WouldBlock` for non-blocking operations.
+fn timeout_error(error: io::Error, timeout: Timeout) -> io::Error {
+    if error.kind() == io::ErrorKind::TimedOut && timeout.error_kind() != io::ErrorKind::TimedOut {
+        io::Error::new(timeout.error_kind(), "Operation would block")
+    }
+    else {
+        error
+    }
+}
+
+// Returns the time it takes to transmit `len` queued bytes and the character in the UART's
+// transmitter.
+fn transmit_time(char_time: Duration, len: usize) -> Duration {
+    char_time * (cmp::min(len, u32::max_value() as usize - 1) as u32 + 1)
+}
+
+// Returns the output baud rate stored in `c_ospeed`, which drivers update to the rate they produce,
+// or `speed` if it's not set.
+#[cfg(target_os = "linux")]
+fn read_back_baud_rate(settings: &TTYSettings, speed: usize) -> usize {
+    match settings.as_termios().c_ospeed {
+        0 => speed,
+        ospeed => ospeed as usize,
+    }
+}
+
+impl Drop for TTYPort {
+    fn drop(&mut self) {
+        use libc::{TIOCNXCL};
+
+        unsafe {
+            if self.exclusive {
+                libc::ioctl(self.fd, TIOCNXCL as _);
+            }
+
+            libc::close(self.fd);
+        }
+    }
+}
+
+impl AsRawFd for TTYPort {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd
+    }
+}
+
+impl io::Read for TTYPort {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        // with VMIN set, read() blocks until data arrives, so there's no need to wait for it
+        if self.min_read_len == 0 || self.read_timeout != Timeout::Infinite {
+            try!(super::poll::wait_read_fd(self.fd, self.read_timeout.as_duration()).map_err(|e| timeout_error(e, self.read_timeout)));
+        }
+
+        let mut len = try!(self.read_available(buf));
+
+        // keep receiving until the line is idle, returning what was received if an error occurs
+        if let Some(gap) = self.inter_byte_gap() {
+            while len > 0 && len < buf.len() {
+                if super::poll::wait_read_fd(self.fd, Some(gap)).is_err() {
+                    break;
+                }
+
+                match self.read_available(&mut buf[len..]) {
+                    Ok(0) | Err(_) => break,
+                    Ok(n) => len += n,
+                }
+            }
+        }
+
+        Ok(len)
+    }
+}
+
+impl io::Write for TTYPort {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        let buf = if self.dtr_dsr {
+            try!(self.wait_dsr());
+            &buf[..cmp::min(buf.len(), DTR_DSR_CHUNK_LEN)]
+        }
+        else {
+            buf
+        };
+
+        try!(super::poll::wait_write_fd(self.fd, self.write_timeout.as_duration()).map_err(|e| timeout_error(e, self.write_timeout)));
+
+        let len = unsafe {
+            libc::write(self.fd, buf.as_ptr() as *mut c_void, buf.len() as size_t)
+        };
+
+        if len >= 0 {
+            Ok(len as usize)
+        }
+        else {
+            Err(io::Error::last_os_error())
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if self.write_timeout != Timeout::Infinite {
+            let timeout = try!(self.flush_timeout());
+            return self.wait_transmit_complete(timeout).map_err(io::Error::from);
+        }
+
+        unsafe {
+            if libc::tcdrain(self.fd) < 0 {
+                Err(io::Error::last_os_error())
+            }
+            else {
+                Ok(())
+            }
+        }
+    }
+}
+
+impl SerialDevice for TTYPort {
+    type Settings = TTYSettings;
+
+    fn read_settings(&self) -> core::Result<TTYSettings> {
+        use libc::{CREAD, CLOCAL}; // cflags
+        use libc::{ICANON, ECHO, ECHOE, ECHOK, ECHONL, ISIG, IEXTEN}; // lflags
+        use libc::{OPOST}; // oflags
+
+        let mut termios = try!(termios::read(self.fd).map_err(|e| e.with_path(&self.path)));
+
+        // setup TTY for binary serial port access
+        termios.c_cflag |= CREAD | CLOCAL;
+        termios.c_lflag &= !(ICANON | ECHO | ECHOE | ECHOK | ECHONL | ISIG | IEXTEN);
+        termios.c_oflag &= !OPOST;
+
+        let mut settings = TTYSettings::new(termios);
+        settings.dtr_dsr = self.dtr_dsr;
+        Ok(settings)
+    }
+
+    fn write_settings(&mut self, settings: &TTYSettings) -> core::Result<()> {
+        try!(termios::write(self.fd, &settings.termios).map_err(|e| e.with_path(&self.path)));
+        try!(termios::flush(self.fd).map_err(|e| e.with_path(&self.path)));
+
+        self.char_time = settings.char_time();
+        self.min_read_len = settings.min_read_len();
+        self.dtr_dsr = settings.dtr_dsr;
+        Ok(())
+    }
+
+    fn read_timeout(&self) -> Timeout {
+        self.read_timeout
+    }
+
+    fn write_timeout(&self) -> Timeout {
+        self.write_timeout
+    }
+
+    fn set_read_timeout(&mut self, timeout: Timeout) -> core::Result<()> {
+        self.read_timeout = timeout;
+        Ok(())
+    }
+
+    fn set_write_timeout(&mut self, timeout: Timeout) -> core::Result<()> {
+        self.write_timeout = timeout;
+        Ok(())
+    }
+
+    fn inter_byte_timeout(&self) -> Option<u32> {
+        self.inter_byte_timeout
+    }
+
+    fn set_inter_byte_timeout(&mut self, chars: Option<u32>) -> core::Result<()> {
+        if chars == Some(0) {
+            return Err(core::Error::new(core::ErrorKind::InvalidInput, "Inter-byte timeout must be at least one character time"));
+        }
+
+        self.inter_byte_timeout = chars;
+        Ok(())
+    }
+
+    fn set_rts(&mut self, level: bool) -> core::Result<()> {
+        self.set_pin(libc::TIOCM_RTS, level)
+    }
 
     fn set_dtr(&mut self, level: bool) -> core::Result<()> {
         self.set_pin(libc::TIOCM_DTR, level)
@@ -263,17 +971,194 @@ impl SerialDevice for TTYPort {
     fn read_cd(&mut self) -> core::Result<bool> {
         self.read_pin(libc::TIOCM_CD)
     }
+
+    fn set_modem_control(&mut self, control: core::ModemControl) -> core::Result<()> {
+        let pins = try!(self.read_pins());
+        self.write_pins(modem_control_pins(pins, control))
+    }
+
+    fn read_dtr(&mut self) -> core::Result<bool> {
+        self.read_pin(libc::TIOCM_DTR)
+    }
+
+    fn read_rts(&mut self) -> core::Result<bool> {
+        self.read_pin(libc::TIOCM_RTS)
+    }
+
+    fn set_break(&mut self, enabled: bool) -> core::Result<()> {
+        use libc::{TIOCSBRK, TIOCCBRK};
+
+        let retval = unsafe {
+            if enabled {
+                libc::ioctl(self.fd, TIOCSBRK)
+            }
+            else {
+                libc::ioctl(self.fd, TIOCCBRK)
+            }
+        };
+
+        if retval < 0 {
+            return Err(self.last_os_error(if enabled { "TIOCSBRK" } else { "TIOCCBRK" }));
+        }
+
+        Ok(())
+    }
+
+    fn suspend_output(&mut self) -> core::Result<()> {
+        self.flow(libc::TCOOFF, "TCOOFF")
+    }
+
+    fn resume_output(&mut self) -> core::Result<()> {
+        self.flow(libc::TCOON, "TCOON")
+    }
+
+    fn send_xoff(&mut self) -> core::Result<()> {
+        self.flow(libc::TCIOFF, "TCIOFF")
+    }
+
+    fn send_xon(&mut self) -> core::Result<()> {
+        self.flow(libc::TCION, "TCION")
+    }
+
+    fn wait_transmit_complete(&mut self, timeout: Timeout) -> core::Result<()> {
+        let deadline = timeout.deadline();
+        let char_time = try!(self.char_time());
+
+        loop {
+            let queued = try!(self.queued_output());
+
+            if queued == 0 && try!(self.is_transmitter_empty()) {
+                return Ok(());
+            }
+
+            let now = Instant::now();
+            if deadline.map(|deadline| now >= deadline) == Some(true) {
+                return Err(core::Error::new(core::ErrorKind::Io(timeout.error_kind()), "Timed out waiting for transmission")
+                               .with_path(&self.path));
+            }
+
+            // sleep for about as long as the queued data takes to transmit
+            let min_interval = Duration::from_millis(TRANSMIT_POLL_MIN_INTERVAL_MS);
+            let max_interval = Duration::from_millis(TRANSMIT_POLL_MAX_INTERVAL_MS);
+            let interval = char_time.map(|char_time| transmit_time(char_time, queued)).unwrap_or(min_interval);
+            let mut sleep = cmp::max(min_interval, cmp::min(max_interval, interval));
+
+            if let Some(deadline) = deadline {
+                sleep = cmp::min(sleep, deadline - now);
+            }
+
+            thread::sleep(sleep);
+        }
+    }
 }
 
+/// The raw terminal attributes wrapped by [`TTYSettings`](struct.TTYSettings.html).
+///
+/// On Linux, this is `libc::termios2`, which is read and written with the `TCGETS2` and `TCSETS2`
+/// ioctls so that arbitrary baud rates can be stored in `c_ispeed` and `c_ospeed`. On other
+/// platforms, it's `libc::termios`, which is read and written with `tcgetattr()` and
+/// `tcsetattr()`.
+pub type Termios = termios::termios;
+
 /// Serial port settings for TTY devices.
 #[derive(Copy,Clone)]
 pub struct TTYSettings {
     termios: termios::termios,
+    dtr_dsr: bool,
 }
 
 impl TTYSettings {
     fn new(termios: termios::termios) -> Self {
-        TTYSettings { termios: termios }
+        TTYSettings {
+            termios: termios,
+            dtr_dsr: false,
+        }
+    }
+
+    /// Creates settings from raw terminal attributes.
+    ///
+    /// The attributes are used as given. In particular, settings created this way aren't put into
+    /// raw mode, which [`read_settings()`](struct.TTYPort.html#method.read_settings) otherwise
+    /// ensures.
+    ///
+    /// DTR/DSR flow control is emulated by `TTYPort` and has no representation in the terminal
+    /// attributes, so settings created this way have it disabled, and `as_termios()` doesn't
+    /// include it. Use `set_flow_control(FlowDtrDsr)` to enable it.
+    pub fn from_termios(termios: Termios) -> Self {
+        TTYSettings::new(termios)
+    }
+
+    /// Returns the raw terminal attributes.
+    pub fn as_termios(&self) -> &Termios {
+        &self.termios
+    }
+
+    /// Returns the raw terminal attributes for modification.
+    ///
+    /// This allows setting options that `TTYSettings` doesn't provide methods for. The changes are
+    /// applied when the settings are written to a port with `write_settings()`.
+    ///
+    /// ```no_run
+    /// extern crate libc;
+    /// extern crate serial_core;
+    /// extern crate serial_unix;
+    ///
+    /// use std::path::Path;
+    /// use serial_core::SerialDevice;
+    ///
+    /// # fn main() {
+    /// let mut port = serial_unix::TTYPort::open(Path::new("/dev/ttyUSB0")).unwrap();
+    /// let mut settings = port.read_settings().unwrap();
+    ///
+    /// settings.as_termios_mut().c_cflag |= libc::HUPCL;
+    /// port.write_settings(&settings).unwrap();
+    /// # }
+    /// ```
+    pub fn as_termios_mut(&mut self) -> &mut Termios {
+        &mut self.termios
+    }
+
+    /// Returns the minimum number of bytes for a read to return (`VMIN`).
+    pub fn min_read_len(&self) -> u8 {
+        self.termios.c_cc[libc::VMIN]
+    }
+
+    /// Sets the minimum number of bytes for a read to return (`VMIN`).
+    ///
+    /// Together with the inter-character time, this lets the kernel batch received bytes into
+    /// fewer reads. Once data is available, a read returns when at least `len` bytes have been
+    /// received, or when the inter-character time has elapsed since the last byte, if it's set.
+    /// The port's read timeout still limits how long a read waits for the first byte, except for
+    /// an infinite timeout, in which case the kernel waits for `len` bytes directly.
+    ///
+    /// A port opened with [`TTYPort::open()`](struct.TTYPort.html#method.open) starts with a
+    /// minimum of zero, so reads return as soon as any data is available.
+    pub fn set_min_read_len(&mut self, len: u8) {
+        self.termios.c_cc[libc::VMIN] = len;
+    }
+
+    /// Returns the kernel's inter-character time (`VTIME`).
+    pub fn inter_char_time(&self) -> Duration {
+        Duration::from_millis(self.termios.c_cc[libc::VTIME] as u64 * 100)
+    }
+
+    /// Sets the kernel's inter-character time (`VTIME`).
+    ///
+    /// With a nonzero minimum read length, a read returns once no byte has been received for
+    /// `time` after the last one. The time is rounded up to tenths of a second.
+    ///
+    /// ## Errors
+    ///
+    /// This function returns an `InvalidInput` error if `time` is longer than 25.5 seconds.
+    pub fn set_inter_char_time(&mut self, time: Duration) -> core::Result<()> {
+        let deciseconds = time.as_secs() * 10 + (time.subsec_nanos() as u64 + 99_999_999) / 100_000_000;
+
+        if deciseconds > 255 {
+            return Err(core::Error::new(core::ErrorKind::InvalidInput, "Inter-character time must not exceed 25.5 seconds"));
+        }
+
+        self.termios.c_cc[libc::VTIME] = deciseconds as libc::cc_t;
+        Ok(())
     }
 }
 
@@ -295,6 +1180,7 @@ impl fmt::Debug for TTYSettings {
 
         f.debug_struct("TTYSettings")
             .field("termios", &TermiosFormatter(self.termios))
+            .field("dtr_dsr", &self.dtr_dsr)
             .finish()
     }
 }
@@ -375,222 +1261,1031 @@ impl SerialPortSettings for TTYSettings {
                 #[cfg(target_os = "linux")]
                 B4000000 => Some(core::BaudOther(4000000)),
 
-                _ => None,
-            },
-            termios::Speed::Custom(baud) => Some(core::BaudOther(baud as usize)),
-        }
+                _ => None,
+            },
+            termios::Speed::Custom(baud) => Some(core::BaudOther(baud as usize)),
+        }
+    }
+
+    fn char_size(&self) -> Option<core::CharSize> {
+        use libc::{CSIZE, CS5, CS6, CS7, CS8};
+
+        match self.termios.c_cflag & CSIZE {
+            CS8 => Some(core::Bits8),
+            CS7 => Some(core::Bits7),
+            CS6 => Some(core::Bits6),
+            CS5 => Some(core::Bits5),
+
+            _ => None,
+        }
+    }
+
+    fn parity(&self) -> Option<core::Parity> {
+        use libc::{PARENB, PARODD};
+
+        if self.termios.c_cflag & PARENB != 0 {
+            if self.termios.c_cflag & PARODD != 0 {
+                Some(core::ParityOdd)
+            }
+            else {
+                Some(core::ParityEven)
+            }
+        }
+        else {
+            Some(core::ParityNone)
+        }
+    }
+
+    fn stop_bits(&self) -> Option<core::StopBits> {
+        use libc::CSTOPB;
+
+        if self.termios.c_cflag & CSTOPB != 0 {
+            Some(core::Stop2)
+        }
+        else {
+            Some(core::Stop1)
+        }
+    }
+
+    fn flow_control(&self) -> Option<core::FlowControl> {
+        use libc::{IXON, IXOFF};
+        use libc::CRTSCTS;
+
+        if self.dtr_dsr {
+            Some(core::FlowDtrDsr)
+        }
+        else if self.termios.c_cflag & CRTSCTS != 0 {
+            Some(core::FlowHardware)
+        }
+        else if self.termios.c_iflag & (IXON | IXOFF) != 0 {
+            Some(core::FlowSoftware)
+        }
+        else {
+            Some(core::FlowNone)
+        }
+    }
+
+    fn set_baud_rate(&mut self, baud_rate: core::BaudRate) -> core::Result<()> {
+        use libc::{B50, B75, B110, B134, B150, B200, B300, B600, B1200, B1800, B2400, B4800, B9600, B19200, B38400};
+        use libc::{B57600, B115200, B230400};
+
+        #[cfg(target_os = "linux")]
+        use libc::{B460800, B500000, B576000, B921600, B1000000, B1152000, B1500000, B2000000, B2500000, B3000000, B3500000, B4000000};
+
+        #[cfg(target_os = "macos")]
+        use libc::{B7200, B14400, B28800, B76800};
+
+        #[cfg(target_os = "freebsd")]
+        use libc::{B7200, B14400, B28800, B76800, B460800, B921600};
+
+        #[cfg(target_os = "openbsd")]
+        use libc::{B7200, B14400, B28800, B76800};
+
+        let speed = match baud_rate {
+            core::BaudOther(50)      => termios::Speed::Standard(B50),
+            core::BaudOther(75)      => termios::Speed::Standard(B75),
+            core::Baud110            => termios::Speed::Standard(B110),
+            core::BaudOther(134)     => termios::Speed::Standard(B134),
+            core::BaudOther(150)     => termios::Speed::Standard(B150),
+            core::BaudOther(200)     => termios::Speed::Standard(B200),
+            core::Baud300            => termios::Speed::Standard(B300),
+            core::Baud600            => termios::Speed::Standard(B600),
+            core::Baud1200           => termios::Speed::Standard(B1200),
+            core::BaudOther(1800)    => termios::Speed::Standard(B1800),
+            core::Baud2400           => termios::Speed::Standard(B2400),
+            core::Baud4800           => termios::Speed::Standard(B4800),
+            #[cfg(any(target_os = "macos", target_os = "freebsd", target_os = "openbsd"))]
+            core::BaudOther(7200)    => termios::Speed::Standard(B7200),
+            core::Baud9600           => termios::Speed::Standard(B9600),
+            #[cfg(any(target_os = "macos", target_os = "freebsd", target_os = "openbsd"))]
+            core::BaudOther(14400)   => termios::Speed::Standard(B14400),
+            core::Baud19200          => termios::Speed::Standard(B19200),
+            #[cfg(any(target_os = "macos", target_os = "freebsd", target_os = "openbsd"))]
+            core::BaudOther(28800)   => termios::Speed::Standard(B28800),
+            core::Baud38400          => termios::Speed::Standard(B38400),
+            core::Baud57600          => termios::Speed::Standard(B57600),
+            #[cfg(any(target_os = "macos", target_os = "freebsd", target_os = "openbsd"))]
+            core::BaudOther(76800)   => termios::Speed::Standard(B76800),
+            core::Baud115200         => termios::Speed::Standard(B115200),
+            core::BaudOther(230400)  => termios::Speed::Standard(B230400),
+            #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+            core::BaudOther(460800)  => termios::Speed::Standard(B460800),
+            #[cfg(target_os = "linux")]
+            core::BaudOther(500000)  => termios::Speed::Standard(B500000),
+            #[cfg(target_os = "linux")]
+            core::BaudOther(576000)  => termios::Speed::Standard(B576000),
+            #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+            core::BaudOther(921600)  => termios::Speed::Standard(B921600),
+            #[cfg(target_os = "linux")]
+            core::BaudOther(1000000) => termios::Speed::Standard(B1000000),
+            #[cfg(target_os = "linux")]
+            core::BaudOther(1152000) => termios::Speed::Standard(B1152000),
+            #[cfg(target_os = "linux")]
+            core::BaudOther(1500000) => termios::Speed::Standard(B1500000),
+            #[cfg(target_os = "linux")]
+            core::BaudOther(2000000) => termios::Speed::Standard(B2000000),
+            #[cfg(target_os = "linux")]
+            core::BaudOther(2500000) => termios::Speed::Standard(B2500000),
+            #[cfg(target_os = "linux")]
+            core::BaudOther(3000000) => termios::Speed::Standard(B3000000),
+            #[cfg(target_os = "linux")]
+            core::BaudOther(3500000) => termios::Speed::Standard(B3500000),
+            #[cfg(target_os = "linux")]
+            core::BaudOther(4000000) => termios::Speed::Standard(B4000000),
+
+            core::BaudOther(baud) => termios::Speed::Custom(baud as libc::speed_t),
+        };
+
+        try!(termios::set_speed(&mut self.termios, speed));
+
+        Ok(())
+    }
+
+    fn set_char_size(&mut self, char_size: core::CharSize) {
+        use libc::{CSIZE, CS5, CS6, CS7, CS8};
+
+        let size = match char_size {
+            core::Bits5 => CS5,
+            core::Bits6 => CS6,
+            core::Bits7 => CS7,
+            core::Bits8 => CS8,
+        };
+
+        self.termios.c_cflag &= !CSIZE;
+        self.termios.c_cflag |= size;
+    }
+
+    fn set_parity(&mut self, parity: core::Parity) {
+        use libc::{PARENB, PARODD, INPCK, IGNPAR};
+
+        match parity {
+            core::ParityNone => {
+                self.termios.c_cflag &= !(PARENB | PARODD);
+                self.termios.c_iflag &= !INPCK;
+                self.termios.c_iflag |= IGNPAR;
+            }
+            core::ParityOdd => {
+                self.termios.c_cflag |= PARENB | PARODD;
+                self.termios.c_iflag |= INPCK;
+                self.termios.c_iflag &= !IGNPAR;
+            }
+            core::ParityEven => {
+                self.termios.c_cflag &= !PARODD;
+                self.termios.c_cflag |= PARENB;
+                self.termios.c_iflag |= INPCK;
+                self.termios.c_iflag &= !IGNPAR;
+            }
+        };
+    }
+
+    fn set_stop_bits(&mut self, stop_bits: core::StopBits) {
+        use libc::CSTOPB;
+
+        match stop_bits {
+            core::Stop1 => self.termios.c_cflag &= !CSTOPB,
+            core::Stop2 => self.termios.c_cflag |= CSTOPB,
+        };
+    }
+
+    fn set_flow_control(&mut self, flow_control: core::FlowControl) {
+        use libc::{IXON, IXOFF};
+        use libc::CRTSCTS;
+
+        self.dtr_dsr = false;
+
+        match flow_control {
+            core::FlowNone => {
+                self.termios.c_iflag &= !(IXON | IXOFF);
+                self.termios.c_cflag &= !CRTSCTS;
+            }
+            core::FlowSoftware => {
+                self.termios.c_iflag |= IXON | IXOFF;
+                self.termios.c_cflag &= !CRTSCTS;
+            }
+            core::FlowHardware => {
+                self.termios.c_iflag &= !(IXON | IXOFF);
+                self.termios.c_cflag |= CRTSCTS;
+            }
+            core::FlowDtrDsr => {
+                // emulated by TTYPort::write()
+                self.termios.c_iflag &= !(IXON | IXOFF);
+                self.termios.c_cflag &= !CRTSCTS;
+                self.dtr_dsr = true;
+            }
+        };
+    }
+
+    fn xon_xoff_chars(&self) -> (u8, u8) {
+        (self.termios.c_cc[libc::VSTART], self.termios.c_cc[libc::VSTOP])
+    }
+
+    fn set_xon_xoff_chars(&mut self, xon: u8, xoff: u8) -> core::Result<()> {
+        if xon == xoff {
+            return Err(core::Error::new(core::ErrorKind::InvalidInput, "XON and XOFF characters must differ"));
+        }
+
+        self.termios.c_cc[libc::VSTART] = xon;
+        self.termios.c_cc[libc::VSTOP] = xoff;
+        Ok(())
+    }
+}
+
+/// Handling of break conditions received on a TTY device.
+#[derive(Debug,Copy,Clone,PartialEq,Eq)]
+pub enum BreakHandling {
+    /// Breaks are ignored (`IGNBRK`).
+    Ignore,
+
+    /// Breaks flush the input and output queues (`BRKINT`). If the device is the controlling
+    /// terminal of a foreground process group, that process group is also sent `SIGINT`.
+    Interrupt,
+
+    /// Breaks are read as a NUL byte, or as the sequence `\xff \0 \0` when parity errors are
+    /// marked.
+    Read,
+}
+
+/// Handling of bytes received on a TTY device with parity or framing errors.
+///
+/// Parity errors are only detected when parity checking is enabled with
+/// [`set_parity_check()`](trait.UnixSettingsExt.html#tymethod.set_parity_check).
+#[derive(Debug,Copy,Clone,PartialEq,Eq)]
+pub enum ParityErrorHandling {
+    /// Bytes with errors are discarded (`IGNPAR`).
+    Ignore,
+
+    /// Bytes with errors are preceded by the sequence `\xff \0` (`PARMRK`). A valid `\xff` byte
+    /// is read as `\xff \xff`.
+    Mark,
+
+    /// Bytes with errors are read as a NUL byte.
+    Zero,
+}
+
+/// Extensions to serial port settings for the input processing options of TTY devices.
+///
+/// `TTYSettings::set_parity()` enables parity checking and sets parity error handling to `Zero` for
+/// odd and even parity, and disables parity checking and sets parity error handling to `Ignore`
+/// for no parity. To use different options, set them after setting the parity mode.
+///
+/// ```no_run
+/// use serial_unix::{BreakHandling, ParityErrorHandling, UnixSettingsExt};
+///
+/// fn mark_errors(settings: &mut serial_unix::TTYSettings) {
+///     settings.set_break_handling(BreakHandling::Read);
+///     settings.set_parity_check(true);
+///     settings.set_parity_error_handling(ParityErrorHandling::Mark);
+/// }
+/// ```
+pub trait UnixSettingsExt {
+    /// Returns how break conditions are handled.
+    fn break_handling(&self) -> BreakHandling;
+
+    /// Sets how break conditions are handled.
+    ///
+    /// A port opened with [`TTYPort::open()`](struct.TTYPort.html#method.open) doesn't ignore
+    /// breaks.
+    fn set_break_handling(&mut self, handling: BreakHandling);
+
+    /// Returns how bytes with parity or framing errors are handled.
+    fn parity_error_handling(&self) -> ParityErrorHandling;
+
+    /// Sets how bytes with parity or framing errors are handled.
+    fn set_parity_error_handling(&mut self, handling: ParityErrorHandling);
+
+    /// Returns whether parity checking of received bytes is enabled (`INPCK`).
+    fn parity_check(&self) -> bool;
+
+    /// Enables or disables parity checking of received bytes (`INPCK`).
+    fn set_parity_check(&mut self, enabled: bool);
+
+    /// Returns whether the eighth bit of received bytes is cleared (`ISTRIP`).
+    fn strip_high_bit(&self) -> bool;
+
+    /// Enables or disables clearing the eighth bit of received bytes (`ISTRIP`).
+    fn set_strip_high_bit(&mut self, enabled: bool);
+
+    /// Returns whether any received byte restarts output stopped by software flow control
+    /// (`IXANY`).
+    fn restart_on_any_char(&self) -> bool;
+
+    /// Enables or disables restarting output stopped by software flow control when any byte is
+    /// received, rather than only the start character (`IXANY`).
+    fn set_restart_on_any_char(&mut self, enabled: bool);
+}
+
+impl UnixSettingsExt for TTYSettings {
+    fn break_handling(&self) -> BreakHandling {
+        use libc::{IGNBRK, BRKINT};
+
+        if self.termios.c_iflag & IGNBRK != 0 {
+            BreakHandling::Ignore
+        }
+        else if self.termios.c_iflag & BRKINT != 0 {
+            BreakHandling::Interrupt
+        }
+        else {
+            BreakHandling::Read
+        }
+    }
+
+    fn set_break_handling(&mut self, handling: BreakHandling) {
+        use libc::{IGNBRK, BRKINT};
+
+        self.termios.c_iflag &= !(IGNBRK | BRKINT);
+
+        match handling {
+            BreakHandling::Ignore => self.termios.c_iflag |= IGNBRK,
+            BreakHandling::Interrupt => self.termios.c_iflag |= BRKINT,
+            BreakHandling::Read => (),
+        }
+    }
+
+    fn parity_error_handling(&self) -> ParityErrorHandling {
+        use libc::{IGNPAR, PARMRK};
+
+        if self.termios.c_iflag & IGNPAR != 0 {
+            ParityErrorHandling::Ignore
+        }
+        else if self.termios.c_iflag & PARMRK != 0 {
+            ParityErrorHandling::Mark
+        }
+        else {
+            ParityErrorHandling::Zero
+        }
+    }
+
+    fn set_parity_error_handling(&mut self, handling: ParityErrorHandling) {
+        use libc::{IGNPAR, PARMRK};
+
+        self.termios.c_iflag &= !(IGNPAR | PARMRK);
+
+        match handling {
+            ParityErrorHandling::Ignore => self.termios.c_iflag |= IGNPAR,
+            ParityErrorHandling::Mark => self.termios.c_iflag |= PARMRK,
+            ParityErrorHandling::Zero => (),
+        }
+    }
+
+    fn parity_check(&self) -> bool {
+        self.termios.c_iflag & libc::INPCK != 0
+    }
+
+    fn set_parity_check(&mut self, enabled: bool) {
+        set_flag(&mut self.termios.c_iflag, libc::INPCK, enabled);
+    }
+
+    fn strip_high_bit(&self) -> bool {
+        self.termios.c_iflag & libc::ISTRIP != 0
+    }
+
+    fn set_strip_high_bit(&mut self, enabled: bool) {
+        set_flag(&mut self.termios.c_iflag, libc::ISTRIP, enabled);
+    }
+
+    fn restart_on_any_char(&self) -> bool {
+        self.termios.c_iflag & libc::IXANY != 0
+    }
+
+    fn set_restart_on_any_char(&mut self, enabled: bool) {
+        set_flag(&mut self.termios.c_iflag, libc::IXANY, enabled);
+    }
+}
+
+fn set_flag(flags: &mut libc::tcflag_t, flag: libc::tcflag_t, enabled: bool) {
+    if enabled {
+        *flags |= flag;
+    }
+    else {
+        *flags &= !flag;
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use core;
+    use libc;
+
+    use std::ffi::CStr;
+    use std::fs;
+    use std::io;
+    use std::io::prelude::*;
+    use std::mem;
+    use std::path::PathBuf;
+    use std::thread;
+    use std::time::Duration;
+
+    use std::os::unix::prelude::*;
+
+    use super::{BreakHandling, OpenOptions, ParityErrorHandling, TTYPort, TTYSettings, UnixSettingsExt};
+    use super::transmit_time;
+    use marked::{MarkedReader, ReadItem};
+    use core::prelude::*;
+    use core::Timeout;
+    use testing::temp_dir;
+
+    #[cfg(target_os = "linux")]
+    use rs485::Rs485Config;
+
+    #[cfg(target_os = "linux")]
+    use super::read_back_baud_rate;
+
+    fn default_settings() -> TTYSettings {
+        TTYSettings::new(unsafe { mem::uninitialized() })
+    }
+
+    // The master side of a pseudoterminal, which is closed when dropped.
+    struct Pty {
+        fd: RawFd,
+        path: PathBuf,
+    }
+
+    impl Pty {
+        fn write(&self, data: &[u8]) {
+            let len = unsafe { libc::write(self.fd, data.as_ptr() as *const libc::c_void, data.len()) };
+            assert_eq!(len, data.len() as isize);
+        }
+
+        // Reads data written to the slave side, waiting up to `timeout_ms` for it to arrive.
+        fn read(&self, buf: &mut [u8], timeout_ms: libc::c_int) -> usize {
+            let mut fds = libc::pollfd { fd: self.fd, events: libc::POLLIN, revents: 0 };
+
+            if unsafe { libc::poll(&mut fds, 1, timeout_ms) } <= 0 {
+                return 0;
+            }
+
+            let len = unsafe { libc::read(self.fd, buf.as_mut_ptr() as *mut libc::c_void, buf.len()) };
+            assert!(len >= 0);
+            len as usize
+        }
+    }
+
+    impl Drop for Pty {
+        fn drop(&mut self) {
+            unsafe { libc::close(self.fd) };
+        }
+    }
+
+    fn open_pty() -> Pty {
+        use libc::{O_RDWR, O_NOCTTY};
+
+        unsafe {
+            let fd = libc::posix_openpt(O_RDWR | O_NOCTTY);
+            assert!(fd >= 0);
+            assert_eq!(libc::grantpt(fd), 0);
+            assert_eq!(libc::unlockpt(fd), 0);
+
+            Pty {
+                fd: fd,
+                path: pts_name(fd),
+            }
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    unsafe fn pts_name(fd: RawFd) -> PathBuf {
+        let mut name = [0 as libc::c_char; 128];
+        assert_eq!(libc::ptsname_r(fd, name.as_mut_ptr(), name.len()), 0);
+
+        PathBuf::from(CStr::from_ptr(name.as_ptr()).to_str().unwrap())
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    unsafe fn pts_name(fd: RawFd) -> PathBuf {
+        let name = libc::ptsname(fd);
+        assert!(!name.is_null());
+
+        PathBuf::from(CStr::from_ptr(name).to_str().unwrap())
+    }
+
+    #[test]
+    fn open_options_holds_uucp_lock_while_open() {
+        let pty = open_pty();
+        let dir = temp_dir("tty-lock");
+
+        let port = OpenOptions::new().uucp_lock(true).lock_dir(&dir).open(&pty.path).unwrap();
+        let lock_file = port.lock_file().unwrap().to_path_buf();
+        assert!(lock_file.exists());
+
+        drop(port);
+        assert!(!lock_file.exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn open_options_fails_when_device_is_locked() {
+        let pty = open_pty();
+        let dir = temp_dir("tty-locked");
+
+        let _port = OpenOptions::new().uucp_lock(true).lock_dir(&dir).open(&pty.path).unwrap();
+        let err = OpenOptions::new().uucp_lock(true).lock_dir(&dir).open(&pty.path).err().unwrap();
+        assert_eq!(err.kind(), core::ErrorKind::NoDevice);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn open_options_fails_when_advisory_lock_is_held() {
+        let pty = open_pty();
+
+        let _port = OpenOptions::new().exclusive(false).advisory_lock(true).open(&pty.path).unwrap();
+        let err = OpenOptions::new().exclusive(false).advisory_lock(true).open(&pty.path).err().unwrap();
+        assert_eq!(err.kind(), core::ErrorKind::NoDevice);
+        assert_eq!(err.operation(), Some("flock"));
+    }
+
+    #[test]
+    fn tty_port_is_exclusive_by_default() {
+        let pty = open_pty();
+
+        let port = TTYPort::open(&pty.path).unwrap();
+        assert!(port.is_exclusive().unwrap());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn tty_port_rs485_is_unsupported_on_pty() {
+        let pty = open_pty();
+
+        let mut port = TTYPort::open(&pty.path).unwrap();
+        assert_eq!(port.get_rs485().unwrap_err().kind(), core::ErrorKind::Unsupported);
+
+        let err = port.set_rs485(&Rs485Config::default()).unwrap_err();
+        assert_eq!(err.kind(), core::ErrorKind::Unsupported);
+        assert_eq!(err.operation(), Some("TIOCSRS485"));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn tty_port_serial_info_is_unsupported_on_pty() {
+        let pty = open_pty();
+
+        let mut port = TTYPort::open(&pty.path).unwrap();
+        assert_eq!(port.serial_info().unwrap_err().kind(), core::ErrorKind::Unsupported);
+        assert_eq!(port.set_low_latency(true).unwrap_err().kind(), core::ErrorKind::Unsupported);
+    }
+
+    #[test]
+    fn tty_port_configures_verified_settings() {
+        let pty = open_pty();
+        let mut port = TTYPort::open(&pty.path).unwrap();
+
+        port.configure_verified(&core::PortSettings {
+            baud_rate: core::Baud115200,
+            char_size: core::Bits8,
+            parity: core::ParityNone,
+            stop_bits: core::Stop2,
+            flow_control: core::FlowNone,
+        }).unwrap();
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn tty_port_reports_settings_that_were_not_applied() {
+        let pty = open_pty();
+        let mut port = TTYPort::open(&pty.path).unwrap();
+
+        // Linux pseudoterminals always use eight data bits without parity
+        let err = port.configure_verified(&core::PortSettings {
+            baud_rate: core::Baud115200,
+            char_size: core::Bits7,
+            parity: core::ParityEven,
+            stop_bits: core::Stop1,
+            flow_control: core::FlowNone,
+        }).unwrap_err();
+
+        assert_eq!(err.kind(), core::ErrorKind::InvalidInput);
+        assert!(err.to_string().contains("char size: requested Bits7, got Some(Bits8)"));
+    }
+
+    #[test]
+    fn tty_port_reports_effective_baud_rate() {
+        let pty = open_pty();
+        let mut port = TTYPort::open(&pty.path).unwrap();
+
+        port.reconfigure(&|settings| settings.set_baud_rate(core::Baud19200)).unwrap();
+
+        let rate = port.effective_baud_rate(core::Baud19200).unwrap();
+        assert_eq!(rate, core::EffectiveBaudRate { requested: 19200, actual: 19200 });
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn read_back_baud_rate_uses_output_speed() {
+        let mut settings = default_settings();
+        settings.set_baud_rate(core::BaudOther(250000)).unwrap();
+
+        // e.g., ftdi_sio stores the rate its divisor produces
+        settings.as_termios_mut().c_ospeed = 250300;
+        assert_eq!(read_back_baud_rate(&settings, 250000), 250300);
+
+        settings.as_termios_mut().c_ospeed = 0;
+        assert_eq!(read_back_baud_rate(&settings, 250000), 250000);
+    }
+
+    #[test]
+    fn tty_port_is_not_exclusive_when_disabled() {
+        let pty = open_pty();
+
+        let port = OpenOptions::new().exclusive(false).open(&pty.path).unwrap();
+        assert!(!port.is_exclusive().unwrap());
+    }
+
+    #[test]
+    fn tty_port_read_times_out_without_data() {
+        let pty = open_pty();
+        let mut port = TTYPort::open(&pty.path).unwrap();
+
+        port.set_timeout(Timeout::Duration(Duration::from_millis(20))).unwrap();
+        let err = port.read(&mut [0u8; 16]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+    }
+
+    #[test]
+    fn tty_port_read_would_block_when_non_blocking() {
+        let pty = open_pty();
+        let mut port = TTYPort::open(&pty.path).unwrap();
+
+        port.set_timeout(Timeout::NonBlocking).unwrap();
+        let err = port.read(&mut [0u8; 16]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::WouldBlock);
+    }
+
+    #[test]
+    fn tty_port_read_returns_partial_data() {
+        let pty = open_pty();
+        let mut port = TTYPort::open(&pty.path).unwrap();
+
+        pty.write(b"abc");
+
+        let mut buf = [0u8; 16];
+        assert_eq!(port.read(&mut buf).unwrap(), 3);
+        assert_eq!(&buf[..3], b"abc");
+    }
+
+    #[test]
+    fn tty_port_read_waits_indefinitely_for_data() {
+        let pty = open_pty();
+        let mut port = TTYPort::open(&pty.path).unwrap();
+
+        port.set_timeout(Timeout::Infinite).unwrap();
+
+        let master = pty.fd;
+        let writer = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(150));
+            unsafe { libc::write(master, b"x".as_ptr() as *const libc::c_void, 1) };
+        });
+
+        assert_eq!(port.read(&mut [0u8; 16]).unwrap(), 1);
+        writer.join().unwrap();
+    }
+
+    #[test]
+    fn tty_port_follows_timeout_contract() {
+        let pty = open_pty();
+        let mut port = TTYPort::open(&pty.path).unwrap();
+
+        let master = pty.fd;
+        core::testing::check_timeout_contract(&mut port, move |data| {
+            let len = unsafe { libc::write(master, data.as_ptr() as *const libc::c_void, data.len()) };
+            assert_eq!(len, data.len() as isize);
+        });
+    }
+
+    #[test]
+    fn tty_port_read_timeout_is_independent_of_write_timeout() {
+        let pty = open_pty();
+        let mut port = TTYPort::open(&pty.path).unwrap();
+
+        port.set_write_timeout(Timeout::Infinite).unwrap();
+        port.set_read_timeout(Timeout::NonBlocking).unwrap();
+
+        assert_eq!(port.write(b"x").unwrap(), 1);
+        assert_eq!(port.read(&mut [0u8; 16]).unwrap_err().kind(), io::ErrorKind::WouldBlock);
+        assert_eq!(port.write_timeout(), Timeout::Infinite);
     }
 
-    fn char_size(&self) -> Option<core::CharSize> {
-        use libc::{CSIZE, CS5, CS6, CS7, CS8};
+    #[test]
+    fn tty_port_read_returns_when_line_is_idle() {
+        let pty = open_pty();
+        let mut port = TTYPort::open(&pty.path).unwrap();
 
-        match self.termios.c_cflag & CSIZE {
-            CS8 => Some(core::Bits8),
-            CS7 => Some(core::Bits7),
-            CS6 => Some(core::Bits6),
-            CS5 => Some(core::Bits5),
+        port.set_inter_byte_timeout(Some(10)).unwrap();
+        pty.write(b"ab");
 
-            _ => None,
-        }
+        let master = pty.fd;
+        let writer = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(300));
+            unsafe { libc::write(master, b"cd".as_ptr() as *const libc::c_void, 2) };
+        });
+
+        assert_eq!(port.read(&mut [0u8; 16]).unwrap(), 2);
+        writer.join().unwrap();
     }
 
-    fn parity(&self) -> Option<core::Parity> {
-        use libc::{PARENB, PARODD};
+    #[test]
+    fn tty_port_read_continues_until_line_is_idle() {
+        let pty = open_pty();
+        let mut port = TTYPort::open(&pty.path).unwrap();
+
+        // about one second at the pseudoterminal's default of 38400 baud
+        port.set_inter_byte_timeout(Some(4000)).unwrap();
+        pty.write(b"ab");
+
+        let master = pty.fd;
+        let writer = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            unsafe { libc::write(master, b"cd".as_ptr() as *const libc::c_void, 2) };
+        });
+
+        let mut buf = [0u8; 4];
+        assert_eq!(port.read(&mut buf).unwrap(), 4);
+        assert_eq!(&buf, b"abcd");
+        writer.join().unwrap();
+    }
 
-        if self.termios.c_cflag & PARENB != 0 {
-            if self.termios.c_cflag & PARODD != 0 {
-                Some(core::ParityOdd)
-            }
-            else {
-                Some(core::ParityEven)
+    #[test]
+    fn tty_port_rejects_zero_inter_byte_timeout() {
+        let pty = open_pty();
+        let mut port = TTYPort::open(&pty.path).unwrap();
+
+        assert_eq!(port.set_inter_byte_timeout(Some(0)).unwrap_err().kind(), core::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn tty_port_read_waits_for_min_read_len() {
+        let pty = open_pty();
+        let mut port = TTYPort::open(&pty.path).unwrap();
+
+        let mut settings = core::SerialDevice::read_settings(&port).unwrap();
+        settings.set_min_read_len(4);
+        core::SerialDevice::write_settings(&mut port, &settings).unwrap();
+        port.set_read_timeout(Timeout::Infinite).unwrap();
+
+        pty.write(b"ab");
+
+        let master = pty.fd;
+        let writer = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(100));
+            unsafe { libc::write(master, b"cd".as_ptr() as *const libc::c_void, 2) };
+        });
+
+        let mut buf = [0u8; 16];
+        assert_eq!(port.read(&mut buf).unwrap(), 4);
+        assert_eq!(&buf[..4], b"abcd");
+        writer.join().unwrap();
+    }
+
+    #[test]
+    fn tty_port_open_resets_min_read_len() {
+        let pty = open_pty();
+
+        let mut port = TTYPort::open(&pty.path).unwrap();
+        let mut settings = core::SerialDevice::read_settings(&port).unwrap();
+        settings.set_min_read_len(4);
+        settings.set_inter_char_time(Duration::from_millis(200)).unwrap();
+        core::SerialDevice::write_settings(&mut port, &settings).unwrap();
+        drop(port);
+
+        let port = TTYPort::open(&pty.path).unwrap();
+        let settings = core::SerialDevice::read_settings(&port).unwrap();
+        assert_eq!(settings.min_read_len(), 0);
+        assert_eq!(settings.inter_char_time(), Duration::from_secs(0));
+    }
+
+    #[test]
+    fn tty_port_read_returns_zero_for_empty_buffer() {
+        let pty = open_pty();
+        let mut port = TTYPort::open(&pty.path).unwrap();
+
+        assert_eq!(port.read(&mut []).unwrap(), 0);
+    }
+
+    #[test]
+    fn tty_settings_converts_from_termios() {
+        let mut termios = *default_settings().as_termios();
+        termios.c_iflag |= libc::IXANY;
+
+        let settings = TTYSettings::from_termios(termios);
+        assert!(settings.restart_on_any_char());
+    }
+
+    #[test]
+    fn marked_reader_reads_mark_byte_as_data() {
+        let pty = open_pty();
+        let mut port = TTYPort::open(&pty.path).unwrap();
+        port.set_timeout(Timeout::Duration(Duration::from_secs(1))).unwrap();
+
+        let mut reader = MarkedReader::new(port).unwrap();
+        pty.write(b"a\xffb");
+
+        let mut data = Vec::new();
+        while data.len() < 3 {
+            match reader.read_item().unwrap() {
+                ReadItem::Data(bytes) => data.extend(bytes),
+                item => panic!("unexpected item: {:?}", item),
             }
         }
-        else {
-            Some(core::ParityNone)
-        }
+
+        assert_eq!(data, b"a\xffb");
     }
 
-    fn stop_bits(&self) -> Option<core::StopBits> {
-        use libc::CSTOPB;
+    #[test]
+    fn tty_port_sends_xoff_and_xon() {
+        let pty = open_pty();
+        let mut port = TTYPort::open(&pty.path).unwrap();
+        let mut buf = [0u8; 8];
+
+        port.send_xoff().unwrap();
+        assert_eq!(pty.read(&mut buf, 1000), 1);
+        assert_eq!(buf[0], core::XOFF);
+
+        port.reconfigure(&|settings| settings.set_xon_xoff_chars(b'!', b'?')).unwrap();
+        port.send_xon().unwrap();
+        assert_eq!(pty.read(&mut buf, 1000), 1);
+        assert_eq!(buf[0], b'!');
+    }
 
-        if self.termios.c_cflag & CSTOPB != 0 {
-            Some(core::Stop2)
-        }
-        else {
-            Some(core::Stop1)
-        }
+    #[test]
+    fn tty_port_suspends_output() {
+        let pty = open_pty();
+        let mut port = TTYPort::open(&pty.path).unwrap();
+        let mut buf = [0u8; 8];
+
+        port.suspend_output().unwrap();
+        assert_eq!(port.write(b"a").unwrap_err().kind(), io::ErrorKind::TimedOut);
+
+        port.resume_output().unwrap();
+        assert_eq!(port.write(b"a").unwrap(), 1);
+        assert_eq!(pty.read(&mut buf, 1000), 1);
     }
 
-    fn flow_control(&self) -> Option<core::FlowControl> {
-        use libc::{IXON, IXOFF};
-        use libc::CRTSCTS;
+    #[test]
+    fn tty_port_does_not_report_stopped_output() {
+        let pty = open_pty();
+        let mut port = TTYPort::open(&pty.path).unwrap();
 
-        if self.termios.c_cflag & CRTSCTS != 0 {
-            Some(core::FlowHardware)
-        }
-        else if self.termios.c_iflag & (IXON | IXOFF) != 0 {
-            Some(core::FlowSoftware)
-        }
-        else {
-            Some(core::FlowNone)
-        }
+        port.suspend_output().unwrap();
+        assert_eq!(port.is_output_stopped().unwrap_err().kind(), core::ErrorKind::Unsupported);
     }
 
-    fn set_baud_rate(&mut self, baud_rate: core::BaudRate) -> core::Result<()> {
-        use libc::{B50, B75, B110, B134, B150, B200, B300, B600, B1200, B1800, B2400, B4800, B9600, B19200, B38400};
-        use libc::{B57600, B115200, B230400};
+    #[test]
+    fn tty_port_waits_for_transmit_complete() {
+        let pty = open_pty();
+        let mut port = TTYPort::open(&pty.path).unwrap();
+        let mut buf = [0u8; 8];
+
+        assert_eq!(port.write(b"abc").unwrap(), 3);
+        port.wait_transmit_complete(Timeout::Duration(Duration::from_millis(100))).unwrap();
+        assert_eq!(pty.read(&mut buf, 1000), 3);
+    }
 
-        #[cfg(target_os = "linux")]
-        use libc::{B460800, B500000, B576000, B921600, B1000000, B1152000, B1500000, B2000000, B2500000, B3000000, B3500000, B4000000};
+    #[test]
+    fn tty_port_flushes_with_write_timeout() {
+        let pty = open_pty();
+        let mut port = TTYPort::open(&pty.path).unwrap();
+        let mut buf = [0u8; 8];
+
+        port.set_write_timeout(Timeout::NonBlocking).unwrap();
+        assert_eq!(port.write(b"abc").unwrap(), 3);
+        port.flush().unwrap();
+        assert_eq!(pty.read(&mut buf, 1000), 3);
+    }
 
-        #[cfg(target_os = "macos")]
-        use libc::{B7200, B14400, B28800, B76800};
+    #[test]
+    fn transmit_time_includes_transmitter() {
+        assert_eq!(transmit_time(Duration::from_millis(1), 0), Duration::from_millis(1));
+        assert_eq!(transmit_time(Duration::from_millis(1), 9), Duration::from_millis(10));
+        assert_eq!(transmit_time(Duration::from_millis(1), usize::max_value()),
+                   Duration::from_millis(u64::from(u32::max_value())));
+    }
 
-        #[cfg(target_os = "freebsd")]
-        use libc::{B7200, B14400, B28800, B76800, B460800, B921600};
+    #[test]
+    fn tty_port_writes_raw_termios_flags() {
+        let pty = open_pty();
+        let mut port = TTYPort::open(&pty.path).unwrap();
 
-        #[cfg(target_os = "openbsd")]
-        use libc::{B7200, B14400, B28800, B76800};
+        let mut settings = core::SerialDevice::read_settings(&port).unwrap();
+        settings.as_termios_mut().c_cflag |= libc::HUPCL;
+        core::SerialDevice::write_settings(&mut port, &settings).unwrap();
 
-        let speed = match baud_rate {
-            core::BaudOther(50)      => termios::Speed::Standard(B50),
-            core::BaudOther(75)      => termios::Speed::Standard(B75),
-            core::Baud110            => termios::Speed::Standard(B110),
-            core::BaudOther(134)     => termios::Speed::Standard(B134),
-            core::BaudOther(150)     => termios::Speed::Standard(B150),
-            core::BaudOther(200)     => termios::Speed::Standard(B200),
-            core::Baud300            => termios::Speed::Standard(B300),
-            core::Baud600            => termios::Speed::Standard(B600),
-            core::Baud1200           => termios::Speed::Standard(B1200),
-            core::BaudOther(1800)    => termios::Speed::Standard(B1800),
-            core::Baud2400           => termios::Speed::Standard(B2400),
-            core::Baud4800           => termios::Speed::Standard(B4800),
-            #[cfg(any(target_os = "macos", target_os = "freebsd", target_os = "openbsd"))]
-            core::BaudOther(7200)    => termios::Speed::Standard(B7200),
-            core::Baud9600           => termios::Speed::Standard(B9600),
-            #[cfg(any(target_os = "macos", target_os = "freebsd", target_os = "openbsd"))]
-            core::BaudOther(14400)   => termios::Speed::Standard(B14400),
-            core::Baud19200          => termios::Speed::Standard(B19200),
-            #[cfg(any(target_os = "macos", target_os = "freebsd", target_os = "openbsd"))]
-            core::BaudOther(28800)   => termios::Speed::Standard(B28800),
-            core::Baud38400          => termios::Speed::Standard(B38400),
-            core::Baud57600          => termios::Speed::Standard(B57600),
-            #[cfg(any(target_os = "macos", target_os = "freebsd", target_os = "openbsd"))]
-            core::BaudOther(76800)   => termios::Speed::Standard(B76800),
-            core::Baud115200         => termios::Speed::Standard(B115200),
-            core::BaudOther(230400)  => termios::Speed::Standard(B230400),
-            #[cfg(any(target_os = "linux", target_os = "freebsd"))]
-            core::BaudOther(460800)  => termios::Speed::Standard(B460800),
-            #[cfg(target_os = "linux")]
-            core::BaudOther(500000)  => termios::Speed::Standard(B500000),
-            #[cfg(target_os = "linux")]
-            core::BaudOther(576000)  => termios::Speed::Standard(B576000),
-            #[cfg(any(target_os = "linux", target_os = "freebsd"))]
-            core::BaudOther(921600)  => termios::Speed::Standard(B921600),
-            #[cfg(target_os = "linux")]
-            core::BaudOther(1000000) => termios::Speed::Standard(B1000000),
-            #[cfg(target_os = "linux")]
-            core::BaudOther(1152000) => termios::Speed::Standard(B1152000),
-            #[cfg(target_os = "linux")]
-            core::BaudOther(1500000) => termios::Speed::Standard(B1500000),
-            #[cfg(target_os = "linux")]
-            core::BaudOther(2000000) => termios::Speed::Standard(B2000000),
-            #[cfg(target_os = "linux")]
-            core::BaudOther(2500000) => termios::Speed::Standard(B2500000),
-            #[cfg(target_os = "linux")]
-            core::BaudOther(3000000) => termios::Speed::Standard(B3000000),
-            #[cfg(target_os = "linux")]
-            core::BaudOther(3500000) => termios::Speed::Standard(B3500000),
-            #[cfg(target_os = "linux")]
-            core::BaudOther(4000000) => termios::Speed::Standard(B4000000),
+        let settings = core::SerialDevice::read_settings(&port).unwrap();
+        assert!(settings.as_termios().c_cflag & libc::HUPCL != 0);
+    }
 
-            core::BaudOther(baud) => termios::Speed::Custom(baud as libc::speed_t),
-        };
+    #[test]
+    fn modem_control_pins_sets_dtr_and_rts() {
+        use libc::{TIOCM_CTS, TIOCM_DTR, TIOCM_RTS};
 
-        try!(termios::set_speed(&mut self.termios, speed));
+        let control = core::ModemControl { dtr: true, rts: false };
+        assert_eq!(super::modem_control_pins(TIOCM_RTS | TIOCM_CTS, control), TIOCM_DTR | TIOCM_CTS);
 
-        Ok(())
+        let control = core::ModemControl { dtr: false, rts: true };
+        assert_eq!(super::modem_control_pins(TIOCM_DTR, control), TIOCM_RTS);
     }
 
-    fn set_char_size(&mut self, char_size: core::CharSize) {
-        use libc::{CSIZE, CS5, CS6, CS7, CS8};
+    #[test]
+    fn tty_settings_sets_xon_xoff_chars() {
+        let mut settings = default_settings();
 
-        let size = match char_size {
-            core::Bits5 => CS5,
-            core::Bits6 => CS6,
-            core::Bits7 => CS7,
-            core::Bits8 => CS8,
-        };
+        settings.set_xon_xoff_chars(b'!', b'?').unwrap();
+        assert_eq!(settings.xon_xoff_chars(), (b'!', b'?'));
+        assert_eq!(settings.set_xon_xoff_chars(b'!', b'!').unwrap_err().kind(), core::ErrorKind::InvalidInput);
+        assert_eq!(settings.xon_xoff_chars(), (b'!', b'?'));
+    }
 
-        self.termios.c_cflag &= !CSIZE;
-        self.termios.c_cflag |= size;
+    #[test]
+    fn tty_settings_sets_break_handling() {
+        let mut settings = default_settings();
+
+        settings.set_break_handling(BreakHandling::Ignore);
+        assert_eq!(settings.break_handling(), BreakHandling::Ignore);
+
+        settings.set_break_handling(BreakHandling::Interrupt);
+        assert_eq!(settings.break_handling(), BreakHandling::Interrupt);
+
+        settings.set_break_handling(BreakHandling::Read);
+        assert_eq!(settings.break_handling(), BreakHandling::Read);
     }
 
-    fn set_parity(&mut self, parity: core::Parity) {
-        use libc::{PARENB, PARODD, INPCK, IGNPAR};
+    #[test]
+    fn tty_settings_sets_parity_error_handling() {
+        let mut settings = default_settings();
 
-        match parity {
-            core::ParityNone => {
-                self.termios.c_cflag &= !(PARENB | PARODD);
-                self.termios.c_iflag &= !INPCK;
-                self.termios.c_iflag |= IGNPAR;
-            }
-            core::ParityOdd => {
-                self.termios.c_cflag |= PARENB | PARODD;
-                self.termios.c_iflag |= INPCK;
-                self.termios.c_iflag &= !IGNPAR;
-            }
-            core::ParityEven => {
-                self.termios.c_cflag &= !PARODD;
-                self.termios.c_cflag |= PARENB;
-                self.termios.c_iflag |= INPCK;
-                self.termios.c_iflag &= !IGNPAR;
-            }
-        };
+        settings.set_parity_error_handling(ParityErrorHandling::Mark);
+        assert_eq!(settings.parity_error_handling(), ParityErrorHandling::Mark);
+
+        settings.set_parity_error_handling(ParityErrorHandling::Ignore);
+        assert_eq!(settings.parity_error_handling(), ParityErrorHandling::Ignore);
+
+        settings.set_parity_error_handling(ParityErrorHandling::Zero);
+        assert_eq!(settings.parity_error_handling(), ParityErrorHandling::Zero);
     }
 
-    fn set_stop_bits(&mut self, stop_bits: core::StopBits) {
-        use libc::CSTOPB;
+    #[test]
+    fn tty_settings_sets_parity_check_independent_of_parity() {
+        let mut settings = default_settings();
 
-        match stop_bits {
-            core::Stop1 => self.termios.c_cflag &= !CSTOPB,
-            core::Stop2 => self.termios.c_cflag |= CSTOPB,
-        };
+        settings.set_parity(core::ParityNone);
+        settings.set_parity_check(true);
+        assert!(settings.parity_check());
+        assert_eq!(settings.parity(), Some(core::ParityNone));
     }
 
-    fn set_flow_control(&mut self, flow_control: core::FlowControl) {
-        use libc::{IXON, IXOFF};
-        use libc::CRTSCTS;
+    #[test]
+    fn tty_settings_sets_input_flags() {
+        let mut settings = default_settings();
 
-        match flow_control {
-            core::FlowNone => {
-                self.termios.c_iflag &= !(IXON | IXOFF);
-                self.termios.c_cflag &= !CRTSCTS;
-            }
-            core::FlowSoftware => {
-                self.termios.c_iflag |= IXON | IXOFF;
-                self.termios.c_cflag &= !CRTSCTS;
-            }
-            core::FlowHardware => {
-                self.termios.c_iflag &= !(IXON | IXOFF);
-                self.termios.c_cflag |= CRTSCTS;
-            }
-        };
+        settings.set_strip_high_bit(true);
+        settings.set_restart_on_any_char(true);
+        assert!(settings.strip_high_bit());
+        assert!(settings.restart_on_any_char());
+
+        settings.set_strip_high_bit(false);
+        assert!(!settings.strip_high_bit());
+        assert!(settings.restart_on_any_char());
     }
-}
 
+    #[test]
+    fn tty_port_keeps_input_flags_after_reconfigure() {
+        let pty = open_pty();
+        let mut port = TTYPort::open(&pty.path).unwrap();
 
-#[cfg(test)]
-mod tests {
-    use core;
+        let mut settings = core::SerialDevice::read_settings(&port).unwrap();
+        assert!(settings.break_handling() != BreakHandling::Ignore);
 
-    use std::mem;
+        settings.set_break_handling(BreakHandling::Ignore);
+        core::SerialDevice::write_settings(&mut port, &settings).unwrap();
+        port.reconfigure(&|settings| settings.set_baud_rate(core::Baud9600)).unwrap();
 
-    use super::TTYSettings;
-    use core::prelude::*;
+        let settings = core::SerialDevice::read_settings(&port).unwrap();
+        assert_eq!(settings.break_handling(), BreakHandling::Ignore);
+    }
 
-    fn default_settings() -> TTYSettings {
-        TTYSettings { termios: unsafe { mem::uninitialized() } }
+    #[test]
+    fn tty_settings_sets_min_read_len() {
+        let mut settings = default_settings();
+
+        settings.set_min_read_len(64);
+        assert_eq!(settings.min_read_len(), 64);
+    }
+
+    #[test]
+    fn tty_settings_rounds_up_inter_char_time() {
+        let mut settings = default_settings();
+
+        settings.set_inter_char_time(Duration::from_millis(150)).unwrap();
+        assert_eq!(settings.inter_char_time(), Duration::from_millis(200));
+    }
+
+    #[test]
+    fn tty_settings_rejects_long_inter_char_time() {
+        let mut settings = default_settings();
+
+        let err = settings.set_inter_char_time(Duration::from_millis(25_600)).unwrap_err();
+        assert_eq!(err.kind(), core::ErrorKind::InvalidInput);
     }
 
     #[test]
@@ -686,6 +2381,32 @@ mod tests {
         assert_eq!(settings.flow_control(), Some(core::FlowHardware));
     }
 
+    #[test]
+    fn tty_settings_sets_flow_control_dtr_dsr() {
+        let mut settings = default_settings();
+
+        settings.set_flow_control(core::FlowHardware);
+        settings.set_flow_control(core::FlowDtrDsr);
+        assert_eq!(settings.flow_control(), Some(core::FlowDtrDsr));
+
+        settings.set_flow_control(core::FlowNone);
+        assert_eq!(settings.flow_control(), Some(core::FlowNone));
+    }
+
+    #[test]
+    fn tty_port_keeps_dtr_dsr_flow_control() {
+        let pty = open_pty();
+        let mut port = TTYPort::open(&pty.path).unwrap();
+
+        port.reconfigure(&|settings| {
+            settings.set_flow_control(core::FlowDtrDsr);
+            Ok(())
+        }).unwrap();
+
+        let settings = core::SerialDevice::read_settings(&port).unwrap();
+        assert_eq!(settings.flow_control(), Some(core::FlowDtrDsr));
+    }
+
     #[test]
     fn tty_settings_sets_flow_control_none() {
         let mut settings = default_settings();
@@ -22,25 +22,37 @@
 use core;
 use error;
 
+use std::cmp;
 use std::ffi::OsStr;
 use std::io;
 use std::mem;
+use std::path::{Path, PathBuf};
 use std::ptr;
-use std::time::Duration;
+use std::thread;
+use std::time::{Duration, Instant};
 
 use std::os::windows::prelude::*;
 
-use core::{SerialDevice, SerialPortSettings};
+use core::{SerialDevice, SerialPortSettings, Timeout};
 
 use libc::c_void;
 use ffi::*;
 
+// Shortest and longest intervals between checks of the output queue while waiting for transmission
+// to complete. Between them, the interval is the time the queued data takes to transmit.
+const TRANSMIT_POLL_MIN_INTERVAL_MS: u64 = 1;
+const TRANSMIT_POLL_MAX_INTERVAL_MS: u64 = 100;
+
 /// A serial port implementation for Windows COM ports.
 ///
 /// The port will be closed when the value is dropped.
 pub struct COMPort {
     handle: HANDLE,
-    timeout: Duration,
+    path: PathBuf,
+    read_timeout: Timeout,
+    write_timeout: Timeout,
+    inter_byte_timeout: Option<u32>,
+    char_time: Option<Duration>,
 }
 
 unsafe impl Send for COMPort {}
@@ -71,25 +83,55 @@ impl COMPort {
             CreateFileW(name.as_ptr(), GENERIC_READ | GENERIC_WRITE, 0, ptr::null_mut(), OPEN_EXISTING, FILE_ATTRIBUTE_NORMAL, 0 as HANDLE)
         };
 
-        let timeout = Duration::from_millis(100);
+        let timeout = Timeout::Duration(Duration::from_millis(100));
 
         if handle != INVALID_HANDLE_VALUE {
             let mut port = COMPort {
                 handle: handle,
-                timeout: timeout,
+                path: Path::new(port).to_path_buf(),
+                read_timeout: timeout,
+                write_timeout: timeout,
+                inter_byte_timeout: None,
+                char_time: None,
             };
 
             try!(port.set_timeout(timeout));
+            port.char_time = try!(port.read_settings()).char_time();
+
             Ok(port)
         }
         else {
-            Err(error::last_os_error())
+            Err(error::last_os_error().with_operation("CreateFileW").with_path(Path::new(port)))
         }
     }
 
+    fn set_comm_timeouts(&mut self, read_timeout: Timeout, write_timeout: Timeout) -> core::Result<()> {
+        let inter_byte_gap = match (self.inter_byte_timeout, self.char_time) {
+            (Some(chars), Some(char_time)) => Some(char_time * chars),
+            _ => None,
+        };
+
+        let timeouts = comm_timeouts(read_timeout, write_timeout, inter_byte_gap);
+
+        if unsafe { SetCommTimeouts(self.handle, &timeouts) } == 0 {
+            return Err(self.last_os_error("SetCommTimeouts"));
+        }
+
+        self.read_timeout = read_timeout;
+        self.write_timeout = write_timeout;
+        Ok(())
+    }
+
     fn escape_comm_function(&mut self, function: DWORD) -> core::Result<()> {
         match unsafe { EscapeCommFunction(self.handle, function) } {
-            0 => Err(error::last_os_error()),
+            0 => Err(self.last_os_error("EscapeCommFunction")),
+            _ => Ok(()),
+        }
+    }
+
+    fn transmit_comm_char(&mut self, c: libc::c_char) -> core::Result<()> {
+        match unsafe { TransmitCommChar(self.handle, c) } {
+            0 => Err(self.last_os_error("TransmitCommChar")),
             _ => Ok(()),
         }
     }
@@ -98,10 +140,15 @@ impl COMPort {
         let mut status: DWORD = unsafe { mem::uninitialized() };
 
         match unsafe { GetCommModemStatus(self.handle, &mut status) } {
-            0 => Err(error::last_os_error()),
+            0 => Err(self.last_os_error("GetCommModemStatus")),
             _ => Ok(status & pin != 0),
         }
     }
+
+    // Returns the last OS error, annotated with the failed operation and the port's name.
+    fn last_os_error(&self, operation: &str) -> core::Error {
+        error::last_os_error().with_operation(operation).with_path(&self.path)
+    }
 }
 
 impl Drop for COMPort {
@@ -120,19 +167,65 @@ impl AsRawHandle for COMPort {
     }
 }
 
+// Returns the COMMTIMEOUTS that implement the read, write and inter-byte timeouts.
+//
+// Setting ReadIntervalTimeout and ReadTotalTimeoutMultiplier to MAXDWORD makes ReadFile() return as
+// soon as any data has been received, or after ReadTotalTimeoutConstant if no data arrives. With a
+// constant of zero, ReadFile() returns immediately. An inter-byte timeout is implemented by
+// ReadIntervalTimeout instead, which ends a read once the line is idle, but then the total
+// timeout bounds the whole read rather than the wait for the first byte. Writes can't be made
+// non-blocking, so they wait for the shortest possible timeout instead.
+fn comm_timeouts(read_timeout: Timeout, write_timeout: Timeout, inter_byte_gap: Option<Duration>) -> COMMTIMEOUTS {
+    let (read_interval, read_multiplier, read_constant) = match (read_timeout, inter_byte_gap) {
+        (Timeout::NonBlocking, _) => (MAXDWORD, 0, 0),
+        (Timeout::Infinite, Some(gap)) => (milliseconds(gap), 0, 0),
+        (Timeout::Infinite, None) => (MAXDWORD, MAXDWORD, MAXDWORD - 1),
+        (Timeout::Duration(duration), Some(gap)) => (milliseconds(gap), 0, milliseconds(duration)),
+        (Timeout::Duration(duration), None) => (MAXDWORD, MAXDWORD, milliseconds(duration)),
+    };
+
+    let write_constant = match write_timeout {
+        Timeout::Infinite => 0,
+        Timeout::NonBlocking => 1,
+        Timeout::Duration(duration) => milliseconds(duration),
+    };
+
+    COMMTIMEOUTS {
+        ReadIntervalTimeout: read_interval,
+        ReadTotalTimeoutMultiplier: read_multiplier,
+        ReadTotalTimeoutConstant: read_constant,
+        WriteTotalTimeoutMultiplier: 0,
+        WriteTotalTimeoutConstant: write_constant,
+    }
+}
+
+// Converts a duration to a COMMTIMEOUTS constant, rounding up to at least one millisecond.
+fn milliseconds(duration: Duration) -> DWORD {
+    let milliseconds = duration.as_secs() * 1000 + (duration.subsec_nanos() as u64 + 999_999) / 1_000_000;
+    cmp::min(cmp::max(milliseconds, 1), MAXDWORD as u64 - 1) as DWORD
+}
+
 impl io::Read for COMPort {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        let mut len: DWORD = 0;
+        if buf.is_empty() {
+            return Ok(0);
+        }
 
-        match unsafe { ReadFile(self.handle, buf.as_mut_ptr() as *mut c_void, buf.len() as DWORD, &mut len, ptr::null_mut()) } {
-            0 => Err(io::Error::last_os_error()),
-            _ => {
-                if len != 0 {
-                    Ok(len as usize)
-                }
-                else {
-                    Err(io::Error::new(io::ErrorKind::TimedOut, "Operation timed out"))
-                }
+        loop {
+            let mut len: DWORD = 0;
+
+            if unsafe { ReadFile(self.handle, buf.as_mut_ptr() as *mut c_void, buf.len() as DWORD, &mut len, ptr::null_mut()) } == 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            if len != 0 {
+                return Ok(len as usize);
+            }
+
+            // an infinite timeout is implemented as the longest timeout supported by the driver, or
+            // without a total timeout when an inter-byte timeout is set
+            if self.read_timeout != Timeout::Infinite {
+                return Err(io::Error::new(self.read_timeout.error_kind(), "Operation timed out"));
             }
         }
     }
@@ -144,6 +237,7 @@ impl io::Write for COMPort {
 
         match unsafe { WriteFile(self.handle, buf.as_ptr() as *mut c_void, buf.len() as DWORD, &mut len, ptr::null_mut()) } {
             0 => Err(io::Error::last_os_error()),
+            _ if len == 0 && !buf.is_empty() => Err(io::Error::new(self.write_timeout.error_kind(), "Operation timed out")),
             _ => Ok(len as usize),
         }
     }
@@ -163,10 +257,9 @@ impl SerialDevice for COMPort {
         let mut dcb = DCB::new();
 
         match unsafe { GetCommState(self.handle, &mut dcb) } {
-            0 => Err(error::last_os_error()),
+            0 => Err(self.last_os_error("GetCommState")),
             _ => {
                 dcb.fBits |= fBinary;
-                dcb.fBits &= fDtrControl;
 
                 Ok(COMSettings { inner: dcb })
             }
@@ -175,32 +268,62 @@ impl SerialDevice for COMPort {
     }
 
     fn write_settings(&mut self, settings: &COMSettings) -> core::Result<()> {
-        match unsafe { SetCommState(self.handle, &settings.inner) } {
-            0 => Err(error::last_os_error()),
-            _ => Ok(()),
+        if unsafe { SetCommState(self.handle, &settings.inner) } == 0 {
+            return Err(self.last_os_error("SetCommState"));
         }
+
+        // the inter-byte timeout depends on the baud rate and character format
+        self.char_time = settings.char_time();
+
+        if self.inter_byte_timeout.is_some() {
+            let (read_timeout, write_timeout) = (self.read_timeout, self.write_timeout);
+            try!(self.set_comm_timeouts(read_timeout, write_timeout));
+        }
+
+        Ok(())
     }
 
-    fn timeout(&self) -> Duration {
-        self.timeout
+    fn read_timeout(&self) -> Timeout {
+        self.read_timeout
     }
 
-    fn set_timeout(&mut self, timeout: Duration) -> core::Result<()> {
-        let milliseconds = timeout.as_secs() * 1000 + timeout.subsec_nanos() as u64 / 1_000_000;
+    fn write_timeout(&self) -> Timeout {
+        self.write_timeout
+    }
 
-        let timeouts = COMMTIMEOUTS {
-            ReadIntervalTimeout: 0,
-            ReadTotalTimeoutMultiplier: 0,
-            ReadTotalTimeoutConstant: milliseconds as DWORD,
-            WriteTotalTimeoutMultiplier: 0,
-            WriteTotalTimeoutConstant: 0,
-        };
+    fn set_read_timeout(&mut self, timeout: Timeout) -> core::Result<()> {
+        let write_timeout = self.write_timeout;
+        self.set_comm_timeouts(timeout, write_timeout)
+    }
 
-        if unsafe { SetCommTimeouts(self.handle, &timeouts) } == 0 {
-            return Err(error::last_os_error());
+    fn set_write_timeout(&mut self, timeout: Timeout) -> core::Result<()> {
+        let read_timeout = self.read_timeout;
+        self.set_comm_timeouts(read_timeout, timeout)
+    }
+
+    fn set_timeout(&mut self, timeout: Timeout) -> core::Result<()> {
+        self.set_comm_timeouts(timeout, timeout)
+    }
+
+    fn inter_byte_timeout(&self) -> Option<u32> {
+        self.inter_byte_timeout
+    }
+
+    fn set_inter_byte_timeout(&mut self, chars: Option<u32>) -> core::Result<()> {
+        if chars == Some(0) {
+            return Err(core::Error::new(core::ErrorKind::InvalidInput, "Inter-byte timeout must be at least one character time"));
+        }
+
+        let previous = self.inter_byte_timeout;
+        self.inter_byte_timeout = chars;
+
+        let (read_timeout, write_timeout) = (self.read_timeout, self.write_timeout);
+
+        if let Err(e) = self.set_comm_timeouts(read_timeout, write_timeout) {
+            self.inter_byte_timeout = previous;
+            return Err(e);
         }
 
-        self.timeout = timeout;
         Ok(())
     }
 
@@ -237,6 +360,81 @@ impl SerialDevice for COMPort {
     fn read_cd(&mut self) -> core::Result<bool> {
         self.read_pin(MS_RLSD_ON)
     }
+
+    fn set_break(&mut self, enabled: bool) -> core::Result<()> {
+        if enabled {
+            self.escape_comm_function(SETBREAK)
+        }
+        else {
+            self.escape_comm_function(CLRBREAK)
+        }
+    }
+
+    fn suspend_output(&mut self) -> core::Result<()> {
+        self.escape_comm_function(SETXOFF)
+    }
+
+    fn resume_output(&mut self) -> core::Result<()> {
+        self.escape_comm_function(SETXON)
+    }
+
+    fn send_xoff(&mut self) -> core::Result<()> {
+        let settings = try!(self.read_settings());
+        self.transmit_comm_char(settings.inner.XoffChar)
+    }
+
+    fn send_xon(&mut self) -> core::Result<()> {
+        let settings = try!(self.read_settings());
+        self.transmit_comm_char(settings.inner.XonChar)
+    }
+
+    fn is_output_stopped(&mut self) -> core::Result<bool> {
+        let mut errors: DWORD = 0;
+        let mut status: COMSTAT = unsafe { mem::zeroed() };
+
+        // this also clears the device's error flags, which aren't used otherwise
+        match unsafe { ClearCommError(self.handle, &mut errors, &mut status) } {
+            0 => Err(self.last_os_error("ClearCommError")),
+            _ => Ok(status.fBits & fXoffHold != 0),
+        }
+    }
+
+    fn wait_transmit_complete(&mut self, timeout: Timeout) -> core::Result<()> {
+        let deadline = timeout.deadline();
+        let char_time = try!(SerialDevice::read_settings(self)).char_time();
+
+        loop {
+            let mut errors: DWORD = 0;
+            let mut status: COMSTAT = unsafe { mem::zeroed() };
+
+            if unsafe { ClearCommError(self.handle, &mut errors, &mut status) } == 0 {
+                return Err(self.last_os_error("ClearCommError"));
+            }
+
+            if status.cbOutQue == 0 {
+                return Ok(());
+            }
+
+            let now = Instant::now();
+
+            if deadline.map(|deadline| now >= deadline) == Some(true) {
+                return Err(core::Error::new(core::ErrorKind::Io(timeout.error_kind()), "Timed out waiting for transmission")
+                               .with_path(&self.path));
+            }
+
+            // sleep for about as long as the queued data takes to transmit
+            let min_interval = Duration::from_millis(TRANSMIT_POLL_MIN_INTERVAL_MS);
+            let max_interval = Duration::from_millis(TRANSMIT_POLL_MAX_INTERVAL_MS);
+            let interval = char_time.map(|char_time| char_time * status.cbOutQue).unwrap_or(min_interval);
+            let mut sleep = cmp::max(min_interval, cmp::min(max_interval, interval));
+
+            if let Some(deadline) = deadline {
+                sleep = cmp::min(sleep, deadline - now);
+            }
+
+            thread::sleep(sleep);
+        }
+    }
 }
 
 
@@ -296,7 +494,10 @@ impl SerialPortSettings for COMSettings {
     }
 
     fn flow_control(&self) -> Option<core::FlowControl> {
-        if self.inner.fBits & (fOutxCtsFlow | fRtsControl) != 0 {
+        if self.inner.fBits & fOutxDsrFlow != 0 {
+            Some(core::FlowDtrDsr)
+        }
+        else if self.inner.fBits & (fOutxCtsFlow | fRtsControl) != 0 {
             Some(core::FlowHardware)
         }
         else if self.inner.fBits & (fOutX | fInX) != 0 {
@@ -358,6 +559,14 @@ impl SerialPortSettings for COMSettings {
     }
 
     fn set_flow_control(&mut self, flow_control: core::FlowControl) {
+        // keep DTR asserted when leaving DTR/DSR flow control
+        if self.inner.fBits & fDtrControl == fDtrControlHandshake {
+            self.inner.fBits &= !fDtrControl;
+            self.inner.fBits |= fDtrControlEnable;
+        }
+
+        self.inner.fBits &= !fOutxDsrFlow;
+
         match flow_control {
             core::FlowNone => {
                 self.inner.fBits &= !(fOutxCtsFlow | fRtsControl);
@@ -371,6 +580,95 @@ impl SerialPortSettings for COMSettings {
                 self.inner.fBits |= fOutxCtsFlow | fRtsControl;
                 self.inner.fBits &= !(fOutX | fInX);
             }
+            core::FlowDtrDsr => {
+                self.inner.fBits &= !(fOutxCtsFlow | fRtsControl);
+                self.inner.fBits &= !(fOutX | fInX);
+                self.inner.fBits &= !fDtrControl;
+                self.inner.fBits |= fOutxDsrFlow | fDtrControlHandshake;
+            }
+        }
+    }
+
+    fn xon_xoff_chars(&self) -> (u8, u8) {
+        (self.inner.XonChar as u8, self.inner.XoffChar as u8)
+    }
+
+    fn set_xon_xoff_chars(&mut self, xon: u8, xoff: u8) -> core::Result<()> {
+        if xon == xoff {
+            return Err(core::Error::new(core::ErrorKind::InvalidInput, "XON and XOFF characters must differ"));
+        }
+
+        self.inner.XonChar = xon as libc::c_char;
+        self.inner.XoffChar = xoff as libc::c_char;
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use core;
+
+    use std::env;
+    use std::io::prelude::*;
+    use std::time::Duration;
+
+    use core::Timeout;
+    use ffi::MAXDWORD;
+
+    use super::COMPort;
+
+    #[test]
+    fn comm_timeouts_maps_read_timeouts() {
+        let ms = Duration::from_millis;
+
+        let cases = [
+            (Timeout::NonBlocking, None, (MAXDWORD, 0, 0)),
+            (Timeout::NonBlocking, Some(ms(2)), (MAXDWORD, 0, 0)),
+            (Timeout::Infinite, None, (MAXDWORD, MAXDWORD, MAXDWORD - 1)),
+            (Timeout::Infinite, Some(ms(2)), (2, 0, 0)),
+            (Timeout::Duration(ms(100)), None, (MAXDWORD, MAXDWORD, 100)),
+            (Timeout::Duration(ms(100)), Some(ms(2)), (2, 0, 100)),
+            (Timeout::Duration(Duration::new(0, 1)), None, (MAXDWORD, MAXDWORD, 1)),
+            (Timeout::Duration(Duration::from_secs(1 << 40)), None, (MAXDWORD, MAXDWORD, MAXDWORD - 1)),
+        ];
+
+        for &(read_timeout, inter_byte_gap, expected) in cases.iter() {
+            let timeouts = super::comm_timeouts(read_timeout, Timeout::Infinite, inter_byte_gap);
+            let actual = (timeouts.ReadIntervalTimeout, timeouts.ReadTotalTimeoutMultiplier, timeouts.ReadTotalTimeoutConstant);
+
+            assert_eq!(actual, expected, "read timeout {:?} with gap {:?}", read_timeout, inter_byte_gap);
         }
     }
+
+    #[test]
+    fn comm_timeouts_maps_write_timeouts() {
+        let cases = [
+            (Timeout::Infinite, 0),
+            (Timeout::NonBlocking, 1),
+            (Timeout::Duration(Duration::from_millis(250)), 250),
+            (Timeout::Duration(Duration::from_micros(1500)), 2),
+        ];
+
+        for &(write_timeout, expected) in cases.iter() {
+            let timeouts = super::comm_timeouts(Timeout::Infinite, write_timeout, None);
+
+            assert_eq!(timeouts.WriteTotalTimeoutMultiplier, 0);
+            assert_eq!(timeouts.WriteTotalTimeoutConstant, expected, "write timeout {:?}", write_timeout);
+        }
+    }
+
+    // Requires two COM ports connected by a null modem cable, named by the SERIAL_TEST_PORTS
+    // environment variable, e.g., `COM3,COM4`.
+    #[test]
+    #[ignore]
+    fn com_port_follows_timeout_contract() {
+        let names = env::var("SERIAL_TEST_PORTS").expect("SERIAL_TEST_PORTS is not set");
+        let mut names = names.split(',');
+
+        let mut port = COMPort::open(names.next().unwrap()).unwrap();
+        let mut peer = COMPort::open(names.next().expect("SERIAL_TEST_PORTS names one port")).unwrap();
+
+        core::testing::check_timeout_contract(&mut port, move |data| peer.write_all(data).unwrap());
+    }
 }
@@ -41,7 +41,7 @@ pub fn last_os_error() -> core::Error {
         _ => core::ErrorKind::Io(io::ErrorKind::Other),
     };
 
-    core::Error::new(kind, error_string(errno).trim())
+    core::Error::new(kind, error_string(errno).trim()).with_source(io::Error::from_raw_os_error(errno))
 }
 
 // the rest of this module is borrowed from libstd
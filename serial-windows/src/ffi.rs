@@ -44,6 +44,7 @@ pub const GENERIC_WRITE: DWORD = 0x40000000;
 pub const OPEN_EXISTING: DWORD = 3;
 pub const FILE_ATTRIBUTE_NORMAL: DWORD = 0x80;
 pub const INVALID_HANDLE_VALUE: HANDLE = !0 as HANDLE;
+pub const MAXDWORD: DWORD = 0xFFFFFFFF;
 
 #[repr(C)]
 pub struct SECURITY_ATTRIBUTES {
@@ -118,6 +119,10 @@ pub const fRtsControl:       DWORD = 0x00003000;
 pub const fAbortOnError:     DWORD = 0x00004000;
 pub const fDummy2:           DWORD = 0xFFFF8000;
 
+// fDtrControl values
+pub const fDtrControlEnable:    DWORD = 0x00000010;
+pub const fDtrControlHandshake: DWORD = 0x00000020;
+
 // Parity values
 pub const NOPARITY:    BYTE = 0;
 pub const ODDPARITY:   BYTE = 1;
@@ -154,6 +159,23 @@ pub const MS_DSR_ON:  DWORD = 0x0020;
 pub const MS_RING_ON: DWORD = 0x0040;
 pub const MS_RLSD_ON: DWORD = 0x0080;
 
+// COMSTAT fBits masks
+pub const fCtsHold:  DWORD = 0x00000001;
+pub const fDsrHold:  DWORD = 0x00000002;
+pub const fRlsdHold: DWORD = 0x00000004;
+pub const fXoffHold: DWORD = 0x00000008;
+pub const fXoffSent: DWORD = 0x00000010;
+pub const fEof:      DWORD = 0x00000020;
+pub const fTxim:     DWORD = 0x00000040;
+
+#[derive(Copy,Clone,Debug)]
+#[repr(C)]
+pub struct COMSTAT {
+    pub fBits: DWORD,
+    pub cbInQue: DWORD,
+    pub cbOutQue: DWORD,
+}
+
 #[derive(Copy,Clone,Debug)]
 #[repr(C)]
 pub struct COMMTIMEOUTS {
@@ -194,6 +216,8 @@ extern "system" {
     pub fn SetCommTimeouts(hFile: HANDLE, lpCommTimeouts: *const COMMTIMEOUTS) -> BOOL;
     pub fn EscapeCommFunction(hFile: HANDLE, dwFunc: DWORD) -> BOOL;
     pub fn GetCommModemStatus(hFile: HANDLE, lpModemStat: *mut DWORD) -> BOOL;
+    pub fn TransmitCommChar(hFile: HANDLE, cChar: c_char) -> BOOL;
+    pub fn ClearCommError(hFile: HANDLE, lpErrors: LPDWORD, lpStat: *mut COMSTAT) -> BOOL;
 
     pub fn GetLastError() -> DWORD;
 }
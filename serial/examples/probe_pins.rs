@@ -45,7 +45,7 @@ fn main() {
 
 fn probe_pins<T: SerialPort>(port: &mut T) -> serial::Result<()> {
     try!(port.configure(&SETTINGS));
-    try!(port.set_timeout(Duration::from_millis(100)));
+    try!(port.set_timeout(serial::Timeout::Duration(Duration::from_millis(100))));
 
     try!(port.set_rts(false));
     try!(port.set_dtr(false));
@@ -22,9 +22,8 @@
 extern crate serial;
 
 use std::env;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-use std::io::prelude::*;
 use serial::prelude::*;
 
 const SETTINGS: serial::PortSettings = serial::PortSettings {
@@ -46,15 +45,15 @@ fn main() {
 
 fn interact<T: SerialPort>(port: &mut T) -> serial::Result<()> {
     try!(port.configure(&SETTINGS));
-    try!(port.set_timeout(Duration::from_secs(1)));
+    try!(port.set_timeout(serial::Timeout::Duration(Duration::from_secs(1))));
 
     let mut buf: Vec<u8> = (0..255).collect();
 
     println!("writing bytes");
-    try!(port.write(&buf[..]));
+    try!(port.write_all_deadline(&buf[..], Instant::now() + Duration::from_secs(1)));
 
     println!("reading bytes");
-    try!(port.read(&mut buf[..]));
+    try!(port.read_exact_deadline(&mut buf[..], Instant::now() + Duration::from_secs(1)));
 
     Ok(())
 }
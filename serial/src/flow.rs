@@ -0,0 +1,603 @@
+// Copyright (c) 2015 David Cuddeback
+//
+// Permission is hereby granted, free of charge, to any person obtaining
+// a copy of this software and associated documentation files (the
+// "Software"), to deal in the Software without restriction, including
+// without limitation the rights to use, copy, modify, merge, publish,
+// distribute, sublicense, and/or sell copies of the Software, and to
+// permit persons to whom the Software is furnished to do so, subject to
+// the following conditions:
+//
+// The above copyright notice and this permission notice shall be
+// included in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND,
+// EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF
+// MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE
+// LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION
+// WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+use core;
+
+use std::cmp;
+use std::collections::VecDeque;
+use std::io;
+use std::time::Instant;
+
+use core::{SerialPort, SerialPortSettings, PortSettings, Timeout};
+
+const DEFAULT_CHUNK_LEN: usize = 64;
+
+/// A serial port with software flow control implemented in userspace.
+///
+/// `SoftwareFlowControl` honors XON/XOFF characters received from the device for ports whose
+/// driver doesn't implement software flow control, such as pseudoterminals, virtual ports and
+/// ports backed by a network connection. The port itself should be configured with `FlowNone`.
+///
+/// Received XON and XOFF characters are removed from the data returned by reads. After an XOFF,
+/// writes wait until an XON is received, up to the port's write timeout. To notice an XOFF while
+/// writing, each write checks for received characters with a non-blocking read and writes at most
+/// 64 bytes, which can be changed with [`set_chunk_len()`](#method.set_chunk_len). Data received
+/// while writing is kept for the next read. `write_all()` switches the port's read timeout to
+/// non-blocking once for the whole buffer rather than for each chunk, which is cheaper on ports
+/// where changing the timeout is slow, such as on Windows.
+///
+/// Only output is flow controlled. XON and XOFF characters in written data are sent as they are.
+///
+/// ```no_run
+/// use std::io::prelude::*;
+///
+/// let port = serial::open("/dev/ttyUSB0").unwrap();
+/// let mut port = serial::SoftwareFlowControl::new(port);
+///
+/// port.write_all(b"a long print job").unwrap();
+/// ```
+pub struct SoftwareFlowControl<P: SerialPort> {
+    port: P,
+    xon: u8,
+    xoff: u8,
+    stopped: bool,
+    input: VecDeque<u8>,
+    chunk_len: usize,
+}
+
+impl<P: SerialPort> SoftwareFlowControl<P> {
+    /// Wraps a port using the standard XON and XOFF characters.
+    pub fn new(port: P) -> Self {
+        SoftwareFlowControl {
+            port: port,
+            xon: core::XON,
+            xoff: core::XOFF,
+            stopped: false,
+            input: VecDeque::new(),
+            chunk_len: DEFAULT_CHUNK_LEN,
+        }
+    }
+
+    /// Wraps a port using custom XON and XOFF characters.
+    ///
+    /// ## Errors
+    ///
+    /// This function returns an `InvalidInput` error if `xon` and `xoff` are the same character.
+    pub fn with_chars(port: P, xon: u8, xoff: u8) -> core::Result<Self> {
+        if xon == xoff {
+            return Err(core::Error::new(core::ErrorKind::InvalidInput, "XON and XOFF characters must differ"));
+        }
+
+        let mut flow = SoftwareFlowControl::new(port);
+        flow.xon = xon;
+        flow.xoff = xoff;
+        Ok(flow)
+    }
+
+    /// Returns the characters used for flow control as a tuple of `(xon, xoff)`.
+    pub fn xon_xoff_chars(&self) -> (u8, u8) {
+        (self.xon, self.xoff)
+    }
+
+    /// Sets the maximum number of bytes written by each write.
+    ///
+    /// Smaller chunks stop output sooner after an XOFF is received, at the cost of more reads.
+    ///
+    /// ## Panics
+    ///
+    /// This function panics if `len` is zero.
+    pub fn set_chunk_len(&mut self, len: usize) {
+        assert!(len > 0, "chunk length must be greater than zero");
+        self.chunk_len = len;
+    }
+
+    /// Returns a reference to the underlying port.
+    pub fn get_ref(&self) -> &P {
+        &self.port
+    }
+
+    /// Returns a mutable reference to the underlying port.
+    ///
+    /// Flow control characters read directly from the underlying port are not processed.
+    pub fn get_mut(&mut self) -> &mut P {
+        &mut self.port
+    }
+
+    /// Returns the underlying port. Received data that hasn't been read is discarded.
+    pub fn into_inner(self) -> P {
+        self.port
+    }
+
+    // Removes flow control characters from `buf` and returns the number of remaining bytes, which
+    // are moved to the start of `buf`.
+    fn filter(&mut self, buf: &mut [u8]) -> usize {
+        let mut len = 0;
+
+        for i in 0..buf.len() {
+            let byte = buf[i];
+
+            if byte == self.xoff {
+                self.stopped = true;
+            }
+            else if byte == self.xon {
+                self.stopped = false;
+            }
+            else {
+                buf[len] = byte;
+                len += 1;
+            }
+        }
+
+        len
+    }
+
+    // Reads from the port with the given timeout, keeping received data for later reads.
+    fn receive(&mut self, timeout: Timeout) -> io::Result<()> {
+        let mut buf = [0u8; 256];
+
+        let len = match self.read_timed(&mut buf, timeout) {
+            Ok(len) => len,
+            Err(ref e) if is_timeout(e) => 0,
+            Err(e) => return Err(e),
+        };
+
+        let len = self.filter(&mut buf[..len]);
+        self.input.extend(&buf[..len]);
+        Ok(())
+    }
+
+    // Reads from the port with the given timeout. The port's read timeout is only changed, and
+    // restored afterwards, if it differs from `timeout`.
+    fn read_timed(&mut self, buf: &mut [u8], timeout: Timeout) -> io::Result<usize> {
+        let saved = self.port.read_timeout();
+
+        if saved == timeout {
+            return self.port.read(buf);
+        }
+
+        try!(self.port.set_read_timeout(timeout));
+        let result = self.port.read(buf);
+        let restored = self.port.set_read_timeout(saved);
+
+        let len = try!(result);
+        try!(restored);
+        Ok(len)
+    }
+
+    fn wait_for_xon(&mut self) -> io::Result<()> {
+        let timeout = self.port.write_timeout();
+        let deadline = timeout.deadline();
+
+        while self.stopped {
+            let remaining = match deadline {
+                Some(deadline) => {
+                    let now = Instant::now();
+
+                    if now >= deadline {
+                        return Err(io::Error::new(timeout.error_kind(), "Output stopped by XOFF"));
+                    }
+
+                    Timeout::Duration(deadline - now)
+                }
+                None => Timeout::Infinite,
+            };
+
+            try!(self.receive(remaining));
+        }
+
+        Ok(())
+    }
+}
+
+fn is_timeout(error: &io::Error) -> bool {
+    error.kind() == io::ErrorKind::TimedOut || error.kind() == io::ErrorKind::WouldBlock
+}
+
+impl<P: SerialPort> io::Read for SoftwareFlowControl<P> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        if !self.input.is_empty() {
+            let len = cmp::min(buf.len(), self.input.len());
+
+            for (dst, src) in buf.iter_mut().zip(self.input.drain(..len)) {
+                *dst = src;
+            }
+
+            return Ok(len);
+        }
+
+        let timeout = self.port.read_timeout();
+        let deadline = timeout.deadline();
+
+        let mut len = try!(self.port.read(buf));
+
+        // a read that only receives flow control characters waits for more data, for the rest of
+        // the read timeout
+        loop {
+            if len == 0 {
+                return Ok(0);
+            }
+
+            len = self.filter(&mut buf[..len]);
+
+            if len > 0 {
+                return Ok(len);
+            }
+
+            let remaining = match deadline {
+                Some(deadline) => {
+                    let now = Instant::now();
+
+                    if now >= deadline {
+                        return Err(io::Error::new(timeout.error_kind(), "Operation timed out"));
+                    }
+
+                    Timeout::Duration(deadline - now)
+                }
+                None => Timeout::Infinite,
+            };
+
+            len = try!(self.read_timed(buf, remaining));
+        }
+    }
+}
+
+impl<P: SerialPort> io::Write for SoftwareFlowControl<P> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        try!(self.receive(Timeout::NonBlocking));
+
+        if self.stopped {
+            try!(self.wait_for_xon());
+        }
+
+        let len = cmp::min(buf.len(), self.chunk_len);
+        self.port.write(&buf[..len])
+    }
+
+    fn write_all(&mut self, mut buf: &[u8]) -> io::Result<()> {
+        let saved = self.port.read_timeout();
+
+        // keeps each chunk's check for received characters from changing the read timeout
+        if saved != Timeout::NonBlocking {
+            try!(self.port.set_read_timeout(Timeout::NonBlocking));
+        }
+
+        let mut result = Ok(());
+
+        while !buf.is_empty() {
+            match self.write(buf) {
+                Ok(0) => {
+                    result = Err(io::Error::new(io::ErrorKind::WriteZero, "Failed to write whole buffer"));
+                    break;
+                }
+                Ok(n) => buf = &buf[n..],
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => (),
+                Err(e) => {
+                    result = Err(e);
+                    break;
+                }
+            }
+        }
+
+        let restored = if saved != Timeout::NonBlocking {
+            self.port.set_read_timeout(saved)
+        }
+        else {
+            Ok(())
+        };
+
+        try!(result);
+        try!(restored);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.port.flush()
+    }
+}
+
+impl<P: SerialPort> SerialPort for SoftwareFlowControl<P> {
+    fn read_timeout(&self) -> Timeout {
+        self.port.read_timeout()
+    }
+
+    fn write_timeout(&self) -> Timeout {
+        self.port.write_timeout()
+    }
+
+    fn set_read_timeout(&mut self, timeout: Timeout) -> core::Result<()> {
+        self.port.set_read_timeout(timeout)
+    }
+
+    fn set_write_timeout(&mut self, timeout: Timeout) -> core::Result<()> {
+        self.port.set_write_timeout(timeout)
+    }
+
+    fn inter_byte_timeout(&self) -> Option<u32> {
+        self.port.inter_byte_timeout()
+    }
+
+    fn set_inter_byte_timeout(&mut self, chars: Option<u32>) -> core::Result<()> {
+        self.port.set_inter_byte_timeout(chars)
+    }
+
+    fn configure(&mut self, settings: &PortSettings) -> core::Result<()> {
+        self.port.configure(settings)
+    }
+
+    fn reconfigure(&mut self, setup: &Fn(&mut SerialPortSettings) -> core::Result<()>) -> core::Result<()> {
+        self.port.reconfigure(setup)
+    }
+
+    fn configure_verified(&mut self, settings: &PortSettings) -> core::Result<()> {
+        self.port.configure_verified(settings)
+    }
+
+    fn settings<'a>(&'a self) -> core::Result<Box<SerialPortSettings + 'a>> {
+        self.port.settings()
+    }
+
+    fn set_rts(&mut self, level: bool) -> core::Result<()> {
+        self.port.set_rts(level)
+    }
+
+    fn set_dtr(&mut self, level: bool) -> core::Result<()> {
+        self.port.set_dtr(level)
+    }
+
+    fn read_cts(&mut self) -> core::Result<bool> {
+        self.port.read_cts()
+    }
+
+    fn read_dsr(&mut self) -> core::Result<bool> {
+        self.port.read_dsr()
+    }
+
+    fn read_ri(&mut self) -> core::Result<bool> {
+        self.port.read_ri()
+    }
+
+    fn read_cd(&mut self) -> core::Result<bool> {
+        self.port.read_cd()
+    }
+
+    fn set_modem_control(&mut self, control: core::ModemControl) -> core::Result<()> {
+        self.port.set_modem_control(control)
+    }
+
+    fn read_dtr(&mut self) -> core::Result<bool> {
+        self.port.read_dtr()
+    }
+
+    fn read_rts(&mut self) -> core::Result<bool> {
+        self.port.read_rts()
+    }
+
+    fn set_break(&mut self, enabled: bool) -> core::Result<()> {
+        self.port.set_break(enabled)
+    }
+
+    fn suspend_output(&mut self) -> core::Result<()> {
+        self.port.suspend_output()
+    }
+
+    fn resume_output(&mut self) -> core::Result<()> {
+        self.port.resume_output()
+    }
+
+    fn send_xoff(&mut self) -> core::Result<()> {
+        let xoff = self.xoff;
+        try!(io::Write::write_all(&mut self.port, &[xoff]));
+        Ok(())
+    }
+
+    fn send_xon(&mut self) -> core::Result<()> {
+        let xon = self.xon;
+        try!(io::Write::write_all(&mut self.port, &[xon]));
+        Ok(())
+    }
+
+    fn is_output_stopped(&mut self) -> core::Result<bool> {
+        Ok(self.stopped)
+    }
+
+    fn wait_transmit_complete(&mut self, timeout: Timeout) -> core::Result<()> {
+        self.port.wait_transmit_complete(timeout)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use core;
+
+    use std::io;
+    use std::io::prelude::*;
+    use std::time::Duration;
+
+    use core::prelude::*;
+    use core::Timeout;
+
+    use testing::{Event, MockPort};
+
+    use super::SoftwareFlowControl;
+
+    #[test]
+    fn read_removes_flow_control_chars() {
+        let mut port = SoftwareFlowControl::new(MockPort::new(&[b"a\x13b\x11c"]));
+        let mut buf = [0u8; 8];
+
+        assert_eq!(port.read(&mut buf).unwrap(), 3);
+        assert_eq!(&buf[..3], b"abc");
+        assert!(!port.is_output_stopped().unwrap());
+    }
+
+    #[test]
+    fn read_waits_past_flow_control_chars() {
+        let mut port = SoftwareFlowControl::new(MockPort::new(&[b"\x13", b"x"]));
+        let mut buf = [0u8; 8];
+
+        assert_eq!(port.read(&mut buf).unwrap(), 1);
+        assert_eq!(buf[0], b'x');
+        assert!(port.is_output_stopped().unwrap());
+    }
+
+    #[test]
+    fn read_waits_past_flow_control_chars_for_rest_of_timeout() {
+        let mut port = SoftwareFlowControl::new(MockPort::new(&[b"\x13", b"x"]));
+        let mut buf = [0u8; 8];
+
+        assert_eq!(port.read(&mut buf).unwrap(), 1);
+
+        let events = &port.get_ref().events;
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[1], Event::ReadTimeout(Timeout::Duration(Duration::from_millis(100))));
+
+        match events[0] {
+            Event::ReadTimeout(Timeout::Duration(remaining)) => assert!(remaining < Duration::from_millis(100)),
+            ref event => panic!("unexpected event {:?}", event),
+        }
+    }
+
+    #[test]
+    fn read_would_block_after_flow_control_chars_without_timeout() {
+        let mut port = SoftwareFlowControl::new(MockPort::new(&[b"\x13", b"x"]));
+        port.set_read_timeout(Timeout::NonBlocking).unwrap();
+
+        assert_eq!(port.read(&mut [0u8; 8]).unwrap_err().kind(), io::ErrorKind::WouldBlock);
+        assert!(port.is_output_stopped().unwrap());
+    }
+
+    #[test]
+    fn write_keeps_non_blocking_read_timeout() {
+        let mut port = SoftwareFlowControl::new(MockPort::new(&[]));
+        port.set_read_timeout(Timeout::NonBlocking).unwrap();
+        port.get_mut().events.clear();
+
+        assert_eq!(port.write(b"hi").unwrap(), 2);
+        assert_eq!(port.get_ref().events, vec![Event::Write(b"hi".to_vec())]);
+    }
+
+    #[test]
+    fn write_all_sets_non_blocking_read_timeout_once() {
+        let mut port = SoftwareFlowControl::new(MockPort::new(&[]));
+        port.set_chunk_len(2);
+
+        port.write_all(b"abcdef").unwrap();
+        assert_eq!(port.get_ref().events, vec![
+            Event::ReadTimeout(Timeout::NonBlocking),
+            Event::Write(b"ab".to_vec()),
+            Event::Write(b"cd".to_vec()),
+            Event::Write(b"ef".to_vec()),
+            Event::ReadTimeout(Timeout::Duration(Duration::from_millis(100))),
+        ]);
+    }
+
+    #[test]
+    fn write_all_waits_for_xon_between_chunks() {
+        let mut port = SoftwareFlowControl::new(MockPort::new(&[b"\x13", b"d\x11"]));
+        port.set_chunk_len(2);
+
+        port.write_all(b"abcd").unwrap();
+        assert_eq!(port.get_ref().output, b"abcd");
+        assert_eq!(port.read_timeout(), Timeout::Duration(Duration::from_millis(100)));
+    }
+
+    #[test]
+    fn write_all_restores_read_timeout_after_error() {
+        let mut port = SoftwareFlowControl::new(MockPort::new(&[b"\x13"]));
+        port.set_write_timeout(Timeout::Duration(Duration::from_millis(10))).unwrap();
+
+        assert_eq!(port.write_all(b"hi").unwrap_err().kind(), io::ErrorKind::TimedOut);
+        assert_eq!(port.read_timeout(), Timeout::Duration(Duration::from_millis(100)));
+    }
+
+    #[test]
+    fn write_waits_for_xon() {
+        let mut port = SoftwareFlowControl::new(MockPort::new(&[b"\x13", b"d\x11"]));
+        let mut buf = [0u8; 8];
+
+        assert_eq!(port.write(b"hi").unwrap(), 2);
+        assert_eq!(port.get_ref().output, b"hi");
+
+        // data received while waiting is kept for reads
+        assert_eq!(port.read(&mut buf).unwrap(), 1);
+        assert_eq!(buf[0], b'd');
+    }
+
+    #[test]
+    fn write_times_out_while_stopped() {
+        let mut port = SoftwareFlowControl::new(MockPort::new(&[b"\x13"]));
+        port.set_write_timeout(Timeout::Duration(Duration::from_millis(10))).unwrap();
+
+        assert_eq!(port.write(b"hi").unwrap_err().kind(), io::ErrorKind::TimedOut);
+        assert!(port.get_ref().output.is_empty());
+        assert_eq!(port.read_timeout(), Timeout::Duration(Duration::from_millis(100)));
+    }
+
+    #[test]
+    fn write_would_block_while_stopped_without_timeout() {
+        let mut port = SoftwareFlowControl::new(MockPort::new(&[b"\x13"]));
+        port.set_write_timeout(Timeout::NonBlocking).unwrap();
+
+        assert_eq!(port.write(b"hi").unwrap_err().kind(), io::ErrorKind::WouldBlock);
+    }
+
+    #[test]
+    fn write_splits_data_into_chunks() {
+        let mut port = SoftwareFlowControl::new(MockPort::new(&[]));
+        port.set_chunk_len(2);
+
+        assert_eq!(port.write(b"abcd").unwrap(), 2);
+        assert_eq!(port.get_ref().output, b"ab");
+    }
+
+    #[test]
+    fn with_chars_uses_custom_chars() {
+        let mut port = SoftwareFlowControl::with_chars(MockPort::new(&[b"a\x13b!"]), b'!', b'?').unwrap();
+        let mut buf = [0u8; 8];
+
+        assert_eq!(port.read(&mut buf).unwrap(), 3);
+        assert_eq!(&buf[..3], b"a\x13b");
+    }
+
+    #[test]
+    fn send_xoff_writes_custom_char() {
+        let mut port = SoftwareFlowControl::with_chars(MockPort::new(&[]), b'!', b'?').unwrap();
+
+        port.send_xoff().unwrap();
+        port.send_xon().unwrap();
+        assert_eq!(port.get_ref().output, b"?!");
+    }
+
+    #[test]
+    fn with_chars_rejects_equal_chars() {
+        let err = SoftwareFlowControl::with_chars(MockPort::new(&[]), b'!', b'!').err().unwrap();
+        assert_eq!(err.kind(), core::ErrorKind::InvalidInput);
+    }
+}
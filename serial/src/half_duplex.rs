@@ -0,0 +1,497 @@
+// Copyright (c) 2015 David Cuddeback
+//
+// Permission is hereby granted, free of charge, to any person obtaining
+// a copy of this software and associated documentation files (the
+// "Software"), to deal in the Software without restriction, including
+// without limitation the rights to use, copy, modify, merge, publish,
+// distribute, sublicense, and/or sell copies of the Software, and to
+// permit persons to whom the Software is furnished to do so, subject to
+// the following conditions:
+//
+// The above copyright notice and this permission notice shall be
+// included in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND,
+// EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF
+// MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE
+// LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION
+// WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+
+use core;
+
+use std::cell::Cell;
+use std::cmp;
+use std::io;
+use std::thread;
+use std::time::Duration;
+
+use core::{SerialPort, SerialPortSettings, PortSettings, Timeout};
+
+// Allowance for the latency of drivers and USB adapters when waiting for written data to be
+// transmitted, which is 16 ms by default for FTDI adapters.
+const TRANSMIT_MARGIN_MS: u64 = 50;
+
+/// A control signal that enables an RS-485 transceiver's driver.
+#[derive(Debug,Copy,Clone,PartialEq,Eq)]
+pub enum DirectionLine {
+    /// The transceiver is controlled by RTS.
+    Rts,
+
+    /// The transceiver is controlled by DTR.
+    Dtr,
+}
+
+/// A serial port that controls the direction of a half-duplex RS-485 transceiver in userspace.
+///
+/// `HalfDuplexPort` is for adapters whose UART doesn't switch the transceiver by itself. Each write
+/// asserts the direction line (RTS by default), waits for a number of character times, writes the
+/// data, waits until the port has transmitted it with `wait_transmit_complete()` or, if the port
+/// doesn't support that, `flush()`, waits for a guard time and then releases the line. The delays
+/// are set in character times with [`set_delays()`](#method.set_delays), and the guard time is one
+/// character time by default. Waiting for the data to be transmitted is limited to the duration of
+/// the data plus the guard time and 50 ms for the latency of the driver and adapter.
+///
+/// Character times are computed from the port's settings when the port is wrapped and whenever
+/// it's configured through the wrapper. If the baud rate or character format can't be determined,
+/// the delays are skipped.
+///
+/// Two-wire transceivers feed transmitted data back to the receiver. With
+/// [`set_discard_echo()`](#method.set_discard_echo), as many bytes as were written are removed
+/// from the data returned by later reads.
+///
+/// ```no_run
+/// use std::io::prelude::*;
+///
+/// let port = serial::open("/dev/ttyUSB0").unwrap();
+/// let mut port = serial::HalfDuplexPort::new(port).unwrap();
+///
+/// port.set_delays(0, 2);
+/// port.set_discard_echo(true);
+/// port.write_all(b"\x01\x03\x00\x00\x00\x01\x84\x0a").unwrap();
+/// ```
+pub struct HalfDuplexPort<P: SerialPort> {
+    port: P,
+    line: DirectionLine,
+    active_level: bool,
+    before_send: u32,
+    after_send: u32,
+    discard_echo: bool,
+    echo: usize,
+    char_time: Option<Duration>,
+}
+
+impl<P: SerialPort> HalfDuplexPort<P> {
+    /// Wraps a port, releasing its direction line.
+    ///
+    /// The wrapper uses RTS as the direction line, asserted while transmitting.
+    ///
+    /// ## Errors
+    ///
+    /// This function returns an error if the port's settings could not be read or the direction
+    /// line could not be released.
+    pub fn new(port: P) -> core::Result<Self> {
+        let mut half_duplex = HalfDuplexPort {
+            port: port,
+            line: DirectionLine::Rts,
+            active_level: true,
+            before_send: 0,
+            after_send: 1,
+            discard_echo: false,
+            echo: 0,
+            char_time: None,
+        };
+
+        half_duplex.char_time = try!(read_char_time(&half_duplex.port));
+        try!(half_duplex.set_line(false));
+
+        Ok(half_duplex)
+    }
+
+    /// Sets the control signal that enables the transceiver's driver and the level that enables
+    /// it. The signal is released immediately.
+    ///
+    /// ## Errors
+    ///
+    /// This function returns an error if the signal could not be released.
+    pub fn set_direction_line(&mut self, line: DirectionLine, active_level: bool) -> core::Result<()> {
+        self.line = line;
+        self.active_level = active_level;
+        self.set_line(false)
+    }
+
+    /// Sets the number of character times to wait after enabling the driver before writing
+    /// (`before_send`) and after the data has been transmitted before disabling it (`after_send`).
+    pub fn set_delays(&mut self, before_send: u32, after_send: u32) {
+        self.before_send = before_send;
+        self.after_send = after_send;
+    }
+
+    /// Sets whether to remove the local echo of written data from reads.
+    pub fn set_discard_echo(&mut self, discard_echo: bool) {
+        self.discard_echo = discard_echo;
+    }
+
+    /// Returns a reference to the underlying port.
+    pub fn get_ref(&self) -> &P {
+        &self.port
+    }
+
+    /// Returns a mutable reference to the underlying port.
+    ///
+    /// Data written directly to the underlying port is sent without changing the direction line.
+    pub fn get_mut(&mut self) -> &mut P {
+        &mut self.port
+    }
+
+    /// Returns the underlying port.
+    pub fn into_inner(self) -> P {
+        self.port
+    }
+
+    fn set_line(&mut self, transmitting: bool) -> core::Result<()> {
+        let level = transmitting == self.active_level;
+
+        match self.line {
+            DirectionLine::Rts => self.port.set_rts(level),
+            DirectionLine::Dtr => self.port.set_dtr(level),
+        }
+    }
+
+    // Waits until `len` bytes of written data have been transmitted, falling back to `flush()` for
+    // ports that don't report the state of their transmitter. The wait is limited to the time it
+    // takes to transmit the data plus the guard time and the transmit margin.
+    fn drain(&mut self, len: usize) -> io::Result<()> {
+        let timeout = match self.char_time {
+            Some(char_time) => {
+                let len = cmp::min(len, u32::max_value() as usize) as u32;
+                let margin = Duration::from_millis(TRANSMIT_MARGIN_MS);
+                Timeout::Duration(char_time * len.saturating_add(self.after_send) + margin)
+            }
+            None => self.port.write_timeout(),
+        };
+
+        match self.port.wait_transmit_complete(timeout) {
+            Err(ref e) if e.kind() == core::ErrorKind::Unsupported => self.port.flush(),
+            result => result.map_err(io::Error::from),
+        }
+    }
+
+    fn wait(&self, chars: u32) {
+        if let Some(char_time) = self.char_time {
+            if chars > 0 {
+                thread::sleep(char_time * chars);
+            }
+        }
+    }
+}
+
+// Reads a port's character time, which is unknown if the port doesn't report its settings.
+fn read_char_time<P: SerialPort>(port: &P) -> core::Result<Option<Duration>> {
+    match port.settings() {
+        Ok(settings) => Ok(settings.char_time()),
+        Err(ref e) if e.kind() == core::ErrorKind::Unsupported => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+impl<P: SerialPort> io::Read for HalfDuplexPort<P> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        // a read that only receives echoed data waits for more data
+        loop {
+            let len = try!(self.port.read(buf));
+
+            if len == 0 || self.echo == 0 {
+                return Ok(len);
+            }
+
+            let echo = cmp::min(len, self.echo);
+            self.echo -= echo;
+
+            if len > echo {
+                for i in echo..len {
+                    buf[i - echo] = buf[i];
+                }
+
+                return Ok(len - echo);
+            }
+        }
+    }
+}
+
+impl<P: SerialPort> io::Write for HalfDuplexPort<P> {
+    /// Writes all of `buf` in one transmission.
+    ///
+    /// The direction line is released even if writing fails. If writing fails after part of `buf`
+    /// was transmitted, its echo isn't discarded.
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        try!(self.set_line(true));
+        self.wait(self.before_send);
+
+        let result = self.port.write_all(buf).and_then(|_| self.drain(buf.len()));
+
+        if result.is_ok() {
+            self.wait(self.after_send);
+        }
+
+        let released = self.set_line(false);
+
+        try!(result);
+        try!(released);
+
+        if self.discard_echo {
+            self.echo += buf.len();
+        }
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.port.flush()
+    }
+}
+
+impl<P: SerialPort> SerialPort for HalfDuplexPort<P> {
+    fn read_timeout(&self) -> Timeout {
+        self.port.read_timeout()
+    }
+
+    fn write_timeout(&self) -> Timeout {
+        self.port.write_timeout()
+    }
+
+    fn set_read_timeout(&mut self, timeout: Timeout) -> core::Result<()> {
+        self.port.set_read_timeout(timeout)
+    }
+
+    fn set_write_timeout(&mut self, timeout: Timeout) -> core::Result<()> {
+        self.port.set_write_timeout(timeout)
+    }
+
+    fn inter_byte_timeout(&self) -> Option<u32> {
+        self.port.inter_byte_timeout()
+    }
+
+    fn set_inter_byte_timeout(&mut self, chars: Option<u32>) -> core::Result<()> {
+        self.port.set_inter_byte_timeout(chars)
+    }
+
+    fn configure(&mut self, settings: &PortSettings) -> core::Result<()> {
+        try!(self.port.configure(settings));
+        self.char_time = settings.char_time();
+        Ok(())
+    }
+
+    fn reconfigure(&mut self, setup: &Fn(&mut SerialPortSettings) -> core::Result<()>) -> core::Result<()> {
+        let char_time = Cell::new(None);
+
+        try!(self.port.reconfigure(&|settings| {
+            try!(setup(settings));
+            char_time.set(settings.char_time());
+            Ok(())
+        }));
+
+        self.char_time = char_time.get();
+        Ok(())
+    }
+
+    fn settings<'a>(&'a self) -> core::Result<Box<SerialPortSettings + 'a>> {
+        self.port.settings()
+    }
+
+    fn set_rts(&mut self, level: bool) -> core::Result<()> {
+        self.port.set_rts(level)
+    }
+
+    fn set_dtr(&mut self, level: bool) -> core::Result<()> {
+        self.port.set_dtr(level)
+    }
+
+    fn read_cts(&mut self) -> core::Result<bool> {
+        self.port.read_cts()
+    }
+
+    fn read_dsr(&mut self) -> core::Result<bool> {
+        self.port.read_dsr()
+    }
+
+    fn read_ri(&mut self) -> core::Result<bool> {
+        self.port.read_ri()
+    }
+
+    fn read_cd(&mut self) -> core::Result<bool> {
+        self.port.read_cd()
+    }
+
+    fn set_modem_control(&mut self, control: core::ModemControl) -> core::Result<()> {
+        self.port.set_modem_control(control)
+    }
+
+    fn read_dtr(&mut self) -> core::Result<bool> {
+        self.port.read_dtr()
+    }
+
+    fn read_rts(&mut self) -> core::Result<bool> {
+        self.port.read_rts()
+    }
+
+    fn set_break(&mut self, enabled: bool) -> core::Result<()> {
+        self.port.set_break(enabled)
+    }
+
+    fn suspend_output(&mut self) -> core::Result<()> {
+        self.port.suspend_output()
+    }
+
+    fn resume_output(&mut self) -> core::Result<()> {
+        self.port.resume_output()
+    }
+
+    fn send_xoff(&mut self) -> core::Result<()> {
+        self.port.send_xoff()
+    }
+
+    fn send_xon(&mut self) -> core::Result<()> {
+        self.port.send_xon()
+    }
+
+    fn is_output_stopped(&mut self) -> core::Result<bool> {
+        self.port.is_output_stopped()
+    }
+
+    fn wait_transmit_complete(&mut self, timeout: Timeout) -> core::Result<()> {
+        self.port.wait_transmit_complete(timeout)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use core;
+
+    use std::io;
+    use std::io::prelude::*;
+    use std::time::{Duration, Instant};
+
+    use core::prelude::*;
+    use core::Timeout;
+
+    use testing::{Event, MockPort};
+
+    use super::{DirectionLine, HalfDuplexPort};
+
+    #[test]
+    fn new_releases_rts() {
+        let port = HalfDuplexPort::new(MockPort::new(&[])).unwrap();
+        assert_eq!(port.get_ref().events, vec![Event::Rts(false)]);
+    }
+
+    #[test]
+    fn write_enables_driver_until_data_is_transmitted() {
+        let mut port = HalfDuplexPort::new(MockPort::new(&[])).unwrap();
+        port.get_mut().events.clear();
+
+        assert_eq!(port.write(b"abc").unwrap(), 3);
+        assert_eq!(port.get_ref().events, vec![
+            Event::Rts(true),
+            Event::Write(b"abc".to_vec()),
+            Event::Flush,
+            Event::Rts(false),
+        ]);
+    }
+
+    #[test]
+    fn write_limits_wait_to_transmission_time() {
+        let mut port = HalfDuplexPort::new(MockPort::new(&[])).unwrap();
+        let char_time = port.get_ref().settings.char_time().unwrap();
+
+        assert_eq!(port.write(b"0123456789").unwrap(), 10);
+        assert_eq!(port.get_ref().transmit_timeout,
+                   Some(Timeout::Duration(char_time * 11 + Duration::from_millis(50))));
+    }
+
+    #[test]
+    fn write_allows_for_transmit_latency() {
+        let mut port = HalfDuplexPort::new(MockPort::new(&[])).unwrap();
+        let char_time = port.get_ref().settings.char_time().unwrap();
+
+        // a USB adapter reports data as transmitted up to 16 ms after the frame was sent
+        port.get_mut().transmit_time = Some(char_time * 11 + Duration::from_millis(16));
+        port.get_mut().events.clear();
+
+        assert_eq!(port.write(b"0123456789").unwrap(), 10);
+        assert_eq!(port.get_ref().events, vec![
+            Event::Rts(true),
+            Event::Write(b"0123456789".to_vec()),
+            Event::Rts(false),
+        ]);
+    }
+
+    #[test]
+    fn write_uses_configured_line_and_level() {
+        let mut port = HalfDuplexPort::new(MockPort::new(&[])).unwrap();
+        port.set_direction_line(DirectionLine::Dtr, false).unwrap();
+        port.get_mut().events.clear();
+
+        assert_eq!(port.write(b"a").unwrap(), 1);
+        assert_eq!(port.get_ref().events, vec![
+            Event::Dtr(false),
+            Event::Write(b"a".to_vec()),
+            Event::Flush,
+            Event::Dtr(true),
+        ]);
+    }
+
+    #[test]
+    fn write_waits_for_delays_in_char_times() {
+        let mut port = HalfDuplexPort::new(MockPort::new(&[])).unwrap();
+
+        // 20 character times at 9600 baud with 8N1 is about 20.8 ms
+        port.reconfigure(&|settings| settings.set_baud_rate(core::Baud9600)).unwrap();
+        port.set_delays(10, 10);
+
+        let start = Instant::now();
+        assert_eq!(port.write(b"a").unwrap(), 1);
+        assert!(start.elapsed() >= Duration::from_millis(20));
+    }
+
+    #[test]
+    fn write_releases_line_after_error() {
+        let mut port = HalfDuplexPort::new(MockPort::new(&[])).unwrap();
+        port.get_mut().fail_writes = true;
+        port.get_mut().events.clear();
+
+        assert_eq!(port.write(b"a").unwrap_err().kind(), io::ErrorKind::TimedOut);
+        assert_eq!(port.get_ref().events, vec![Event::Rts(true), Event::Rts(false)]);
+    }
+
+    #[test]
+    fn read_discards_echo() {
+        let mut port = HalfDuplexPort::new(MockPort::new(&[b"ab", b"cde"])).unwrap();
+        port.set_discard_echo(true);
+        assert_eq!(port.write(b"abc").unwrap(), 3);
+
+        let mut buf = [0u8; 8];
+        assert_eq!(port.read(&mut buf).unwrap(), 2);
+        assert_eq!(&buf[..2], b"de");
+    }
+
+    #[test]
+    fn read_keeps_echo_by_default() {
+        let mut port = HalfDuplexPort::new(MockPort::new(&[b"abc"])).unwrap();
+        assert_eq!(port.write(b"abc").unwrap(), 3);
+
+        let mut buf = [0u8; 8];
+        assert_eq!(port.read(&mut buf).unwrap(), 3);
+    }
+}
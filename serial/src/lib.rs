@@ -27,13 +27,17 @@ pub extern crate serial_unix as unix;
 #[cfg(windows)]
 pub extern crate serial_windows as windows;
 
+#[cfg(unix)]
+extern crate libc;
+
 use std::ffi::OsStr;
 
 #[doc(no_inline)] pub use core::prelude;
 
 #[doc(no_inline)] pub use core::{Result, Error, ErrorKind};
 #[doc(no_inline)] pub use core::{PortSettings, BaudRate, CharSize, Parity, StopBits, FlowControl};
-#[doc(no_inline)] pub use core::{SerialPort, SerialPortSettings};
+#[doc(no_inline)] pub use core::{BaudClock, EffectiveBaudRate};
+#[doc(no_inline)] pub use core::{SerialPort, SerialPortSettings, ModemControl, Timeout, XON, XOFF};
 
 pub use core::BaudRate::*;
 pub use core::CharSize::*;
@@ -41,6 +45,22 @@ pub use core::Parity::*;
 pub use core::StopBits::*;
 pub use core::FlowControl::*;
 
+#[cfg(target_os = "linux")]
+#[doc(no_inline)] pub use unix::{DeviceMonitor, DeviceEvent, PortInfo};
+
+pub use flow::SoftwareFlowControl;
+pub use half_duplex::{HalfDuplexPort, DirectionLine};
+pub use reconnect::{ReconnectingPort, Backoff, ConnectionEvent, Identity};
+
+pub mod sequence;
+
+mod flow;
+mod half_duplex;
+mod reconnect;
+
+#[cfg(test)]
+mod testing;
+
 /// A convenience type alias for the system's native serial port type.
 #[cfg(unix)]
 pub type SystemPort = unix::TTYPort;
@@ -125,3 +145,30 @@ pub fn open<T: AsRef<OsStr> + ?Sized>(port: &T) -> ::core::Result<SystemPort> {
 pub fn open<T: AsRef<OsStr> + ?Sized>(port: &T) -> ::core::Result<SystemPort> {
     windows::COMPort::open(port)
 }
+
+/// Diagnoses why a native serial port might be unavailable.
+///
+/// The argument should be the path to a TTY device file. The returned
+/// [`Diagnosis`](../serial_unix/struct.Diagnosis.html) lists the processes that have the device
+/// open, whether the current user has permission to open it, and whether a UUCP lock file exists
+/// for it.
+///
+/// ## Errors
+///
+/// This function returns an error if the device's metadata could not be read:
+///
+/// * `NoDevice` if the device does not exist.
+/// * `Io` for any other error.
+///
+/// ## Example
+///
+/// ```no_run
+/// if let Err(err) = serial::open("/dev/ttyUSB0") {
+///     println!("{}: {}", err, serial::diagnose("/dev/ttyUSB0").unwrap());
+/// }
+/// ```
+#[cfg(unix)]
+pub fn diagnose<T: AsRef<OsStr> + ?Sized>(port: &T) -> ::core::Result<unix::Diagnosis> {
+    use std::path::Path;
+    unix::diagnose(Path::new(port))
+}
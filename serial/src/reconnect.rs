@@ -0,0 +1,885 @@
+// Copyright (c) 2015 David Cuddeback
+//
+// Permission is hereby granted, free of charge, to any person obtaining
+// a copy of this software and associated documentation files (the
+// "Software"), to deal in the Software without restriction, including
+// without limitation the rights to use, copy, modify, merge, publish,
+// distribute, sublicense, and/or sell copies of the Software, and to
+// permit persons to whom the Software is furnished to do so, subject to
+// the following conditions:
+//
+// The above copyright notice and this permission notice shall be
+// included in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND,
+// EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF
+// MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE
+// LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION
+// WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+use core;
+
+use std::cell::Cell;
+use std::ffi::OsStr;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use core::{SerialPort, SerialPortSettings, PortSettings, Timeout};
+
+/// The delays between attempts to reopen a disconnected port.
+///
+/// The first attempt is made immediately. After each failed attempt, the delay starts at `initial`
+/// and doubles up to `max`.
+#[derive(Debug,Copy,Clone,PartialEq,Eq)]
+pub struct Backoff {
+    /// Delay after the first failed attempt.
+    pub initial: Duration,
+
+    /// Maximum delay between attempts.
+    pub max: Duration,
+
+    /// Maximum number of attempts per reconnection, or `None` to keep trying indefinitely.
+    pub max_attempts: Option<u32>,
+}
+
+impl Backoff {
+    /// Returns the delay after the given number of failed attempts.
+    fn delay(&self, failures: u32) -> Duration {
+        let mut delay = self.initial;
+
+        for _ in 1..failures {
+            delay = match delay.checked_mul(2) {
+                Some(delay) if delay < self.max => delay,
+                _ => return self.max,
+            };
+        }
+
+        if delay < self.max { delay } else { self.max }
+    }
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Backoff {
+            initial: Duration::from_millis(100),
+            max: Duration::from_secs(5),
+            max_attempts: None,
+        }
+    }
+}
+
+/// An event reported by a [`ReconnectingPort`](struct.ReconnectingPort.html).
+#[derive(Debug,Clone,PartialEq,Eq)]
+pub enum ConnectionEvent {
+    /// The port was disconnected. The description of the error is included.
+    Disconnected(String),
+
+    /// An attempt to reopen the port failed. The attempt number and the description of the error
+    /// are included.
+    ReconnectFailed(u32, String),
+
+    /// The port was reopened and its state was restored after the given number of attempts.
+    Reconnected(u32),
+}
+
+/// The identity of a serial port device.
+#[derive(Debug,Clone,PartialEq,Eq)]
+pub enum Identity {
+    /// A device path, which is opened with [`serial::open()`](fn.open.html).
+    ///
+    /// Stable paths, such as links in `/dev/serial/by-id` on Linux, keep identifying a device
+    /// after it has been reset or plugged into a different USB port.
+    Path(PathBuf),
+
+    /// The serial number of a USB device.
+    ///
+    /// The serial number is resolved to a device path through `/dev/serial/by-id` each time the
+    /// port is opened. This is only supported on Linux.
+    UsbSerialNumber(String),
+}
+
+type Opener<P> = Box<FnMut() -> core::Result<P> + Send>;
+type Listener = Box<FnMut(&ConnectionEvent) + Send>;
+
+/// A serial port that is reopened automatically after it is disconnected.
+///
+/// `ReconnectingPort` remembers the settings, timeouts and RTS/DTR levels that were applied through
+/// it. When an operation fails because the device was disconnected, the port is reopened with the
+/// configured [`Backoff`](struct.Backoff.html) and the remembered state is reapplied.
+///
+/// Operations that only change or query the port's state are retried transparently after the port
+/// is reopened. Reads and writes are not retried, because data may have been lost with the
+/// disconnected device. Instead, they return an error of kind `io::ErrorKind::ConnectionReset`
+/// once the port has been reopened, or `io::ErrorKind::NotConnected` if it could not be reopened.
+///
+/// Reconnection attempts block the calling thread. Reads and writes stop reconnecting once their
+/// read or write timeout has expired, so a `NonBlocking` timeout allows a single attempt. Other
+/// operations continue until the device reappears if `max_attempts` is `None`.
+///
+/// ```no_run
+/// use std::io::prelude::*;
+///
+/// let mut port = serial::ReconnectingPort::open("/dev/serial/by-id/usb-FTDI_FT232R_A9M9DV3R-if00-port0").unwrap();
+///
+/// port.on_event(|event| println!("{:?}", event));
+/// port.write_all(b"hello").unwrap();
+/// ```
+pub struct ReconnectingPort<P: SerialPort> {
+    port: Option<P>,
+    opener: Opener<P>,
+    settings: Option<PortSettings>,
+    read_timeout: Timeout,
+    write_timeout: Timeout,
+    inter_byte_timeout: Option<u32>,
+    rts: Option<bool>,
+    dtr: Option<bool>,
+    backoff: Backoff,
+    listener: Option<Listener>,
+}
+
+impl ReconnectingPort<::SystemPort> {
+    /// Opens a native serial port that is reopened by the same name after it is disconnected.
+    ///
+    /// ## Errors
+    ///
+    /// This function returns an error if the port could not be opened initially. See
+    /// [`serial::open()`](fn.open.html).
+    pub fn open<T: AsRef<OsStr> + ?Sized>(port: &T) -> core::Result<Self> {
+        ReconnectingPort::open_identity(Identity::Path(PathBuf::from(port.as_ref())))
+    }
+
+    /// Opens the native serial port with the given identity, which is reopened after it is
+    /// disconnected.
+    ///
+    /// ## Errors
+    ///
+    /// This function returns an error if the port could not be opened initially:
+    ///
+    /// * `NoDevice` if the device could not be found or opened.
+    /// * `InvalidInput` if the identity is not valid.
+    /// * `Io` for any other error while opening or initializing the device.
+    pub fn open_identity(identity: Identity) -> core::Result<Self> {
+        ReconnectingPort::new(move || match identity {
+            Identity::Path(ref path) => ::open(path),
+            Identity::UsbSerialNumber(ref serial) => ::open(&try!(find_usb_serial_number(serial))),
+        })
+    }
+}
+
+impl<P: SerialPort> ReconnectingPort<P> {
+    /// Creates a port that is opened, and reopened after disconnection, by calling `opener`.
+    ///
+    /// ## Errors
+    ///
+    /// This function returns any error returned by `opener` when opening the port initially.
+    pub fn new<F>(mut opener: F) -> core::Result<Self>
+        where F: FnMut() -> core::Result<P> + Send + 'static
+    {
+        let port = try!(opener());
+        let read_timeout = port.read_timeout();
+        let write_timeout = port.write_timeout();
+        let inter_byte_timeout = port.inter_byte_timeout();
+
+        Ok(ReconnectingPort {
+            port: Some(port),
+            opener: Box::new(opener),
+            settings: None,
+            read_timeout: read_timeout,
+            write_timeout: write_timeout,
+            inter_byte_timeout: inter_byte_timeout,
+            rts: None,
+            dtr: None,
+            backoff: Backoff::default(),
+            listener: None,
+        })
+    }
+
+    /// Sets the delays between attempts to reopen the port.
+    pub fn set_backoff(&mut self, backoff: Backoff) {
+        self.backoff = backoff;
+    }
+
+    /// Registers a function to be called for each disconnection and reconnection attempt.
+    pub fn on_event<F>(&mut self, listener: F)
+        where F: FnMut(&ConnectionEvent) + Send + 'static
+    {
+        self.listener = Some(Box::new(listener));
+    }
+
+    /// Returns `true` if the port is currently open.
+    pub fn is_connected(&self) -> bool {
+        self.port.is_some()
+    }
+
+    /// Returns a reference to the underlying port, if it is open.
+    pub fn get_ref(&self) -> Option<&P> {
+        self.port.as_ref()
+    }
+
+    /// Returns a mutable reference to the underlying port, if it is open.
+    ///
+    /// State changed directly on the underlying port is not restored after reconnecting.
+    pub fn get_mut(&mut self) -> Option<&mut P> {
+        self.port.as_mut()
+    }
+
+    /// Reopens the port if it is disconnected.
+    ///
+    /// ## Errors
+    ///
+    /// This function returns the last error from reopening the port if all attempts failed.
+    pub fn reconnect(&mut self) -> core::Result<()> {
+        self.reconnect_until(None)
+    }
+
+    // Reopens the port if it is disconnected, giving up after `deadline`.
+    fn reconnect_until(&mut self, deadline: Option<Instant>) -> core::Result<()> {
+        if self.port.is_some() {
+            return Ok(());
+        }
+
+        let mut attempt = 0;
+
+        loop {
+            attempt += 1;
+
+            let error = match self.reopen() {
+                Ok(port) => {
+                    self.port = Some(port);
+                    self.notify(ConnectionEvent::Reconnected(attempt));
+                    return Ok(());
+                }
+                Err(e) => e,
+            };
+
+            self.notify(ConnectionEvent::ReconnectFailed(attempt, error.to_string()));
+
+            if self.backoff.max_attempts.map(|max| attempt >= max) == Some(true) {
+                return Err(error);
+            }
+
+            let mut delay = self.backoff.delay(attempt);
+
+            if let Some(deadline) = deadline {
+                let now = Instant::now();
+
+                if now >= deadline {
+                    return Err(error);
+                }
+
+                if deadline - now < delay {
+                    delay = deadline - now;
+                }
+            }
+
+            thread::sleep(delay);
+        }
+    }
+
+    fn reopen(&mut self) -> core::Result<P> {
+        let mut port = try!((self.opener)());
+
+        if let Some(ref settings) = self.settings {
+            try!(port.configure(settings));
+        }
+
+        try!(port.set_read_timeout(self.read_timeout));
+        try!(port.set_write_timeout(self.write_timeout));
+        try!(port.set_inter_byte_timeout(self.inter_byte_timeout));
+
+        match (self.dtr, self.rts) {
+            (Some(dtr), Some(rts)) => try!(port.set_modem_control(core::ModemControl { dtr: dtr, rts: rts })),
+            (Some(dtr), None) => try!(port.set_dtr(dtr)),
+            (None, Some(rts)) => try!(port.set_rts(rts)),
+            (None, None) => (),
+        }
+
+        Ok(port)
+    }
+
+    fn disconnect(&mut self, description: String) {
+        self.port = None;
+        self.notify(ConnectionEvent::Disconnected(description));
+    }
+
+    fn notify(&mut self, event: ConnectionEvent) {
+        if let Some(ref mut listener) = self.listener {
+            listener(&event);
+        }
+    }
+
+    // Calls `f` with the port, reconnecting and retrying once if the port was disconnected.
+    fn with_port<T, F>(&mut self, mut f: F) -> core::Result<T>
+        where F: FnMut(&mut P) -> core::Result<T>
+    {
+        for _ in 0..2 {
+            try!(self.reconnect());
+
+            match f(self.port.as_mut().unwrap()) {
+                Err(ref e) if is_disconnected(e) => self.disconnect(e.to_string()),
+                result => return result,
+            }
+        }
+
+        Err(core::Error::new(core::ErrorKind::NoDevice, "Device was disconnected"))
+    }
+
+    // Calls `f` with the port. If the port was disconnected, it is reconnected within `timeout`,
+    // but `f` is not retried, since data may have been lost.
+    fn transfer<T, F>(&mut self, timeout: Timeout, f: F) -> io::Result<T>
+        where F: FnOnce(&mut P) -> io::Result<T>
+    {
+        let deadline = timeout.deadline();
+
+        if self.port.is_none() {
+            try!(self.reconnect_until(deadline).map_err(not_connected));
+        }
+
+        match f(self.port.as_mut().unwrap()) {
+            Err(ref e) if is_disconnected_io(e) => {
+                self.disconnect(e.to_string());
+
+                match self.reconnect_until(deadline) {
+                    Ok(()) => Err(io::Error::new(io::ErrorKind::ConnectionReset, "Device was reconnected; data in transit may have been lost")),
+                    Err(e) => Err(not_connected(e)),
+                }
+            }
+            result => result,
+        }
+    }
+}
+
+impl<P: SerialPort> io::Read for ReconnectingPort<P> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let timeout = self.read_timeout;
+        let len = buf.len();
+
+        self.transfer(timeout, |port| match port.read(buf) {
+            // a TTY reads end of file after it has been hung up
+            Ok(0) if len > 0 => Err(io::Error::new(io::ErrorKind::BrokenPipe, "Device was hung up")),
+            result => result,
+        })
+    }
+}
+
+impl<P: SerialPort> io::Write for ReconnectingPort<P> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let timeout = self.write_timeout;
+        self.transfer(timeout, |port| port.write(buf))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let timeout = self.write_timeout;
+        self.transfer(timeout, |port| port.flush())
+    }
+}
+
+impl<P: SerialPort> SerialPort for ReconnectingPort<P> {
+    fn read_timeout(&self) -> Timeout {
+        self.read_timeout
+    }
+
+    fn write_timeout(&self) -> Timeout {
+        self.write_timeout
+    }
+
+    fn set_read_timeout(&mut self, timeout: Timeout) -> core::Result<()> {
+        try!(self.with_port(|port| port.set_read_timeout(timeout)));
+        self.read_timeout = timeout;
+        Ok(())
+    }
+
+    fn set_write_timeout(&mut self, timeout: Timeout) -> core::Result<()> {
+        try!(self.with_port(|port| port.set_write_timeout(timeout)));
+        self.write_timeout = timeout;
+        Ok(())
+    }
+
+    fn inter_byte_timeout(&self) -> Option<u32> {
+        self.inter_byte_timeout
+    }
+
+    fn set_inter_byte_timeout(&mut self, chars: Option<u32>) -> core::Result<()> {
+        try!(self.with_port(|port| port.set_inter_byte_timeout(chars)));
+        self.inter_byte_timeout = chars;
+        Ok(())
+    }
+
+    fn configure(&mut self, settings: &PortSettings) -> core::Result<()> {
+        try!(self.with_port(|port| port.configure(settings)));
+        self.settings = Some(*settings);
+        Ok(())
+    }
+
+    fn configure_verified(&mut self, settings: &PortSettings) -> core::Result<()> {
+        try!(self.with_port(|port| port.configure_verified(settings)));
+        self.settings = Some(*settings);
+        Ok(())
+    }
+
+    fn reconfigure(&mut self, setup: &Fn(&mut SerialPortSettings) -> core::Result<()>) -> core::Result<()> {
+        let applied = Cell::new(None);
+
+        try!(self.with_port(|port| {
+            port.reconfigure(&|settings| {
+                try!(setup(settings));
+                applied.set(port_settings(settings));
+                Ok(())
+            })
+        }));
+
+        // settings that can't be represented by `PortSettings` can't be restored
+        self.settings = applied.get();
+        Ok(())
+    }
+
+    fn settings<'a>(&'a self) -> core::Result<Box<SerialPortSettings + 'a>> {
+        match self.port {
+            Some(ref port) => port.settings(),
+            None => Err(core::Error::new(core::ErrorKind::NoDevice, "Device was disconnected")),
+        }
+    }
+
+    fn set_rts(&mut self, level: bool) -> core::Result<()> {
+        try!(self.with_port(|port| port.set_rts(level)));
+        self.rts = Some(level);
+        Ok(())
+    }
+
+    fn set_dtr(&mut self, level: bool) -> core::Result<()> {
+        try!(self.with_port(|port| port.set_dtr(level)));
+        self.dtr = Some(level);
+        Ok(())
+    }
+
+    fn read_cts(&mut self) -> core::Result<bool> {
+        self.with_port(|port| port.read_cts())
+    }
+
+    fn read_dsr(&mut self) -> core::Result<bool> {
+        self.with_port(|port| port.read_dsr())
+    }
+
+    fn read_ri(&mut self) -> core::Result<bool> {
+        self.with_port(|port| port.read_ri())
+    }
+
+    fn read_cd(&mut self) -> core::Result<bool> {
+        self.with_port(|port| port.read_cd())
+    }
+
+    fn set_modem_control(&mut self, control: core::ModemControl) -> core::Result<()> {
+        try!(self.with_port(|port| port.set_modem_control(control)));
+        self.dtr = Some(control.dtr);
+        self.rts = Some(control.rts);
+        Ok(())
+    }
+
+    fn read_dtr(&mut self) -> core::Result<bool> {
+        self.with_port(|port| port.read_dtr())
+    }
+
+    fn read_rts(&mut self) -> core::Result<bool> {
+        self.with_port(|port| port.read_rts())
+    }
+
+    fn set_break(&mut self, enabled: bool) -> core::Result<()> {
+        self.with_port(|port| port.set_break(enabled))
+    }
+
+    fn suspend_output(&mut self) -> core::Result<()> {
+        self.with_port(|port| port.suspend_output())
+    }
+
+    fn resume_output(&mut self) -> core::Result<()> {
+        self.with_port(|port| port.resume_output())
+    }
+
+    fn send_xoff(&mut self) -> core::Result<()> {
+        self.with_port(|port| port.send_xoff())
+    }
+
+    fn send_xon(&mut self) -> core::Result<()> {
+        self.with_port(|port| port.send_xon())
+    }
+
+    fn is_output_stopped(&mut self) -> core::Result<bool> {
+        self.with_port(|port| port.is_output_stopped())
+    }
+
+    fn wait_transmit_complete(&mut self, timeout: Timeout) -> core::Result<()> {
+        self.with_port(|port| port.wait_transmit_complete(timeout))
+    }
+}
+
+fn port_settings(settings: &SerialPortSettings) -> Option<PortSettings> {
+    match (settings.baud_rate(), settings.char_size(), settings.parity(), settings.stop_bits(), settings.flow_control()) {
+        (Some(baud_rate), Some(char_size), Some(parity), Some(stop_bits), Some(flow_control)) => Some(PortSettings {
+            baud_rate: baud_rate,
+            char_size: char_size,
+            parity: parity,
+            stop_bits: stop_bits,
+            flow_control: flow_control,
+        }),
+        _ => None,
+    }
+}
+
+fn not_connected(error: core::Error) -> io::Error {
+    io::Error::new(io::ErrorKind::NotConnected, error)
+}
+
+fn is_disconnected(error: &core::Error) -> bool {
+    match error.kind() {
+        // `NoDevice` also covers busy and locked devices, which are reported with an OS error
+        core::ErrorKind::NoDevice => error.raw_os_error().map(is_disconnected_os_error) != Some(false),
+        core::ErrorKind::Io(kind) => is_disconnected_kind(kind, error.raw_os_error()),
+        _ => false,
+    }
+}
+
+fn is_disconnected_io(error: &io::Error) -> bool {
+    if let Some(error) = error.get_ref().and_then(|e| e.downcast_ref::<core::Error>()) {
+        return is_disconnected(error);
+    }
+
+    is_disconnected_kind(error.kind(), error.raw_os_error())
+}
+
+fn is_disconnected_kind(kind: io::ErrorKind, raw_os_error: Option<i32>) -> bool {
+    match kind {
+        io::ErrorKind::BrokenPipe | io::ErrorKind::NotFound | io::ErrorKind::NotConnected => true,
+        _ => raw_os_error.map(is_disconnected_os_error) == Some(true),
+    }
+}
+
+// These errors are reported by TTY drivers for devices that have been removed.
+#[cfg(unix)]
+fn is_disconnected_os_error(errno: i32) -> bool {
+    use libc::{EIO, ENXIO, ENODEV};
+
+    errno == EIO || errno == ENXIO || errno == ENODEV
+}
+
+#[cfg(windows)]
+const ERROR_BAD_COMMAND: i32 = 22;
+#[cfg(windows)]
+const ERROR_GEN_FAILURE: i32 = 31;
+#[cfg(windows)]
+const ERROR_OPERATION_ABORTED: i32 = 995;
+#[cfg(windows)]
+const ERROR_DEVICE_NOT_CONNECTED: i32 = 1167;
+
+// These errors are reported for COM ports whose device has been removed. ERROR_ACCESS_DENIED is
+// not included, since it is also reported when the port is open in another process.
+#[cfg(windows)]
+fn is_disconnected_os_error(errno: i32) -> bool {
+    errno == ERROR_BAD_COMMAND || errno == ERROR_GEN_FAILURE || errno == ERROR_OPERATION_ABORTED ||
+        errno == ERROR_DEVICE_NOT_CONNECTED
+}
+
+#[cfg(target_os = "linux")]
+fn find_usb_serial_number(serial: &str) -> core::Result<PathBuf> {
+    use std::fs;
+
+    let dir = Path::new("/dev/serial/by-id");
+
+    let entries = try!(fs::read_dir(dir).map_err(|e| core::Error::from(e).with_path(dir)));
+
+    for entry in entries.filter_map(|e| e.ok()) {
+        let name = entry.file_name();
+
+        if name.to_str().map(|name| by_id_has_serial_number(name, serial)) == Some(true) {
+            return Ok(entry.path());
+        }
+    }
+
+    Err(core::Error::new(core::ErrorKind::NoDevice, format!("No USB device with serial number {}", serial)).with_path(dir))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn find_usb_serial_number(serial: &str) -> core::Result<PathBuf> {
+    Err(core::Error::new(core::ErrorKind::InvalidInput,
+                         format!("Can't find USB device with serial number {} on this platform", serial)))
+}
+
+// Links in /dev/serial/by-id are named `usb-<vendor>_<product>_<serial>-if<nn>[-port<n>]`, where
+// each part has been sanitized by udev.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn by_id_has_serial_number(name: &str, serial: &str) -> bool {
+    let name = match name.rfind("-if") {
+        Some(end) => &name[..end],
+        None => name,
+    };
+
+    name.starts_with("usb-") && name.ends_with(&format!("_{}", udev_sanitize(serial)))
+}
+
+// Sanitizes a string the way udev does for device identifiers: leading and trailing whitespace is
+// removed, other runs of whitespace are replaced by one underscore, and characters that aren't
+// allowed in identifiers are replaced by underscores.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn udev_sanitize(s: &str) -> String {
+    let mut sanitized = String::with_capacity(s.len());
+    let mut whitespace = false;
+
+    for c in s.trim().chars() {
+        if c.is_whitespace() {
+            whitespace = true;
+            continue;
+        }
+
+        if whitespace {
+            sanitized.push('_');
+            whitespace = false;
+        }
+
+        if c.is_ascii_alphanumeric() || !c.is_ascii() || "#+-.:=@_".contains(c) {
+            sanitized.push(c);
+        }
+        else {
+            sanitized.push('_');
+        }
+    }
+
+    sanitized
+}
+
+
+#[cfg(test)]
+mod tests {
+    use core;
+
+    use std::io;
+    use std::io::prelude::*;
+    use std::sync::{Arc, Mutex};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::time::Duration;
+
+    use core::prelude::*;
+    use core::Timeout;
+
+    use testing::{self, Event, MockPort};
+
+    use super::{Backoff, ConnectionEvent, ReconnectingPort};
+
+    // Opens ports for a simulated device while `present` is set. Each port has one byte to read.
+    fn reconnecting_port(present: &Arc<AtomicBool>) -> ReconnectingPort<MockPort> {
+        present.store(true, Ordering::SeqCst);
+
+        let present = present.clone();
+
+        let mut port = ReconnectingPort::new(move || {
+            if !present.load(Ordering::SeqCst) {
+                return Err(core::Error::new(core::ErrorKind::NoDevice, "No such device"));
+            }
+
+            Ok(MockPort::new(&[b"x"]))
+        }).unwrap();
+
+        port.set_backoff(Backoff {
+            initial: Duration::from_millis(1),
+            max: Duration::from_millis(1),
+            max_attempts: Some(3),
+        });
+
+        port
+    }
+
+    // Simulates unplugging the device. It's plugged back in if it's still present.
+    fn unplug(port: &ReconnectingPort<MockPort>) {
+        port.get_ref().unwrap().unplugged.set(true);
+    }
+
+    #[test]
+    fn backoff_doubles_delay_up_to_max() {
+        let backoff = Backoff {
+            initial: Duration::from_millis(100),
+            max: Duration::from_millis(500),
+            max_attempts: None,
+        };
+
+        assert_eq!(backoff.delay(1), Duration::from_millis(100));
+        assert_eq!(backoff.delay(2), Duration::from_millis(200));
+        assert_eq!(backoff.delay(3), Duration::from_millis(400));
+        assert_eq!(backoff.delay(4), Duration::from_millis(500));
+    }
+
+    #[test]
+    fn reconnecting_port_restores_state_after_reconnect() {
+        let present = Arc::new(AtomicBool::new(true));
+        let mut port = reconnecting_port(&present);
+
+        let mut settings = testing::default_settings();
+        settings.baud_rate = core::Baud115200;
+
+        port.configure(&settings).unwrap();
+        port.set_read_timeout(Timeout::Duration(Duration::from_millis(250))).unwrap();
+        port.set_write_timeout(Timeout::Infinite).unwrap();
+        port.set_inter_byte_timeout(Some(3)).unwrap();
+        port.set_rts(false).unwrap();
+        port.set_dtr(true).unwrap();
+
+        unplug(&port);
+        assert!(port.read_cts().unwrap());
+
+        let mock = port.get_ref().unwrap();
+        assert_eq!(mock.settings, settings);
+        assert_eq!(mock.inter_byte_timeout, Some(3));
+        assert_eq!(mock.events, vec![
+            Event::Configure,
+            Event::ReadTimeout(Timeout::Duration(Duration::from_millis(250))),
+            Event::WriteTimeout(Timeout::Infinite),
+            Event::ModemControl(true, false),
+        ]);
+    }
+
+    #[test]
+    fn reconnecting_port_restores_reconfigured_settings() {
+        let present = Arc::new(AtomicBool::new(true));
+        let mut port = reconnecting_port(&present);
+
+        port.reconfigure(&|settings| settings.set_baud_rate(core::Baud57600)).unwrap();
+
+        unplug(&port);
+        port.set_rts(true).unwrap();
+
+        assert_eq!(port.get_ref().unwrap().settings.baud_rate, core::Baud57600);
+    }
+
+    #[test]
+    fn reconnecting_port_restores_modem_control() {
+        let present = Arc::new(AtomicBool::new(true));
+        let mut port = reconnecting_port(&present);
+
+        port.set_modem_control(core::ModemControl { dtr: false, rts: true }).unwrap();
+
+        unplug(&port);
+        assert!(port.read_cts().unwrap());
+
+        assert!(port.get_ref().unwrap().events.contains(&Event::ModemControl(false, true)));
+    }
+
+    #[test]
+    fn reconnecting_port_reports_reset_to_interrupted_read() {
+        let present = Arc::new(AtomicBool::new(true));
+        let mut port = reconnecting_port(&present);
+
+        unplug(&port);
+
+        let mut buf = [0u8; 1];
+        assert_eq!(port.read(&mut buf).unwrap_err().kind(), io::ErrorKind::ConnectionReset);
+        assert_eq!(port.read(&mut buf).unwrap(), 1);
+    }
+
+    #[test]
+    fn reconnecting_port_reports_reset_to_hung_up_read() {
+        let present = Arc::new(AtomicBool::new(true));
+        let mut port = reconnecting_port(&present);
+
+        {
+            let mock = port.get_mut().unwrap();
+            mock.input.clear();
+            mock.eof = true;
+        }
+
+        let mut buf = [0u8; 1];
+        assert_eq!(port.read(&mut buf).unwrap_err().kind(), io::ErrorKind::ConnectionReset);
+        assert_eq!(port.read(&mut buf).unwrap(), 1);
+        assert_eq!(port.read(&mut []).unwrap(), 0);
+    }
+
+    #[test]
+    fn reconnecting_port_limits_reconnection_by_timeout() {
+        let present = Arc::new(AtomicBool::new(true));
+        let mut port = reconnecting_port(&present);
+
+        port.set_backoff(Backoff {
+            initial: Duration::from_millis(1),
+            max: Duration::from_millis(1),
+            max_attempts: None,
+        });
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let listener_events = events.clone();
+        port.on_event(move |event| listener_events.lock().unwrap().push(event.clone()));
+
+        port.set_read_timeout(Timeout::NonBlocking).unwrap();
+        port.set_write_timeout(Timeout::Duration(Duration::from_millis(20))).unwrap();
+
+        present.store(false, Ordering::SeqCst);
+        unplug(&port);
+
+        let mut buf = [0u8; 1];
+        assert_eq!(port.read(&mut buf).unwrap_err().kind(), io::ErrorKind::NotConnected);
+        assert_eq!(events.lock().unwrap().len(), 2);
+
+        assert_eq!(port.write(b"x").unwrap_err().kind(), io::ErrorKind::NotConnected);
+        assert!(events.lock().unwrap().len() > 3);
+    }
+
+    #[test]
+    fn reconnecting_port_reports_missing_device() {
+        let present = Arc::new(AtomicBool::new(true));
+        let mut port = reconnecting_port(&present);
+
+        present.store(false, Ordering::SeqCst);
+        unplug(&port);
+
+        assert_eq!(port.write(b"x").unwrap_err().kind(), io::ErrorKind::NotConnected);
+        assert!(!port.is_connected());
+
+        present.store(true, Ordering::SeqCst);
+        assert_eq!(port.write(b"x").unwrap(), 1);
+    }
+
+    #[test]
+    fn reconnecting_port_notifies_listener() {
+        let present = Arc::new(AtomicBool::new(true));
+        let mut port = reconnecting_port(&present);
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let listener_events = events.clone();
+        port.on_event(move |event| listener_events.lock().unwrap().push(event.clone()));
+
+        unplug(&port);
+        port.set_dtr(true).unwrap();
+
+        assert_eq!(*events.lock().unwrap(), vec![
+            ConnectionEvent::Disconnected(String::from("No such device")),
+            ConnectionEvent::Reconnected(1),
+        ]);
+    }
+
+    #[test]
+    fn by_id_has_serial_number_matches_serial_number() {
+        assert!(super::by_id_has_serial_number("usb-FTDI_FT232R_USB_UART_A9M9DV3R-if00-port0", "A9M9DV3R"));
+        assert!(!super::by_id_has_serial_number("usb-FTDI_FT232R_USB_UART_A9M9DV3R-if00-port0", "A9M9DV3"));
+        assert!(!super::by_id_has_serial_number("pci-0000:00:14.0-usb-0:1:1.0", "0"));
+    }
+
+    #[test]
+    fn by_id_has_serial_number_matches_sanitized_serial_number() {
+        assert!(super::by_id_has_serial_number("usb-Acme_Widget_SN_0042-if00", " SN  0042 "));
+        assert!(super::by_id_has_serial_number("usb-Acme_Widget_AB_12_34-if01-port0", "AB/12*34"));
+        assert!(!super::by_id_has_serial_number("usb-Acme_Widget_SN_0042-if00", "N 0042"));
+    }
+
+    #[test]
+    fn is_disconnected_ignores_busy_device() {
+        let busy = core::Error::new(core::ErrorKind::NoDevice, "Device or resource busy")
+                       .with_source(io::Error::from_raw_os_error(::libc::EBUSY));
+        let removed = core::Error::new(core::ErrorKind::NoDevice, "No such device")
+                          .with_source(io::Error::from_raw_os_error(::libc::ENODEV));
+        let io_error = core::Error::new(core::ErrorKind::Io(io::ErrorKind::Other), "Input/output error")
+                           .with_source(io::Error::from_raw_os_error(::libc::EIO));
+
+        assert!(!super::is_disconnected(&busy));
+        assert!(super::is_disconnected(&removed));
+        assert!(super::is_disconnected(&io_error));
+    }
+}
@@ -0,0 +1,290 @@
+// Copyright (c) 2015 David Cuddeback
+//
+// Permission is hereby granted, free of charge, to any person obtaining
+// a copy of this software and associated documentation files (the
+// "Software"), to deal in the Software without restriction, including
+// without limitation the rights to use, copy, modify, merge, publish,
+// distribute, sublicense, and/or sell copies of the Software, and to
+// permit persons to whom the Software is furnished to do so, subject to
+// the following conditions:
+//
+// The above copyright notice and this permission notice shall be
+// included in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND,
+// EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF
+// MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE
+// LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION
+// WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+//! Timed control signal sequences, such as those that reset a microcontroller or start its
+//! bootloader.
+//!
+//! A [`Sequence`](struct.Sequence.html) is a list of steps that is run on any `SerialPort`.
+//! Presets are provided for common development boards:
+//!
+//! ```no_run
+//! use serial::sequence::Sequence;
+//!
+//! let mut port = serial::open("/dev/ttyUSB0").unwrap();
+//! Sequence::esp_unix_tight_reset().run(&mut port).unwrap();
+//! ```
+//!
+//! Control signals are described by whether they're asserted, as with `SerialPort::set_dtr()`.
+//! Most USB serial adapters drive an asserted signal low.
+
+use core;
+
+use std::thread;
+use std::time::Duration;
+
+use core::{ModemControl, SerialPort};
+
+/// A step of a control signal sequence.
+#[derive(Debug,Copy,Clone,PartialEq,Eq)]
+pub enum Step {
+    /// Sets the DTR signal.
+    SetDtr(bool),
+
+    /// Sets the RTS signal.
+    SetRts(bool),
+
+    /// Sets the DTR and RTS signals together with `SerialPort::set_modem_control()`.
+    SetModemControl(ModemControl),
+
+    /// Waits for the given duration.
+    Wait(Duration),
+
+    /// Sends a break for the given duration.
+    Break(Duration),
+
+    /// Waits until all written data has been transmitted.
+    Flush,
+}
+
+/// A sequence of control signal steps.
+#[derive(Debug,Clone,PartialEq,Eq)]
+pub struct Sequence {
+    steps: Vec<Step>,
+}
+
+impl Sequence {
+    /// Creates a sequence from a list of steps.
+    pub fn new(steps: Vec<Step>) -> Self {
+        Sequence { steps: steps }
+    }
+
+    /// Returns the steps of the sequence.
+    pub fn steps(&self) -> &[Step] {
+        &self.steps
+    }
+
+    /// Runs the sequence on a port.
+    ///
+    /// Steps are run in order. The calling thread is blocked while waiting.
+    ///
+    /// ## Errors
+    ///
+    /// This function returns the first error from a step. The remaining steps aren't run, so
+    /// control signals may be left in an intermediate state.
+    pub fn run<P: SerialPort + ?Sized>(&self, port: &mut P) -> core::Result<()> {
+        for step in &self.steps {
+            match *step {
+                Step::SetDtr(level) => try!(port.set_dtr(level)),
+                Step::SetRts(level) => try!(port.set_rts(level)),
+                Step::SetModemControl(control) => try!(port.set_modem_control(control)),
+                Step::Wait(duration) => thread::sleep(duration),
+                Step::Break(duration) => {
+                    try!(port.set_break(true));
+                    thread::sleep(duration);
+                    try!(port.set_break(false));
+                }
+                Step::Flush => try!(port.flush()),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Resets an Arduino board with the auto-reset circuit, which starts its bootloader.
+    ///
+    /// The board is reset by asserting DTR, which is coupled to the reset line through a capacitor.
+    /// Both DTR and RTS are cleared first, so that asserting them produces an edge.
+    pub fn arduino_reset() -> Self {
+        Sequence::new(vec![
+            Step::SetModemControl(modem_control(false, false)),
+            Step::Wait(Duration::from_millis(250)),
+            Step::SetModemControl(modem_control(true, true)),
+            Step::Wait(Duration::from_millis(50)),
+        ])
+    }
+
+    /// Starts the bootloader of an ESP8266 or ESP32 board by changing DTR and RTS one at a time.
+    ///
+    /// This is esptool's `ClassicReset`, for boards whose auto-reset circuit connects RTS to EN
+    /// and DTR to GPIO0.
+    pub fn esp_classic_reset() -> Self {
+        Sequence::new(vec![
+            Step::SetDtr(false),
+            Step::SetRts(true),
+            Step::Wait(Duration::from_millis(100)),
+            Step::SetDtr(true),
+            Step::SetRts(false),
+            Step::Wait(Duration::from_millis(50)),
+            Step::SetDtr(false),
+        ])
+    }
+
+    /// Starts the bootloader of an ESP8266 or ESP32 board by changing DTR and RTS together.
+    ///
+    /// This is esptool's `UnixTightReset`, which avoids the intermediate states of
+    /// [`esp_classic_reset()`](#method.esp_classic_reset) that can reset some boards into the
+    /// wrong mode. It's only reliable on ports that implement `set_modem_control()` atomically.
+    pub fn esp_unix_tight_reset() -> Self {
+        Sequence::new(vec![
+            Step::SetModemControl(modem_control(false, false)),
+            Step::SetModemControl(modem_control(true, true)),
+            Step::SetModemControl(modem_control(false, true)),
+            Step::Wait(Duration::from_millis(100)),
+            Step::SetModemControl(modem_control(true, false)),
+            Step::Wait(Duration::from_millis(50)),
+            Step::SetModemControl(modem_control(false, false)),
+            Step::SetDtr(false),
+        ])
+    }
+
+    /// Resets an ESP8266 or ESP32 board into its application by pulsing RTS, which is connected to
+    /// EN.
+    pub fn esp_hard_reset() -> Self {
+        Sequence::new(vec![
+            Step::SetRts(true),
+            Step::Wait(Duration::from_millis(100)),
+            Step::SetRts(false),
+        ])
+    }
+
+    /// Starts the system memory bootloader of an STM32 microcontroller.
+    ///
+    /// This assumes that asserting DTR holds NRST low and asserting RTS drives BOOT0 high. The
+    /// microcontroller is reset with BOOT0 high, and BOOT0 is released once the bootloader has
+    /// started.
+    pub fn stm32_bootloader() -> Self {
+        Sequence::new(vec![
+            Step::SetModemControl(modem_control(true, true)),
+            Step::Wait(Duration::from_millis(100)),
+            Step::SetModemControl(modem_control(false, true)),
+            Step::Wait(Duration::from_millis(100)),
+            Step::SetRts(false),
+        ])
+    }
+
+    /// Resets an STM32 microcontroller into its application.
+    ///
+    /// This assumes the same wiring as [`stm32_bootloader()`](#method.stm32_bootloader).
+    pub fn stm32_reset() -> Self {
+        Sequence::new(vec![
+            Step::SetModemControl(modem_control(true, false)),
+            Step::Wait(Duration::from_millis(100)),
+            Step::SetModemControl(modem_control(false, false)),
+        ])
+    }
+}
+
+fn modem_control(dtr: bool, rts: bool) -> ModemControl {
+    ModemControl { dtr: dtr, rts: rts }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use core;
+
+    use std::time::{Duration, Instant};
+
+    use testing::{Event, MockPort};
+
+    use super::{Sequence, Step};
+
+    #[test]
+    fn run_performs_steps_in_order() {
+        let mut port = MockPort::default();
+
+        let sequence = Sequence::new(vec![
+            Step::SetDtr(true),
+            Step::Break(Duration::from_millis(1)),
+            Step::SetRts(false),
+            Step::Flush,
+        ]);
+
+        sequence.run(&mut port).unwrap();
+        assert_eq!(port.events, vec![
+            Event::Dtr(true),
+            Event::Break(true),
+            Event::Break(false),
+            Event::Rts(false),
+            Event::Flush,
+        ]);
+    }
+
+    #[test]
+    fn run_waits_between_steps() {
+        let mut port = MockPort::default();
+        let start = Instant::now();
+
+        Sequence::new(vec![Step::Wait(Duration::from_millis(20))]).run(&mut port).unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(20));
+    }
+
+    #[test]
+    fn run_stops_at_first_error() {
+        let mut port = MockPort { fail_rts: true, ..MockPort::default() };
+
+        let sequence = Sequence::new(vec![Step::SetDtr(true), Step::SetRts(true), Step::SetDtr(false)]);
+
+        assert_eq!(sequence.run(&mut port).unwrap_err().kind(), core::ErrorKind::NoDevice);
+        assert_eq!(port.events, vec![Event::Dtr(true)]);
+    }
+
+    #[test]
+    fn esp_unix_tight_reset_changes_signals_together() {
+        let mut port = MockPort::default();
+
+        Sequence::esp_unix_tight_reset().run(&mut port).unwrap();
+        assert_eq!(port.events, vec![
+            Event::ModemControl(false, false),
+            Event::ModemControl(true, true),
+            Event::ModemControl(false, true),
+            Event::ModemControl(true, false),
+            Event::ModemControl(false, false),
+            Event::Dtr(false),
+        ]);
+    }
+
+    #[test]
+    fn esp_classic_reset_holds_gpio0_low_while_leaving_reset() {
+        let mut port = MockPort::default();
+
+        Sequence::esp_classic_reset().run(&mut port).unwrap();
+        assert_eq!(port.events, vec![
+            Event::Dtr(false),
+            Event::Rts(true),
+            Event::Dtr(true),
+            Event::Rts(false),
+            Event::Dtr(false),
+        ]);
+    }
+
+    #[test]
+    fn stm32_bootloader_releases_reset_with_boot0_high() {
+        let mut port = MockPort::default();
+
+        Sequence::stm32_bootloader().run(&mut port).unwrap();
+        assert_eq!(port.events, vec![
+            Event::ModemControl(true, true),
+            Event::ModemControl(false, true),
+            Event::Rts(false),
+        ]);
+    }
+}
@@ -0,0 +1,277 @@
+// Copyright (c) 2015 David Cuddeback
+//
+// Permission is hereby granted, free of charge, to any person obtaining
+// a copy of this software and associated documentation files (the
+// "Software"), to deal in the Software without restriction, including
+// without limitation the rights to use, copy, modify, merge, publish,
+// distribute, sublicense, and/or sell copies of the Software, and to
+// permit persons to whom the Software is furnished to do so, subject to
+// the following conditions:
+//
+// The above copyright notice and this permission notice shall be
+// included in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND,
+// EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF
+// MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+// NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE
+// LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION
+// WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+//! A mock serial port shared by unit tests.
+
+use core;
+
+use std::cell::Cell;
+use std::cmp;
+use std::collections::VecDeque;
+use std::io;
+use std::time::Duration;
+
+use core::{SerialPort, SerialPortSettings, ModemControl, PortSettings, Timeout};
+
+/// An operation recorded by `MockPort`.
+#[derive(Debug,Clone,PartialEq,Eq)]
+pub enum Event {
+    Write(Vec<u8>),
+    Flush,
+    Configure,
+    ReadTimeout(Timeout),
+    WriteTimeout(Timeout),
+    Rts(bool),
+    Dtr(bool),
+    ModemControl(bool, bool),
+    Break(bool),
+}
+
+/// A port that returns scripted chunks of data from reads and records other operations.
+///
+/// Once the scripted data has been read, reads fail with the error for the read timeout, or
+/// return end of file if `eof` is set. `wait_transmit_complete()` is unsupported unless
+/// `transmit_time` is set, in which case it times out if its timeout is shorter. After `unplugged`
+/// is set, every operation fails as if the device had been removed.
+pub struct MockPort {
+    pub input: VecDeque<Vec<u8>>,
+    pub output: Vec<u8>,
+    pub events: Vec<Event>,
+    pub settings: PortSettings,
+    pub read_timeout: Timeout,
+    pub write_timeout: Timeout,
+    pub inter_byte_timeout: Option<u32>,
+    pub transmit_timeout: Option<Timeout>,
+    pub transmit_time: Option<Duration>,
+    pub eof: bool,
+    pub fail_writes: bool,
+    pub fail_rts: bool,
+    pub unplugged: Cell<bool>,
+}
+
+impl MockPort {
+    pub fn new(input: &[&[u8]]) -> Self {
+        MockPort {
+            input: input.iter().map(|chunk| chunk.to_vec()).collect(),
+            output: Vec::new(),
+            events: Vec::new(),
+            settings: default_settings(),
+            read_timeout: Timeout::Duration(Duration::from_millis(100)),
+            write_timeout: Timeout::Duration(Duration::from_millis(100)),
+            inter_byte_timeout: None,
+            transmit_timeout: None,
+            transmit_time: None,
+            eof: false,
+            fail_writes: false,
+            fail_rts: false,
+            unplugged: Cell::new(false),
+        }
+    }
+
+    fn check(&self) -> core::Result<()> {
+        if self.unplugged.get() {
+            return Err(core::Error::new(core::ErrorKind::NoDevice, "No such device"));
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for MockPort {
+    fn default() -> Self {
+        MockPort::new(&[])
+    }
+}
+
+impl io::Read for MockPort {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        try!(self.check());
+
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        let mut chunk = match self.input.pop_front() {
+            Some(chunk) => chunk,
+            None if self.eof => return Ok(0),
+            None => return Err(io::Error::new(self.read_timeout.error_kind(), "Operation timed out")),
+        };
+
+        let len = cmp::min(buf.len(), chunk.len());
+        buf[..len].copy_from_slice(&chunk[..len]);
+
+        if len < chunk.len() {
+            self.input.push_front(chunk.split_off(len));
+        }
+
+        Ok(len)
+    }
+}
+
+impl io::Write for MockPort {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        try!(self.check());
+
+        if self.fail_writes {
+            return Err(io::Error::new(io::ErrorKind::TimedOut, "Operation timed out"));
+        }
+
+        self.output.extend_from_slice(buf);
+        self.events.push(Event::Write(buf.to_vec()));
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        try!(self.check());
+        self.events.push(Event::Flush);
+        Ok(())
+    }
+}
+
+impl SerialPort for MockPort {
+    fn read_timeout(&self) -> Timeout {
+        self.read_timeout
+    }
+
+    fn write_timeout(&self) -> Timeout {
+        self.write_timeout
+    }
+
+    fn set_read_timeout(&mut self, timeout: Timeout) -> core::Result<()> {
+        try!(self.check());
+        self.read_timeout = timeout;
+        self.events.push(Event::ReadTimeout(timeout));
+        Ok(())
+    }
+
+    fn set_write_timeout(&mut self, timeout: Timeout) -> core::Result<()> {
+        try!(self.check());
+        self.write_timeout = timeout;
+        self.events.push(Event::WriteTimeout(timeout));
+        Ok(())
+    }
+
+    fn inter_byte_timeout(&self) -> Option<u32> {
+        self.inter_byte_timeout
+    }
+
+    fn set_inter_byte_timeout(&mut self, chars: Option<u32>) -> core::Result<()> {
+        try!(self.check());
+        self.inter_byte_timeout = chars;
+        Ok(())
+    }
+
+    fn configure(&mut self, settings: &PortSettings) -> core::Result<()> {
+        try!(self.check());
+        self.settings = *settings;
+        self.events.push(Event::Configure);
+        Ok(())
+    }
+
+    fn reconfigure(&mut self, setup: &Fn(&mut SerialPortSettings) -> core::Result<()>) -> core::Result<()> {
+        try!(self.check());
+
+        let mut settings = self.settings;
+        try!(setup(&mut settings));
+
+        self.settings = settings;
+        self.events.push(Event::Configure);
+        Ok(())
+    }
+
+    fn settings<'a>(&'a self) -> core::Result<Box<SerialPortSettings + 'a>> {
+        try!(self.check());
+        Ok(Box::new(self.settings))
+    }
+
+    fn set_rts(&mut self, level: bool) -> core::Result<()> {
+        try!(self.check());
+
+        if self.fail_rts {
+            return Err(core::Error::new(core::ErrorKind::NoDevice, "No such device"));
+        }
+
+        self.events.push(Event::Rts(level));
+        Ok(())
+    }
+
+    fn set_dtr(&mut self, level: bool) -> core::Result<()> {
+        try!(self.check());
+        self.events.push(Event::Dtr(level));
+        Ok(())
+    }
+
+    fn read_cts(&mut self) -> core::Result<bool> {
+        try!(self.check());
+        Ok(true)
+    }
+
+    fn read_dsr(&mut self) -> core::Result<bool> {
+        try!(self.check());
+        Ok(true)
+    }
+
+    fn read_ri(&mut self) -> core::Result<bool> {
+        try!(self.check());
+        Ok(false)
+    }
+
+    fn read_cd(&mut self) -> core::Result<bool> {
+        try!(self.check());
+        Ok(false)
+    }
+
+    fn set_modem_control(&mut self, control: ModemControl) -> core::Result<()> {
+        try!(self.check());
+        self.events.push(Event::ModemControl(control.dtr, control.rts));
+        Ok(())
+    }
+
+    fn set_break(&mut self, enabled: bool) -> core::Result<()> {
+        try!(self.check());
+        self.events.push(Event::Break(enabled));
+        Ok(())
+    }
+
+    // records the timeout and, unless `transmit_time` is set, lets callers fall back to flush()
+    fn wait_transmit_complete(&mut self, timeout: Timeout) -> core::Result<()> {
+        try!(self.check());
+        self.transmit_timeout = Some(timeout);
+
+        match (self.transmit_time, timeout) {
+            (None, _) => Err(core::Error::new(core::ErrorKind::Unsupported, "Operation not supported")),
+            (Some(time), Timeout::Duration(timeout)) if timeout < time => {
+                Err(core::Error::new(core::ErrorKind::Io(io::ErrorKind::TimedOut), "Operation timed out"))
+            }
+            (Some(_), _) => Ok(()),
+        }
+    }
+}
+
+pub fn default_settings() -> PortSettings {
+    PortSettings {
+        baud_rate: core::Baud9600,
+        char_size: core::Bits8,
+        parity: core::ParityNone,
+        stop_bits: core::Stop1,
+        flow_control: core::FlowNone,
+    }
+}